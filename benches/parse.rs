@@ -0,0 +1,57 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use dhcp_rs::dhcp_packet::DhcpPacket;
+use dhcp_rs::dhcp_view::DhcpPacketView;
+use std::hint::black_box;
+
+// Same format as the diff command: one hex packet per line, # comments.
+fn corpus() -> Vec<Vec<u8>> {
+    include_str!("corpus.hex").lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(|x| (0..x.len()).step_by(2).map(|i| u8::from_str_radix(&x[i..i + 2], 16).unwrap()).collect())
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let corpus = corpus();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(corpus.len() as u64));
+    group.bench_function("packet", |b| b.iter(|| {
+        for buf in &corpus {
+            black_box(DhcpPacket::parse(black_box(buf)).unwrap());
+        }
+    }));
+    group.bench_function("view", |b| b.iter(|| {
+        for buf in &corpus {
+            black_box(DhcpPacketView::parse(black_box(buf)).unwrap());
+        }
+    }));
+    // What a server does with each request before deciding on an answer.
+    group.bench_function("view_lookup", |b| b.iter(|| {
+        for buf in &corpus {
+            let (_, view) = DhcpPacketView::parse(black_box(buf)).unwrap();
+            black_box((view.message_type(), view.option(50), view.option(54), view.option(55), view.chaddr()));
+        }
+    }));
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let packets: Vec<_> = corpus().iter().map(|x| DhcpPacket::parse(x).unwrap().1).collect();
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(packets.len() as u64));
+    group.bench_function("packet", |b| {
+        let mut buf = Vec::with_capacity(1500);
+        b.iter(|| {
+            for packet in &packets {
+                buf.clear();
+                black_box(packet).encode(&mut buf).unwrap();
+                black_box(&buf);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, encode);
+criterion_main!(benches);
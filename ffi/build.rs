@@ -0,0 +1,13 @@
+use std::env;
+
+// Regenerates include/dhcp_rs.h, the header is committed so C users don't
+// need a Rust toolchain to build against a prebuilt library.
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("invalid cbindgen.toml");
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("unable to generate C header")
+        .write_to_file("include/dhcp_rs.h");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,139 @@
+/*
+ * C API for dhcp-rs.
+ *
+ * Functions returning int return DHCP_OK or one of the negative DHCP_ERR_*
+ * codes, see dhcp_strerror. Output buffers are passed as a pointer and a
+ * length: on entry *len holds the capacity of the buffer, on return the
+ * number of bytes needed. When that exceeds the capacity nothing is copied
+ * and DHCP_ERR_BUFFER_TOO_SMALL is returned, so passing NULL and 0 queries
+ * the size. Handles are not thread safe, use one per thread or lock.
+ */
+
+#ifndef DHCP_RS_H
+#define DHCP_RS_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define DHCP_OK 0
+
+#define DHCP_ERR_NULL -1
+
+#define DHCP_ERR_PARSE -2
+
+#define DHCP_ERR_NOT_FOUND -3
+
+#define DHCP_ERR_BUFFER_TOO_SMALL -4
+
+#define DHCP_ERR_INVALID -5
+
+#define DHCP_ERR_PANIC -6
+
+/**
+ * Opaque handle to a parsed or constructed packet, release it with
+ * dhcp_packet_free.
+ */
+typedef struct DhcpPacketHandle DhcpPacketHandle;
+
+/**
+ * The fixed BOOTP header. Addresses are in network byte order, sname and
+ * file are NUL terminated unless they fill the whole field.
+ */
+typedef struct DhcpHeader {
+  uint8_t op;
+  uint8_t htype;
+  uint8_t hlen;
+  uint8_t hops;
+  uint32_t xid;
+  uint16_t secs;
+  uint16_t flags;
+  uint8_t ciaddr[4];
+  uint8_t yiaddr[4];
+  uint8_t siaddr[4];
+  uint8_t giaddr[4];
+  uint8_t chaddr[16];
+  char sname[64];
+  char file[128];
+} DhcpHeader;
+
+/**
+ * Parses the DHCP payload in buf. On success *packet receives a new handle.
+ * On DHCP_ERR_PARSE the offset of the offending byte is stored in
+ * *error_offset, which may be NULL.
+ */
+int dhcp_packet_parse(const uint8_t *buf,
+                      size_t len,
+                      struct DhcpPacketHandle **packet,
+                      size_t *error_offset);
+
+/**
+ * Creates an empty packet with an Ethernet chaddr of 6 bytes. op is 1
+ * (BOOTREQUEST) or 2 (BOOTREPLY).
+ */
+int dhcp_packet_new(uint8_t op,
+                    uint32_t xid,
+                    const uint8_t *chaddr,
+                    struct DhcpPacketHandle **packet);
+
+/**
+ * Releases a handle, NULL is ignored.
+ */
+void dhcp_packet_free(struct DhcpPacketHandle *packet);
+
+int dhcp_packet_get_header(const struct DhcpPacketHandle *packet, struct DhcpHeader *header);
+
+/**
+ * Replaces the header fields, the options are left alone. Only the first
+ * 6 bytes of chaddr are kept, flags must be 0x0000 or 0x8000 and sname and
+ * file UTF-8. Nothing is changed when DHCP_ERR_INVALID is returned.
+ */
+int dhcp_packet_set_header(struct DhcpPacketHandle *packet, const struct DhcpHeader *header);
+
+/**
+ * Returns the DHCP message type (1 = DISCOVER ... 15 = LEASEQUERYDONE), or 0 for
+ * a plain BOOTP packet.
+ */
+int dhcp_packet_message_type(const struct DhcpPacketHandle *packet);
+
+/**
+ * Copies the codes of the options present, in ascending order.
+ */
+int dhcp_packet_option_codes(const struct DhcpPacketHandle *packet, uint8_t *codes, size_t *len);
+
+/**
+ * Copies the value of an option, without its code and length bytes.
+ */
+int dhcp_packet_get_option(const struct DhcpPacketHandle *packet,
+                           uint8_t code,
+                           uint8_t *value,
+                           size_t *len);
+
+/**
+ * Adds or replaces an option. The value is decoded like it would be when
+ * parsing, DHCP_ERR_INVALID is returned when that fails.
+ */
+int dhcp_packet_set_option(struct DhcpPacketHandle *packet,
+                           uint8_t code,
+                           const uint8_t *value,
+                           size_t len);
+
+int dhcp_packet_remove_option(struct DhcpPacketHandle *packet, uint8_t code);
+
+/**
+ * Encodes the packet as a UDP payload, see DhcpPacket::encode.
+ * DHCP_ERR_INVALID is returned when an option doesn't fit its 255 bytes, secs
+ * doesn't fit 16 bits or sname or file are longer than their field.
+ */
+int dhcp_packet_encode(const struct DhcpPacketHandle *packet, uint8_t *buf, size_t *len);
+
+/**
+ * Returns a static description of a status code.
+ */
+const char *dhcp_strerror(int status);
+
+#endif /* DHCP_RS_H */
@@ -0,0 +1,511 @@
+// The safety contract is the same for every function and is documented once
+// in the generated header: pointers are either NULL or valid for the given
+// length, and handles come from dhcp_packet_parse or dhcp_packet_new.
+#![allow(clippy::missing_safety_doc)]
+
+use dhcp_rs::dhcp_packet::{BootpOpcode, DhcpDuration, DhcpOption, DhcpOptionID, DhcpPacket, HardwareType, MacAddr};
+use std::net::Ipv4Addr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+pub const DHCP_OK: c_int = 0;
+pub const DHCP_ERR_NULL: c_int = -1;
+pub const DHCP_ERR_PARSE: c_int = -2;
+pub const DHCP_ERR_NOT_FOUND: c_int = -3;
+pub const DHCP_ERR_BUFFER_TOO_SMALL: c_int = -4;
+pub const DHCP_ERR_INVALID: c_int = -5;
+pub const DHCP_ERR_PANIC: c_int = -6;
+
+/// Opaque handle to a parsed or constructed packet, release it with
+/// dhcp_packet_free.
+pub struct DhcpPacketHandle(DhcpPacket);
+
+/// The fixed BOOTP header. Addresses are in network byte order, sname and
+/// file are NUL terminated unless they fill the whole field.
+#[repr(C)]
+pub struct DhcpHeader {
+    pub op: u8,
+    pub htype: u8,
+    pub hlen: u8,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: [u8; 4],
+    pub yiaddr: [u8; 4],
+    pub siaddr: [u8; 4],
+    pub giaddr: [u8; 4],
+    pub chaddr: [u8; 16],
+    pub sname: [c_char; 64],
+    pub file: [c_char; 128],
+}
+
+// Nothing may unwind into C, a panic is reported as DHCP_ERR_PANIC instead.
+fn guard<F: FnOnce() -> c_int>(f: F) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(DHCP_ERR_PANIC)
+}
+
+unsafe fn input<'a>(buf: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (buf.is_null(), len) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(buf, len)),
+    }
+}
+
+unsafe fn output(bytes: &[u8], out: *mut u8, len: *mut usize) -> c_int {
+    if len.is_null() {
+        return DHCP_ERR_NULL;
+    }
+    let capacity = *len;
+    *len = bytes.len();
+    if bytes.len() > capacity {
+        return DHCP_ERR_BUFFER_TOO_SMALL;
+    }
+    if !bytes.is_empty() {
+        if out.is_null() {
+            return DHCP_ERR_NULL;
+        }
+        ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+    }
+    DHCP_OK
+}
+
+fn parse_opcode(op: u8) -> Option<BootpOpcode> {
+    match op {
+        1 => Some(BootpOpcode::BootRequest),
+        2 => Some(BootpOpcode::BootReply),
+        _ => None,
+    }
+}
+
+fn octets(addr: Option<Ipv4Addr>) -> [u8; 4] {
+    addr.unwrap_or(Ipv4Addr::UNSPECIFIED).octets()
+}
+
+fn addr(octets: [u8; 4]) -> Option<Ipv4Addr> {
+    Some(Ipv4Addr::from(octets)).filter(|x| !x.is_unspecified())
+}
+
+fn copy_cstring(field: &mut [c_char], value: &str) {
+    for (d, s) in field.iter_mut().zip(value.bytes()) {
+        *d = s as c_char;
+    }
+}
+
+fn cstring(field: &[c_char]) -> Option<String> {
+    let bytes: Vec<u8> = field.iter().map(|x| *x as u8).take_while(|x| *x != 0).collect();
+    String::from_utf8(bytes).ok()
+}
+
+unsafe fn handle_ref<'a>(handle: *const DhcpPacketHandle) -> Option<&'a DhcpPacket> {
+    handle.as_ref().map(|x| &x.0)
+}
+
+unsafe fn handle_mut<'a>(handle: *mut DhcpPacketHandle) -> Option<&'a mut DhcpPacket> {
+    handle.as_mut().map(|x| &mut x.0)
+}
+
+/// Parses the DHCP payload in buf. On success *packet receives a new handle.
+/// On DHCP_ERR_PARSE the offset of the offending byte is stored in
+/// *error_offset, which may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_parse(buf: *const u8, len: usize, packet: *mut *mut DhcpPacketHandle,
+                                           error_offset: *mut usize) -> c_int {
+    guard(|| {
+        let buf = match input(buf, len) {
+            Some(buf) if !packet.is_null() => buf,
+            _ => return DHCP_ERR_NULL,
+        };
+        match DhcpPacket::parse(buf) {
+            Ok((_, p)) => {
+                *packet = Box::into_raw(Box::new(DhcpPacketHandle(p)));
+                DHCP_OK
+            }
+            Err(e) => {
+                if !error_offset.is_null() {
+                    *error_offset = match e {
+                        nom::Err::Error(e) | nom::Err::Failure(e) => e.offset(),
+                        nom::Err::Incomplete(_) => len,
+                    };
+                }
+                DHCP_ERR_PARSE
+            }
+        }
+    })
+}
+
+/// Creates an empty packet with an Ethernet chaddr of 6 bytes. op is 1
+/// (BOOTREQUEST) or 2 (BOOTREPLY).
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_new(op: u8, xid: u32, chaddr: *const u8,
+                                         packet: *mut *mut DhcpPacketHandle) -> c_int {
+    guard(|| {
+        let chaddr = match input(chaddr, 6) {
+            Some(chaddr) if !packet.is_null() => chaddr,
+            _ => return DHCP_ERR_NULL,
+        };
+        let opcode = match parse_opcode(op) {
+            Some(opcode) => opcode,
+            None => return DHCP_ERR_INVALID,
+        };
+        let mut mac = MacAddr::default();
+        mac.0.copy_from_slice(chaddr);
+        *packet = Box::into_raw(Box::new(DhcpPacketHandle(DhcpPacket::new(opcode, xid, mac))));
+        DHCP_OK
+    })
+}
+
+/// Releases a handle, NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_free(packet: *mut DhcpPacketHandle) {
+    if !packet.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(packet))));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_get_header(packet: *const DhcpPacketHandle, header: *mut DhcpHeader) -> c_int {
+    guard(|| {
+        let (p, header) = match (handle_ref(packet), header.as_mut()) {
+            (Some(p), Some(header)) => (p, header),
+            _ => return DHCP_ERR_NULL,
+        };
+        let mut chaddr = [0; 16];
+        chaddr[..6].copy_from_slice(&p.chaddr.0);
+        *header = DhcpHeader {
+            op: p.opcode.code(),
+            htype: p.htype.0,
+            hlen: p.hlen as u8,
+            hops: p.hops as u8,
+            xid: p.xid,
+            secs: p.secs.as_secs() as u16,
+            flags: if p.broadcast { 0x8000 } else { 0 },
+            ciaddr: octets(p.ciaddr),
+            yiaddr: octets(p.yiaddr),
+            siaddr: octets(p.siaddr),
+            giaddr: octets(p.giaddr),
+            chaddr,
+            sname: [0; 64],
+            file: [0; 128],
+        };
+        copy_cstring(&mut header.sname, &p.sname);
+        copy_cstring(&mut header.file, &p.file);
+        DHCP_OK
+    })
+}
+
+/// Replaces the header fields, the options are left alone. Only the first
+/// 6 bytes of chaddr are kept, flags must be 0x0000 or 0x8000 and sname and
+/// file UTF-8. Nothing is changed when DHCP_ERR_INVALID is returned.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_set_header(packet: *mut DhcpPacketHandle, header: *const DhcpHeader) -> c_int {
+    guard(|| {
+        let (p, header) = match (handle_mut(packet), header.as_ref()) {
+            (Some(p), Some(header)) => (p, header),
+            _ => return DHCP_ERR_NULL,
+        };
+        let (opcode, broadcast) = match (parse_opcode(header.op), header.flags) {
+            (Some(opcode), 0x8000) => (opcode, true),
+            (Some(opcode), 0) => (opcode, false),
+            _ => return DHCP_ERR_INVALID,
+        };
+        let (sname, file) = match (cstring(&header.sname), cstring(&header.file)) {
+            (Some(sname), Some(file)) => (sname, file),
+            _ => return DHCP_ERR_INVALID,
+        };
+        p.opcode = opcode;
+        p.htype = HardwareType(header.htype);
+        p.hlen = header.hlen.into();
+        p.hops = header.hops.into();
+        p.xid = header.xid;
+        p.secs = DhcpDuration::new(header.secs.into(), 0);
+        p.broadcast = broadcast;
+        p.ciaddr = addr(header.ciaddr);
+        p.yiaddr = addr(header.yiaddr);
+        p.siaddr = addr(header.siaddr);
+        p.giaddr = addr(header.giaddr);
+        p.chaddr.0.copy_from_slice(&header.chaddr[..6]);
+        p.sname = sname;
+        p.file = file;
+        DHCP_OK
+    })
+}
+
+/// Returns the DHCP message type (1 = DISCOVER ... 15 = LEASEQUERYDONE), or 0 for
+/// a plain BOOTP packet.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_message_type(packet: *const DhcpPacketHandle) -> c_int {
+    guard(|| match handle_ref(packet) {
+        Some(p) => p.message_type().map(|x| x.code().into()).unwrap_or(0),
+        None => DHCP_ERR_NULL,
+    })
+}
+
+/// Copies the codes of the options present, in ascending order.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_option_codes(packet: *const DhcpPacketHandle, codes: *mut u8,
+                                                  len: *mut usize) -> c_int {
+    guard(|| {
+        let p = match handle_ref(packet) {
+            Some(p) => p,
+            None => return DHCP_ERR_NULL,
+        };
+        let mut option_codes: Vec<u8> = p.options.keys().map(|x| x.code()).collect();
+        option_codes.sort_unstable();
+        output(&option_codes, codes, len)
+    })
+}
+
+/// Copies the value of an option, without its code and length bytes.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_get_option(packet: *const DhcpPacketHandle, code: u8, value: *mut u8,
+                                                len: *mut usize) -> c_int {
+    guard(|| {
+        let p = match handle_ref(packet) {
+            Some(p) => p,
+            None => return DHCP_ERR_NULL,
+        };
+        let option = match p.options.get(&DhcpOptionID::from(code)) {
+            Some(option) => option,
+            None => return DHCP_ERR_NOT_FOUND,
+        };
+        let mut buf = Vec::new();
+        match option.encode(&mut buf) {
+            Ok(()) => output(&buf[2..], value, len),
+            Err(_) => DHCP_ERR_INVALID,
+        }
+    })
+}
+
+/// Adds or replaces an option. The value is decoded like it would be when
+/// parsing, DHCP_ERR_INVALID is returned when that fails.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_set_option(packet: *mut DhcpPacketHandle, code: u8, value: *const u8,
+                                                len: usize) -> c_int {
+    guard(|| {
+        let (p, value) = match (handle_mut(packet), input(value, len)) {
+            (Some(p), Some(value)) => (p, value),
+            _ => return DHCP_ERR_NULL,
+        };
+        match DhcpOption::decode(code, value) {
+            Some(option) => {
+                p.options.insert(DhcpOptionID::from(code), option);
+                DHCP_OK
+            }
+            None => DHCP_ERR_INVALID,
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_remove_option(packet: *mut DhcpPacketHandle, code: u8) -> c_int {
+    guard(|| match handle_mut(packet) {
+        Some(p) => match p.options.remove(&DhcpOptionID::from(code)) {
+            Some(_) => DHCP_OK,
+            None => DHCP_ERR_NOT_FOUND,
+        },
+        None => DHCP_ERR_NULL,
+    })
+}
+
+/// Encodes the packet as a UDP payload, see DhcpPacket::encode.
+/// DHCP_ERR_INVALID is returned when an option doesn't fit its 255 bytes, secs
+/// doesn't fit 16 bits or sname or file are longer than their field.
+#[no_mangle]
+pub unsafe extern "C" fn dhcp_packet_encode(packet: *const DhcpPacketHandle, buf: *mut u8, len: *mut usize) -> c_int {
+    guard(|| {
+        let p = match handle_ref(packet) {
+            Some(p) => p,
+            None => return DHCP_ERR_NULL,
+        };
+        let mut encoded = Vec::new();
+        match p.encode(&mut encoded) {
+            Ok(()) => output(&encoded, buf, len),
+            Err(_) => DHCP_ERR_INVALID,
+        }
+    })
+}
+
+/// Returns a static description of a status code.
+#[no_mangle]
+pub extern "C" fn dhcp_strerror(status: c_int) -> *const c_char {
+    let message: &'static [u8] = match status {
+        DHCP_OK => b"success\0",
+        DHCP_ERR_NULL => b"NULL argument\0",
+        DHCP_ERR_PARSE => b"malformed packet\0",
+        DHCP_ERR_NOT_FOUND => b"option not present\0",
+        DHCP_ERR_BUFFER_TOO_SMALL => b"buffer too small\0",
+        DHCP_ERR_INVALID => b"invalid value\0",
+        DHCP_ERR_PANIC => b"internal error\0",
+        _ => b"unknown error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcp_rs::dhcp_packet::DhcpMessageType;
+    use std::ffi::CStr;
+
+    const CHADDR: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+
+    unsafe fn new_packet() -> *mut DhcpPacketHandle {
+        let mut packet = ptr::null_mut();
+        assert_eq!(dhcp_packet_new(1, 0x1234, CHADDR.as_ptr(), &mut packet), DHCP_OK);
+        packet
+    }
+
+    unsafe fn encode(packet: *const DhcpPacketHandle) -> Vec<u8> {
+        let mut len = 0;
+        assert_eq!(dhcp_packet_encode(packet, ptr::null_mut(), &mut len), DHCP_ERR_BUFFER_TOO_SMALL);
+        let mut buf = vec![0; len];
+        assert_eq!(dhcp_packet_encode(packet, buf.as_mut_ptr(), &mut len), DHCP_OK);
+        buf
+    }
+
+    #[test]
+    fn build_encode_and_parse() {
+        unsafe {
+            let packet = new_packet();
+            assert_eq!(dhcp_packet_set_option(packet, 53, [1].as_ptr(), 1), DHCP_OK);
+            assert_eq!(dhcp_packet_set_option(packet, 12, b"host".as_ptr(), 4), DHCP_OK);
+            let buf = encode(packet);
+            dhcp_packet_free(packet);
+
+            let mut parsed = ptr::null_mut();
+            assert_eq!(dhcp_packet_parse(buf.as_ptr(), buf.len(), &mut parsed, ptr::null_mut()), DHCP_OK);
+            assert_eq!(dhcp_packet_message_type(parsed), DhcpMessageType::DhcpDiscover.code().into());
+            let mut codes = [0; 8];
+            let mut len = codes.len();
+            assert_eq!(dhcp_packet_option_codes(parsed, codes.as_mut_ptr(), &mut len), DHCP_OK);
+            assert_eq!(codes[..len], [12, 53]);
+            let mut value = [0; 8];
+            let mut len = value.len();
+            assert_eq!(dhcp_packet_get_option(parsed, 12, value.as_mut_ptr(), &mut len), DHCP_OK);
+            assert_eq!(&value[..len], b"host");
+            assert_eq!(dhcp_packet_remove_option(parsed, 12), DHCP_OK);
+            assert_eq!(dhcp_packet_remove_option(parsed, 12), DHCP_ERR_NOT_FOUND);
+            assert_eq!(dhcp_packet_get_option(parsed, 12, value.as_mut_ptr(), &mut len), DHCP_ERR_NOT_FOUND);
+
+            let mut header: DhcpHeader = std::mem::zeroed();
+            assert_eq!(dhcp_packet_get_header(parsed, &mut header), DHCP_OK);
+            assert_eq!((header.op, header.xid, &header.chaddr[..6]), (1, 0x1234, &CHADDR[..]));
+            dhcp_packet_free(parsed);
+        }
+    }
+
+    #[test]
+    fn null_arguments() {
+        unsafe {
+            let mut packet = ptr::null_mut();
+            let mut len = 0;
+            assert_eq!(dhcp_packet_parse(ptr::null(), 10, &mut packet, ptr::null_mut()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_parse([0].as_ptr(), 1, ptr::null_mut(), ptr::null_mut()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_new(1, 0, ptr::null(), &mut packet), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_new(1, 0, CHADDR.as_ptr(), ptr::null_mut()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_message_type(ptr::null()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_get_header(ptr::null(), &mut std::mem::zeroed()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_set_header(ptr::null_mut(), ptr::null()), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_option_codes(ptr::null(), ptr::null_mut(), &mut len), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_get_option(ptr::null(), 53, ptr::null_mut(), &mut len), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_set_option(ptr::null_mut(), 53, [1].as_ptr(), 1), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_remove_option(ptr::null_mut(), 53), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_encode(ptr::null(), ptr::null_mut(), &mut len), DHCP_ERR_NULL);
+            dhcp_packet_free(ptr::null_mut());
+
+            let packet = new_packet();
+            assert_eq!(dhcp_packet_set_option(packet, 12, ptr::null(), 4), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_encode(packet, ptr::null_mut(), ptr::null_mut()), DHCP_ERR_NULL);
+            // A non empty output with room for it still needs a buffer.
+            len = 1024;
+            assert_eq!(dhcp_packet_encode(packet, ptr::null_mut(), &mut len), DHCP_ERR_NULL);
+            assert_eq!(dhcp_packet_set_header(packet, ptr::null()), DHCP_ERR_NULL);
+            dhcp_packet_free(packet);
+        }
+    }
+
+    #[test]
+    fn short_buffers() {
+        unsafe {
+            let packet = new_packet();
+            assert_eq!(dhcp_packet_set_option(packet, 12, b"host".as_ptr(), 4), DHCP_OK);
+            let mut value = [0xff; 4];
+            let mut len = 3;
+            assert_eq!(dhcp_packet_get_option(packet, 12, value.as_mut_ptr(), &mut len), DHCP_ERR_BUFFER_TOO_SMALL);
+            assert_eq!((len, value), (4, [0xff; 4]));
+            assert_eq!(dhcp_packet_get_option(packet, 12, value.as_mut_ptr(), &mut len), DHCP_OK);
+
+            let mut codes = [0; 1];
+            len = 0;
+            assert_eq!(dhcp_packet_option_codes(packet, codes.as_mut_ptr(), &mut len), DHCP_ERR_BUFFER_TOO_SMALL);
+            assert_eq!(len, 1);
+
+            let buf = encode(packet);
+            let mut parsed = ptr::null_mut();
+            let mut offset = 0;
+            assert_eq!(dhcp_packet_parse(buf.as_ptr(), 100, &mut parsed, &mut offset), DHCP_ERR_PARSE);
+            assert!(parsed.is_null());
+            assert!(offset <= 100);
+            dhcp_packet_free(packet);
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        unsafe {
+            let mut packet = ptr::null_mut();
+            assert_eq!(dhcp_packet_new(3, 0, CHADDR.as_ptr(), &mut packet), DHCP_ERR_INVALID);
+            let packet = new_packet();
+            // A Message Type must be one byte.
+            assert_eq!(dhcp_packet_set_option(packet, 53, [1, 2].as_ptr(), 2), DHCP_ERR_INVALID);
+
+            let mut header: DhcpHeader = std::mem::zeroed();
+            assert_eq!(dhcp_packet_get_header(packet, &mut header), DHCP_OK);
+            header.flags = 0x0001;
+            assert_eq!(dhcp_packet_set_header(packet, &header), DHCP_ERR_INVALID);
+            header.flags = 0x8000;
+            header.xid = 7;
+            header.sname[0] = 0xff_u8 as c_char;
+            assert_eq!(dhcp_packet_set_header(packet, &header), DHCP_ERR_INVALID);
+            assert_eq!(handle_ref(packet).unwrap().xid, 0x1234);
+            header.sname[0] = b's' as c_char;
+            header.file.iter_mut().for_each(|x| *x = b'f' as c_char);
+            assert_eq!(dhcp_packet_set_header(packet, &header), DHCP_OK);
+            let p = handle_ref(packet).unwrap();
+            assert_eq!((p.xid, p.broadcast, p.sname.as_str(), p.file.len()), (7, true, "s", 128));
+            dhcp_packet_free(packet);
+        }
+    }
+
+    #[test]
+    fn fields_that_dont_fit_are_not_encoded() {
+        unsafe {
+            let packet = new_packet();
+            handle_mut(packet).unwrap().sname = "s".repeat(65);
+            let mut len = 1024;
+            let mut buf = vec![0; len];
+            assert_eq!(dhcp_packet_encode(packet, buf.as_mut_ptr(), &mut len), DHCP_ERR_INVALID);
+            handle_mut(packet).unwrap().sname.clear();
+            handle_mut(packet).unwrap().secs = DhcpDuration::new(65_536, 0);
+            assert_eq!(dhcp_packet_encode(packet, buf.as_mut_ptr(), &mut len), DHCP_ERR_INVALID);
+            dhcp_packet_free(packet);
+        }
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        assert_eq!(guard(|| panic!("in a test")), DHCP_ERR_PANIC);
+        assert_eq!(guard(|| DHCP_OK), DHCP_OK);
+    }
+
+    #[test]
+    fn error_strings() {
+        let message = |status| unsafe { CStr::from_ptr(dhcp_strerror(status)) }.to_str().unwrap();
+        assert_eq!(message(DHCP_ERR_BUFFER_TOO_SMALL), "buffer too small");
+        assert_eq!(message(DHCP_ERR_PANIC), "internal error");
+        assert_eq!(message(42), "unknown error");
+    }
+}
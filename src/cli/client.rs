@@ -0,0 +1,157 @@
+use crate::cli::{
+    bind_udp, find_interface, interface_mac, parse_mac, recv_until, CliError, CliResult, Output, EXIT_FAILURE,
+    EXIT_OK, MAX_PACKET_SIZE,
+};
+use dhcp_rs::dhcp_frame::DHCP_SERVER_PORT;
+use dhcp_rs::dhcp_packet::{
+    BootpOpcode, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpOptionIDs, DhcpPacket, MacAddr,
+};
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, clap::Args)]
+pub struct ClientArgs {
+    /// Hardware address to use, defaults to the MAC address of --interface
+    #[arg(long)]
+    pub chaddr: Option<String>,
+    #[arg(long)]
+    pub hostname: Option<String>,
+    /// Address to ask the server for
+    #[arg(long)]
+    pub requested_ip: Option<Ipv4Addr>,
+    /// Seconds to wait for each answer
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+    /// How many times to send DISCOVER and REQUEST before giving up
+    #[arg(long, default_value_t = 3)]
+    pub retries: u32,
+    /// Release the lease again once it is acknowledged
+    #[arg(long)]
+    pub release: bool,
+}
+
+struct Client<'a> {
+    output: &'a Output,
+    args: &'a ClientArgs,
+    socket: UdpSocket,
+    local: SocketAddrV4,
+    chaddr: MacAddr,
+    xid: u32,
+}
+
+impl<'a> Client<'a> {
+    fn request(&self, message_type: DhcpMessageType) -> DhcpPacket {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, self.xid, self.chaddr);
+        // Without an address the client can't receive unicast replies.
+        packet.broadcast = true;
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        if let Some(hostname) = &self.args.hostname {
+            packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName(hostname.clone()));
+        }
+        let requested = vec![DhcpOptionID::SubnetMask, DhcpOptionID::Router, DhcpOptionID::DNSserver,
+                             DhcpOptionID::DomainName, DhcpOptionID::LeaseTime];
+        packet.options.insert(DhcpOptionID::ParameterRequestList,
+                              DhcpOption::ParameterRequestList(DhcpOptionIDs::from(&requested)));
+        packet
+    }
+
+    fn send(&self, packet: &DhcpPacket, dst: SocketAddrV4) -> Result<(), CliError> {
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).map_err(|e| CliError::new(format!("encoding the packet: {}", e)))?;
+        self.output.packet(&self.output.udp_origin(format!("-> {}", dst), self.local.into(), dst.into()), &buf, packet);
+        self.socket.send_to(&buf, dst).map_err(|e| CliError::new(format!("sending to {}: {}", dst, e)))?;
+        self.output.sent(self.local, dst, &buf, Some(packet));
+        Ok(())
+    }
+
+    // Sends the packet until a reply to it arrives or the retries run out.
+    fn exchange(&self, packet: &DhcpPacket, expected: &[DhcpMessageType]) -> Result<Option<DhcpPacket>, CliError> {
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        for _ in 0..self.args.retries.max(1) {
+            self.send(packet, SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_SERVER_PORT))?;
+            let deadline = Instant::now() + Duration::from_secs(self.args.timeout);
+            while let Some((length, src_addr)) = recv_until(&self.socket, deadline, &mut buf)? {
+                let reply = DhcpPacket::parse(&buf[..length]).ok().map(|(_, x)| x);
+                self.output.record_received(src_addr, self.local, &buf[..length], reply.as_ref());
+                let reply = match reply {
+                    Some(reply) => reply,
+                    None => continue,
+                };
+                if reply.opcode != BootpOpcode::BootReply || reply.xid != self.xid {
+                    continue;
+                }
+                if reply.message_type().is_some_and(|x| expected.contains(x)) {
+                    let origin = self.output.udp_origin(src_addr.to_string(), src_addr, self.local.into());
+                    self.output.packet(&origin, &buf[..length], &reply);
+                    return Ok(Some(reply));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Goes through DISCOVER, OFFER, REQUEST and ACK and prints the lease, the
+// interface itself is left alone.
+pub fn client(output: &Output, args: &ClientArgs, interface: Option<&str>, port: u16) -> CliResult {
+    let chaddr = match (&args.chaddr, interface) {
+        (Some(chaddr), _) => parse_mac(chaddr)?,
+        (None, Some(name)) => interface_mac(&find_interface(name)?)?,
+        (None, None) => return Err(CliError::usage("client needs --chaddr or --interface")),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let xid = now.subsec_nanos() ^ process::id();
+    let local = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
+    let socket = bind_udp(local)?;
+    let client = Client { output, args, socket, local, chaddr, xid };
+
+    let mut discover = client.request(DhcpMessageType::DhcpDiscover);
+    if let Some(addr) = args.requested_ip {
+        discover.options.insert(DhcpOptionID::RequestedIpAddr, DhcpOption::RequestedIpAddr(addr));
+    }
+    let offer = match client.exchange(&discover, &[DhcpMessageType::DhcpOffer])? {
+        Some(offer) => offer,
+        None => {
+            output.error("client", "no offer received");
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    let (offered, server_id) = match (offer.yiaddr, offer.options.get(&DhcpOptionID::ServerID)) {
+        (Some(addr), Some(DhcpOption::ServerID(server_id))) => (addr, *server_id),
+        _ => {
+            output.error("client", "offer without an address or server identifier");
+            return Ok(EXIT_FAILURE);
+        }
+    };
+
+    let mut request = client.request(DhcpMessageType::DhcpRequest);
+    request.options.insert(DhcpOptionID::RequestedIpAddr, DhcpOption::RequestedIpAddr(offered));
+    request.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(server_id));
+    let ack = match client.exchange(&request, &[DhcpMessageType::DhcpAck, DhcpMessageType::DhcpNak])? {
+        Some(ack) => ack,
+        None => {
+            output.error("client", "no answer to the request");
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if ack.message_type() == Some(&DhcpMessageType::DhcpNak) {
+        output.error("client", format!("{} refused {}", server_id, offered));
+        return Ok(EXIT_FAILURE);
+    }
+    outln!("lease {} from {}", offered, server_id);
+    let mut options: Vec<_> = ack.options.iter().filter(|(x, _)| **x != DhcpOptionID::MsgType).collect();
+    options.sort_by_key(|(x, _)| x.code());
+    for (option_id, option) in options {
+        outln!("    {}: {}", option_id, option);
+    }
+
+    if args.release {
+        let mut release = DhcpPacket::new(BootpOpcode::BootRequest, xid, chaddr);
+        release.ciaddr = Some(offered);
+        release.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpRelease));
+        release.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(server_id));
+        client.send(&release, SocketAddrV4::new(server_id, DHCP_SERVER_PORT))?;
+    }
+    Ok(EXIT_OK)
+}
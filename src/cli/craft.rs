@@ -0,0 +1,478 @@
+use crate::cli::input::{parse_hex_digits, read_input};
+use crate::cli::sniff::open_channel;
+use crate::cli::{
+    bind_udp, find_interface, interface_mac, parse_mac, recv_until, CliError, CliResult, Origin, Output, EXIT_OK,
+    MAX_PACKET_SIZE,
+};
+use dhcp_rs::dhcp_frame::{DhcpFrameError, DhcpFrameHeader, DhcpLinkType, DHCP_MAX_UDP_PAYLOAD};
+use dhcp_rs::dhcp_packet::{
+    BootpOpcode, DhcpDuration, DhcpMessageType, DhcpOption, DhcpOptionOther, DhcpPacket, HardwareType, MacAddr,
+    ParseMode,
+};
+use pnet::datalink::NetworkInterface;
+use serde_json::{Map, Value};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, clap::Args)]
+pub struct CraftArgs {
+    /// Packets described in JSON or YAML, a list for more than one, - for stdin
+    #[arg(long = "from", value_name = "FILE")]
+    pub file: Option<String>,
+    /// Field to set in every packet, such as xid=0x1234, msgtype=DISCOVER or hlen=20
+    #[arg(long = "set", value_name = "FIELD=VALUE")]
+    pub fields: Vec<String>,
+    /// Option to append to every packet, such as 12=text:host, 55=hex:010306 or 50/9=ip:10.0.0.5
+    #[arg(long = "option", value_name = "CODE[/LENGTH]=TYPE:VALUE")]
+    pub options: Vec<String>,
+    /// Destination address
+    #[arg(long, default_value = "255.255.255.255:67")]
+    pub to: SocketAddrV4,
+    /// Source address, bound for UDP and written into raw frames
+    #[arg(long)]
+    pub src: Option<SocketAddrV4>,
+    /// Send as Ethernet frames on --interface instead of from a UDP socket
+    #[arg(long)]
+    pub raw: bool,
+    /// Destination MAC address of raw frames
+    #[arg(long, default_value = "ff:ff:ff:ff:ff:ff", requires = "raw")]
+    pub dst_mac: String,
+    /// Seconds to wait for replies after the last packet
+    #[arg(long, default_value_t = 2)]
+    pub wait: u64,
+    /// Only print the packets, don't send them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+// The fields a packet description may have, see craft_packet.
+const CRAFT_FIELDS: [&str; 23] = [
+    "op", "htype", "hlen", "hops", "xid", "secs", "flags", "broadcast", "ciaddr", "yiaddr", "siaddr", "giaddr",
+    "chaddr", "sname", "file", "cookie", "msgtype", "options", "sname_options", "file_options", "end", "pad_to",
+    "truncate",
+];
+const CRAFT_SNAME: Range<usize> = 44..108;
+const CRAFT_FILE: Range<usize> = 108..236;
+const CRAFT_HEADER_LEN: usize = 240;
+const CRAFT_MIN_LEN: usize = 300;
+
+fn number(field: &str, value: &Value, max: u64) -> Result<u64, String> {
+    let number = match value {
+        Value::Number(x) => x.as_u64(),
+        Value::String(x) => match x.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => x.parse().ok(),
+        },
+        _ => None,
+    };
+    number.filter(|x| *x <= max).ok_or_else(|| format!("{} must be a number up to {}, not {}", field, max, value))
+}
+
+fn text<'a>(field: &str, value: &'a Value) -> Result<&'a str, String> {
+    value.as_str().ok_or_else(|| format!("{} must be a string, not {}", field, value))
+}
+
+fn flag(field: &str, value: &Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(x) => Ok(*x),
+        Value::String(x) if x == "true" || x == "false" => Ok(x == "true"),
+        _ => Err(format!("{} must be true or false, not {}", field, value)),
+    }
+}
+
+fn address(field: &str, value: &Value) -> Result<Ipv4Addr, String> {
+    text(field, value)?.parse().map_err(|_| format!("{} must be an IPv4 address, not {}", field, value))
+}
+
+fn hex(field: &str, value: &str) -> Result<Vec<u8>, String> {
+    parse_hex_digits(&value.replace([':', ' '], "")).ok_or_else(|| format!("{} must be hex, not {}", field, value))
+}
+
+fn message_type(value: &Value) -> Result<u8, String> {
+    match value.as_str().and_then(DhcpMessageType::from_name) {
+        Some(message_type) => Ok(message_type.code()),
+        None => Ok(number("msgtype", value, u8::MAX as u64)? as u8),
+    }
+}
+
+// An option as `CODE[/LENGTH]=TYPE:VALUE`, the type being hex, text, ip (a
+// comma separated list), u8, u16 or u32. LENGTH replaces the real length and
+// `0` or `255` alone make a Pad or End.
+fn parse_option_spec(spec: &str) -> Result<Value, String> {
+    let mut option = Map::new();
+    let (code, value) = match spec.split_once('=') {
+        Some((code, value)) => (code, Some(value)),
+        None => (spec, None),
+    };
+    let (code, length) = match code.split_once('/') {
+        Some((code, length)) => (code, Some(length)),
+        None => (code, None),
+    };
+    option.insert("code".into(), code.into());
+    if let Some(length) = length {
+        option.insert("length".into(), length.into());
+    }
+    if let Some(value) = value {
+        let (kind, value) = value.split_once(':').ok_or_else(|| format!("option {} needs TYPE:VALUE", spec))?;
+        let value = match kind {
+            "ip" => Value::Array(value.split(',').map(Value::from).collect()),
+            _ => value.into(),
+        };
+        option.insert(kind.into(), value);
+    }
+    Ok(Value::Object(option))
+}
+
+// An option as the encoder writes it, with whatever length was asked for.
+fn encode_option(buf: &mut Vec<u8>, option: &Value) -> Result<(), String> {
+    let option = match option {
+        Value::String(spec) => parse_option_spec(spec)?,
+        option => option.clone(),
+    };
+    let option = option.as_object().ok_or_else(|| format!("option must be an object or a string, not {}", option))?;
+    let code = number("code", option.get("code").ok_or("option without a code")?, u8::MAX as u64)? as u8;
+    let mut value = Vec::new();
+    let mut has_value = false;
+    for (kind, x) in option.iter().filter(|(x, _)| *x != "code" && *x != "length") {
+        has_value = true;
+        match kind.as_str() {
+            "hex" => value.extend(hex("hex", text(kind, x)?)?),
+            "text" => value.extend_from_slice(text(kind, x)?.as_bytes()),
+            "ip" => match x {
+                Value::Array(list) => for x in list {
+                    value.extend_from_slice(&address(kind, x)?.octets());
+                },
+                x => value.extend_from_slice(&address(kind, x)?.octets()),
+            },
+            "u8" => value.push(number(kind, x, u8::MAX as u64)? as u8),
+            "u16" => value.extend_from_slice(&(number(kind, x, u16::MAX as u64)? as u16).to_be_bytes()),
+            "u32" => value.extend_from_slice(&(number(kind, x, u32::MAX as u64)? as u32).to_be_bytes()),
+            "bytes" => for x in x.as_array().ok_or("bytes must be a list of numbers")? {
+                value.push(number(kind, x, u8::MAX as u64)? as u8);
+            },
+            _ => return Err(format!("option {} has an unknown type {}, expected hex, text, ip, u8, u16, u32 or bytes",
+                                    code, kind)),
+        }
+    }
+    let length = option.get("length").map(|x| number("length", x, u8::MAX as u64)).transpose()?;
+    if (code == 0 || code == 255) && !has_value && length.is_none() {
+        buf.push(code);
+        return Ok(());
+    }
+    if value.len() > u8::MAX as usize {
+        return Err(format!("option {} is {} bytes long, options hold at most 255", code, value.len()));
+    }
+    let start = buf.len();
+    DhcpOption::Other(DhcpOptionOther { option_id: code, option: value.into() }).encode(buf)
+        .map_err(|e| e.to_string())?;
+    if let Some(length) = length {
+        buf[start + 1] = length as u8;
+    }
+    Ok(())
+}
+
+fn encode_options(buf: &mut Vec<u8>, field: &str, options: &Value) -> Result<(), String> {
+    let options = options.as_array().ok_or_else(|| format!("{} must be a list of options", field))?;
+    options.iter().try_for_each(|x| encode_option(buf, x))
+}
+
+// Fills the sname or file field with options, for Option Overload.
+fn overload(buf: &mut [u8], field: &str, options: &Value) -> Result<(), String> {
+    let mut encoded = Vec::new();
+    encode_options(&mut encoded, field, options)?;
+    if encoded.len() > buf.len() {
+        return Err(format!("{} take {} bytes, the field holds {}", field, encoded.len(), buf.len()));
+    }
+    buf[..encoded.len()].copy_from_slice(&encoded);
+    buf[encoded.len()..].fill(0);
+    Ok(())
+}
+
+// The packet a description asks for. The header is encoded from a
+// DhcpPacket and the options are written as given, in order, so duplicates,
+// wrong lengths and missing ends make it onto the wire as they are.
+pub fn craft_packet(description: &Map<String, Value>) -> Result<Vec<u8>, String> {
+    if let Some(field) = description.keys().find(|x| !CRAFT_FIELDS.contains(&x.as_str())) {
+        return Err(format!("unknown field {}, expected one of {}", field, CRAFT_FIELDS.join(", ")));
+    }
+    let get = |field: &str| description.get(field);
+    let xid = get("xid").map(|x| number("xid", x, u32::MAX as u64)).transpose()?.unwrap_or(0) as u32;
+    let chaddr = match get("chaddr") {
+        Some(x) => parse_mac(text("chaddr", x)?).map_err(|e| e.message)?,
+        None => MacAddr::default(),
+    };
+    let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, xid, chaddr);
+    if let Some(x) = get("htype") {
+        packet.htype = HardwareType(number("htype", x, u8::MAX as u64)? as u8);
+    }
+    if let Some(x) = get("hlen") {
+        packet.hlen = number("hlen", x, u8::MAX as u64)? as usize;
+    }
+    if let Some(x) = get("hops") {
+        packet.hops = number("hops", x, u8::MAX as u64)? as usize;
+    }
+    if let Some(x) = get("secs") {
+        packet.secs = DhcpDuration::new(number("secs", x, u16::MAX as u64)?, 0);
+    }
+    if let Some(x) = get("broadcast") {
+        packet.broadcast = flag("broadcast", x)?;
+    }
+    packet.ciaddr = get("ciaddr").map(|x| address("ciaddr", x)).transpose()?;
+    packet.yiaddr = get("yiaddr").map(|x| address("yiaddr", x)).transpose()?;
+    packet.siaddr = get("siaddr").map(|x| address("siaddr", x)).transpose()?;
+    packet.giaddr = get("giaddr").map(|x| address("giaddr", x)).transpose()?;
+    if let Some(x) = get("sname") {
+        packet.sname = text("sname", x)?.to_string();
+    }
+    if let Some(x) = get("file") {
+        packet.file = text("file", x)?.to_string();
+    }
+    let mut buf = Vec::new();
+    packet.encode(&mut buf).map_err(|e| e.to_string())?;
+    buf.truncate(CRAFT_HEADER_LEN);
+
+    if let Some(x) = get("op") {
+        buf[0] = match x.as_str().map(|x| x.to_ascii_lowercase()).as_deref() {
+            Some("request") => BootpOpcode::BootRequest.code(),
+            Some("reply") => BootpOpcode::BootReply.code(),
+            _ => number("op", x, u8::MAX as u64)? as u8,
+        };
+    }
+    if let Some(x) = get("flags") {
+        buf[10..12].copy_from_slice(&(number("flags", x, u16::MAX as u64)? as u16).to_be_bytes());
+    }
+    if let Some(x) = get("cookie") {
+        let cookie = hex("cookie", text("cookie", x)?)?;
+        if cookie.len() != 4 {
+            return Err(format!("cookie must be 4 bytes, not {}", cookie.len()));
+        }
+        buf[236..CRAFT_HEADER_LEN].copy_from_slice(&cookie);
+    }
+    if let Some(x) = get("sname_options") {
+        overload(&mut buf[CRAFT_SNAME], "sname_options", x)?;
+    }
+    if let Some(x) = get("file_options") {
+        overload(&mut buf[CRAFT_FILE], "file_options", x)?;
+    }
+
+    if let Some(x) = get("msgtype") {
+        buf.extend_from_slice(&[53, 1, message_type(x)?]);
+    }
+    if let Some(x) = get("options") {
+        encode_options(&mut buf, "options", x)?;
+    }
+    if get("end").map(|x| flag("end", x)).transpose()?.unwrap_or(true) {
+        buf.push(255);
+    }
+    let pad_to = get("pad_to").map(|x| number("pad_to", x, DHCP_MAX_UDP_PAYLOAD as u64)).transpose()?;
+    buf.resize(buf.len().max(pad_to.unwrap_or(CRAFT_MIN_LEN as u64) as usize), 0);
+    if let Some(x) = get("truncate") {
+        buf.truncate(number("truncate", x, DHCP_MAX_UDP_PAYLOAD as u64)? as usize);
+    }
+    Ok(buf)
+}
+
+// The descriptions in --from with --set and --option applied to each.
+fn descriptions(args: &CraftArgs) -> Result<Vec<Map<String, Value>>, CliError> {
+    let mut descriptions = match &args.file {
+        Some(path) => {
+            let text = read_input(path).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+            // YAML is a superset of JSON, one parser takes both.
+            let value: Value = serde_yaml::from_slice(&text).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+            let values = match value {
+                Value::Array(values) => values,
+                value => vec![value],
+            };
+            values.into_iter()
+                .map(|x| match x {
+                    Value::Object(x) => Ok(x),
+                    x => Err(CliError::new(format!("{}: expected a packet description, not {}", path, x))),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        None => vec![Map::new()],
+    };
+    for description in &mut descriptions {
+        for field in &args.fields {
+            let (name, value) = field.split_once('=')
+                .ok_or_else(|| CliError::usage(format!("--set {} needs FIELD=VALUE", field)))?;
+            description.insert(name.to_string(), value.into());
+        }
+        if !args.options.is_empty() {
+            let options = description.entry("options").or_insert_with(|| Value::Array(Vec::new()));
+            let options = options.as_array_mut().ok_or_else(|| CliError::new("options must be a list"))?;
+            options.extend(args.options.iter().map(|x| Value::from(x.as_str())));
+        }
+    }
+    Ok(descriptions)
+}
+
+fn send_udp(output: &Output, args: &CraftArgs, packets: &[Vec<u8>], port: u16) -> CliResult {
+    let local = args.src.unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    let socket = bind_udp(local)?;
+    for buf in packets {
+        output.decode(&output.udp_origin(format!("-> {}", args.to), local.into(), args.to.into()), buf);
+        socket.send_to(buf, args.to).map_err(|e| CliError::new(format!("sending to {}: {}", args.to, e)))?;
+        output.sent(local, args.to, buf, DhcpPacket::parse(buf).ok().map(|(_, x)| x).as_ref());
+    }
+    let deadline = Instant::now() + Duration::from_secs(args.wait);
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    while let Some((length, src_addr)) = recv_until(&socket, deadline, &mut buf)? {
+        output.received(src_addr, local, &buf[..length]);
+    }
+    Ok(EXIT_OK)
+}
+
+// Frames go out as they are, replies are the BOOTREPLY frames carrying the
+// transaction ID of one of them.
+fn send_raw(output: &Output, args: &CraftArgs, packets: &[Vec<u8>], interface: &NetworkInterface, port: u16)
+            -> CliResult {
+    let (mut tx, mut rx) = open_channel(interface)?;
+    let src = args.src.unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+    let header = DhcpFrameHeader {
+        src_mac: interface_mac(interface)?,
+        dst_mac: parse_mac(&args.dst_mac)?,
+        vlan: None,
+        src_ip: *src.ip(),
+        dst_ip: *args.to.ip(),
+        src_port: src.port(),
+        dst_port: args.to.port(),
+    };
+    let mut xids = Vec::new();
+    for buf in packets {
+        let mut frame = Vec::new();
+        header.encode(buf, &mut frame).map_err(|e| CliError::new(format!("encoding the frame: {}", e)))?;
+        output.decode(&Origin::frame(format!("-> {}", args.to), &header), buf);
+        tx.send_to(&frame, None)
+            .unwrap_or_else(|| Err(io::Error::other("no room in the send buffer")))
+            .map_err(|e| CliError::new(format!("sending on {}: {}", interface.name, e)))?;
+        output.record_captured(&interface.name, &frame, buf, DhcpPacket::parse(buf).ok().map(|(_, x)| x).as_ref());
+        xids.extend(buf.get(4..8).map(|x| [x[0], x[1], x[2], x[3]]));
+    }
+    let deadline = Instant::now() + Duration::from_secs(args.wait);
+    while Instant::now() < deadline {
+        let frame = match rx.next() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(CliError::new(format!("capturing on {}: {}", interface.name, e))),
+        };
+        let (header, payload) =
+            match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, frame, ParseMode::Lenient) {
+                Ok(x) => x,
+                Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) |
+                Err(DhcpFrameError::WrongPorts { .. }) => continue,
+                Err(e) => {
+                    output.debug(format!("{}: {}", interface.name, e));
+                    continue;
+                }
+            };
+        let reply = payload.first() == Some(&BootpOpcode::BootReply.code()) &&
+            payload.get(4..8).is_some_and(|x| xids.iter().any(|xid| xid == x));
+        if reply {
+            output.captured(&interface.name, frame, &header, payload);
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+// Builds packets from descriptions and flags, sends them and prints the
+// replies. The packets are printed as the decoder sees them, malformed ones
+// as the error they cause.
+pub fn craft(output: &Output, args: &CraftArgs, interface: Option<&str>, port: u16) -> CliResult {
+    let descriptions = descriptions(args)?;
+    let packets = descriptions.iter()
+        .enumerate()
+        .map(|(i, x)| craft_packet(x).map_err(|e| match descriptions.len() {
+            1 => CliError::usage(e),
+            _ => CliError::usage(format!("packet {}: {}", i + 1, e)),
+        }))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.dry_run {
+        for (i, buf) in packets.iter().enumerate() {
+            output.decode(&Origin::new(format!("#{}", i + 1)), buf);
+        }
+        return Ok(EXIT_OK);
+    }
+    match (args.raw, interface) {
+        (true, Some(name)) => send_raw(output, args, &packets, &find_interface(name)?, port),
+        (true, None) => Err(CliError::usage("--raw needs --interface")),
+        (false, _) => send_udp(output, args, &packets, port),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn craft(description: Value) -> Result<Vec<u8>, String> {
+        craft_packet(description.as_object().unwrap())
+    }
+
+    #[test]
+    fn header_fields() {
+        let buf = craft(json!({"op": "reply", "xid": "0x01020304", "flags": 0x8000, "chaddr": "02:00:00:00:00:01",
+                               "ciaddr": "192.0.2.10", "sname": "server"})).unwrap();
+        assert_eq!(buf.len(), CRAFT_MIN_LEN);
+        assert_eq!(buf[..8], [2, 1, 6, 0, 1, 2, 3, 4]);
+        assert_eq!(buf[10..16], [0x80, 0, 192, 0, 2, 10]);
+        assert_eq!(buf[28..34], [2, 0, 0, 0, 0, 1]);
+        assert_eq!(buf[CRAFT_SNAME][..7], *b"server\0");
+        assert_eq!(buf[236..CRAFT_HEADER_LEN], [99, 130, 83, 99]);
+        assert_eq!(buf[CRAFT_HEADER_LEN], 255);
+    }
+
+    #[test]
+    fn options_are_written_as_given() {
+        let buf = craft(json!({"msgtype": "discover", "options": ["12=text:a", "12=text:b",
+            {"code": 50, "length": 9, "ip": "10.0.0.5"}, "61=hex:01:02", "0", {"code": 3, "u16": 1}]})).unwrap();
+        assert_eq!(buf[CRAFT_HEADER_LEN..CRAFT_HEADER_LEN + 26], [
+            53, 1, 1, 12, 1, b'a', 12, 1, b'b', 50, 9, 10, 0, 0, 5, 61, 2, 1, 2, 0, 3, 2, 0, 1, 255, 0]);
+        assert_eq!(craft(json!({"options": ["50/9=ip:10.0.0.5,10.0.0.6"]})).unwrap()[CRAFT_HEADER_LEN..CRAFT_HEADER_LEN + 11],
+                   [50, 9, 10, 0, 0, 5, 10, 0, 0, 6, 255]);
+        assert!(craft(json!({"options": ["12=word:a"]})).unwrap_err().contains("unknown type word"));
+        assert!(craft(json!({"options": [format!("12=hex:{}", "00".repeat(256))]})).unwrap_err()
+            .contains("256 bytes long"));
+    }
+
+    #[test]
+    fn overloaded_options() {
+        let buf = craft(json!({"sname_options": ["12=text:host"], "file_options": [{"code": 15, "text": "lan"}, "255"],
+                               "options": ["52=u8:3"]})).unwrap();
+        assert_eq!(buf[CRAFT_SNAME][..7], [12, 4, b'h', b'o', b's', b't', 0]);
+        assert_eq!(buf[CRAFT_FILE][..6], [15, 3, b'l', b'a', b'n', 255]);
+        assert_eq!(buf[CRAFT_HEADER_LEN..CRAFT_HEADER_LEN + 4], [52, 1, 3, 255]);
+        let error = craft(json!({"sname_options": [format!("12=text:{}", "h".repeat(63))]})).unwrap_err();
+        assert_eq!(error, "sname_options take 65 bytes, the field holds 64");
+    }
+
+    #[test]
+    fn end_and_length() {
+        assert_eq!(craft(json!({"msgtype": 1, "pad_to": 0})).unwrap().len(), CRAFT_HEADER_LEN + 4);
+        assert_eq!(craft(json!({"msgtype": 1, "pad_to": 0, "end": false})).unwrap().len(), CRAFT_HEADER_LEN + 3);
+        assert_eq!(craft(json!({"pad_to": 576})).unwrap().len(), 576);
+        assert_eq!(craft(json!({"pad_to": DHCP_MAX_UDP_PAYLOAD})).unwrap().len(), DHCP_MAX_UDP_PAYLOAD);
+        assert!(craft(json!({"pad_to": DHCP_MAX_UDP_PAYLOAD + 1})).is_err());
+        assert_eq!(craft(json!({"truncate": 100})).unwrap().len(), 100);
+        assert_eq!(craft(json!({"truncate": "1000"})).unwrap().len(), CRAFT_MIN_LEN);
+        assert_eq!(craft(json!({"truncate": DHCP_MAX_UDP_PAYLOAD + 1})).unwrap_err(),
+                   "truncate must be a number up to 65507, not 65508");
+    }
+
+    #[test]
+    fn cookie() {
+        let buf = craft(json!({"cookie": "de:ad:be:ef"})).unwrap();
+        assert_eq!(buf[236..CRAFT_HEADER_LEN], [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(craft(json!({"cookie": "dead"})).unwrap_err(), "cookie must be 4 bytes, not 2");
+    }
+
+    #[test]
+    fn invalid_descriptions() {
+        assert!(craft(json!({"xd": 1})).unwrap_err().starts_with("unknown field xd"));
+        assert_eq!(craft(json!({"hops": 256})).unwrap_err(), "hops must be a number up to 255, not 256");
+        assert_eq!(craft(json!({"broadcast": "yes"})).unwrap_err(), "broadcast must be true or false, not \"yes\"");
+        assert_eq!(craft(json!({"msgtype": "hello"})).unwrap_err(), "msgtype must be a number up to 255, not \"hello\"");
+    }
+}
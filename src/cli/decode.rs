@@ -0,0 +1,25 @@
+use crate::cli::input::{parse_input, read_input, InputFormat};
+use crate::cli::{frame_origin, CliError, CliResult, Origin, Output, EXIT_FAILURE, EXIT_OK};
+use dhcp_rs::dhcp_frame::{DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::ParseMode;
+
+// Exits with 1 when any of the packets fails to parse. Whole frames, as
+// Wireshark copies them, are recognized and their UDP payload is decoded.
+// Checksums aren't verified, like in pcap.
+pub fn decode(output: &Output, path: &str, format: InputFormat) -> CliResult {
+    let buf = read_input(path).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+    let packets = parse_input(&buf, format).map_err(|e| CliError::usage(format!("{}: {}", path, e)))?;
+    if packets.is_empty() {
+        return Err(CliError::usage(format!("{}: no packets found", path)));
+    }
+    let mut failed = false;
+    for (index, buf) in packets.iter().enumerate() {
+        let decoded = match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, buf, ParseMode::Lenient) {
+            Ok((header, payload)) =>
+                output.decode(&Origin::frame(format!("packet {} {}", index, frame_origin(&header)), &header), payload),
+            Err(_) => output.decode(&Origin::new(format!("packet {}", index)), buf),
+        };
+        failed |= decoded.is_none();
+    }
+    Ok(if failed { EXIT_FAILURE } else { EXIT_OK })
+}
@@ -0,0 +1,96 @@
+use crate::cli::input::{parse_input, read_input, InputFormat};
+use crate::cli::{CliError, CliResult, EXIT_FAILURE, EXIT_OK};
+use dhcp_rs::dhcp_frame::{DhcpFrameError, DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::{DhcpPacket, ParseMode};
+use dhcp_rs::dhcp_pcap::{DhcpPcapError, DhcpPcapReader};
+use std::io::Cursor;
+
+// The DHCP packets of a pcap or pcapng capture, frames of other traffic are
+// left out.
+fn read_capture(path: &str, reader: DhcpPcapReader<Cursor<&Vec<u8>>>) -> Result<Vec<Vec<u8>>, CliError> {
+    let mut packets = Vec::new();
+    for (index, record) in reader.enumerate() {
+        let record = record.map_err(|e| CliError::usage(format!("{}: frame {}: {}", path, index + 1, e)))?;
+        let link_type = match DhcpLinkType::from_linktype(record.link_type) {
+            Some(link_type) => link_type,
+            None => continue,
+        };
+        match DhcpFrameHeader::parse_link_with_mode(link_type, &record.data, ParseMode::Lenient) {
+            Ok((_, payload)) => packets.push(payload.to_vec()),
+            Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) | Err(DhcpFrameError::WrongPorts { .. }) |
+            Err(DhcpFrameError::Fragmented) => (),
+            Err(e) => return Err(CliError::usage(format!("{}: frame {}: {}", path, index + 1, e))),
+        }
+    }
+    Ok(packets)
+}
+
+// The UDP payload of a whole frame, other packets as they are.
+fn payload(buf: Vec<u8>) -> Vec<u8> {
+    match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, &buf, ParseMode::Lenient) {
+        Ok((_, payload)) => payload.to_vec(),
+        Err(_) => buf,
+    }
+}
+
+// Captures are recognized by their magic number, anything else is read like
+// decode does and whole frames are reduced to their UDP payload.
+fn read_packets(path: &str, format: InputFormat) -> Result<Vec<Vec<u8>>, CliError> {
+    let buf = read_input(path).map_err(|e| CliError::usage(format!("{}: {}", path, e)))?;
+    match DhcpPcapReader::new(Cursor::new(&buf)) {
+        Ok(reader) => return read_capture(path, reader),
+        Err(DhcpPcapError::UnknownFormat(_)) => (),
+        Err(DhcpPcapError::Truncated) if buf.len() < 4 => (),
+        Err(e) => return Err(CliError::usage(format!("{}: {}", path, e))),
+    }
+    let packets = parse_input(&buf, format).map_err(|e| CliError::usage(format!("{}: {}", path, e)))?;
+    Ok(packets.into_iter().map(payload).collect())
+}
+
+fn parse_or_report(path: &str, index: usize, buf: &[u8]) -> Option<DhcpPacket> {
+    match DhcpPacket::parse(buf) {
+        Ok((_, packet)) => Some(packet),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            outln!("packet {}: {}: {}", index, path, e);
+            None
+        }
+        Err(nom::Err::Incomplete(_)) => {
+            outln!("packet {}: {}: incomplete packet", index, path);
+            None
+        }
+    }
+}
+
+// Exits with 0 when both captures decode to the same packets, 1 when they
+// differ and 2 when a capture can't be read, like diff(1).
+pub fn diff(old_path: &str, new_path: &str, format: InputFormat) -> CliResult {
+    let old = read_packets(old_path, format)?;
+    let new = read_packets(new_path, format)?;
+    let mut differ = old.len() != new.len();
+    for (index, (old_buf, new_buf)) in old.iter().zip(new.iter()).enumerate() {
+        let old_packet = parse_or_report(old_path, index, old_buf);
+        let new_packet = parse_or_report(new_path, index, new_buf);
+        let (old_packet, new_packet) = match (old_packet, new_packet) {
+            (Some(old_packet), Some(new_packet)) => (old_packet, new_packet),
+            _ => {
+                differ = true;
+                continue;
+            }
+        };
+        let differences = old_packet.diff(&new_packet);
+        if !differences.is_empty() {
+            differ = true;
+            outln!("packet {}: {:#}", index, old_packet);
+            for difference in differences {
+                outln!("    {}", difference);
+            }
+        }
+    }
+    for index in new.len()..old.len() {
+        outln!("packet {}: only in {}", index, old_path);
+    }
+    for index in old.len()..new.len() {
+        outln!("packet {}: only in {}", index, new_path);
+    }
+    Ok(if differ { EXIT_FAILURE } else { EXIT_OK })
+}
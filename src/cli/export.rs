@@ -0,0 +1,417 @@
+use crate::cli::{format_timestamp, Device, Origin};
+use dhcp_rs::dhcp_packet::{DhcpOption, DhcpOptionID, DhcpPacket, DhcpSipServers};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    Timestamp,
+    Interface,
+    Src,
+    Dst,
+    SrcMac,
+    Length,
+    Op,
+    MsgType,
+    Xid,
+    Chaddr,
+    Ciaddr,
+    Yiaddr,
+    Siaddr,
+    Giaddr,
+    Hostname,
+    RequestedIp,
+    ServerId,
+    VendorClass,
+    ClientId,
+    LeaseTime,
+    Params,
+    // The matching signature and its details, empty for unknown clients.
+    Device,
+    DeviceClass,
+    Os,
+    DeviceVendor,
+    // The features the client was matched on.
+    Fingerprint,
+    Error,
+    // Any option by code, option82 for the relay agent information.
+    Option(u8),
+}
+
+const CSV_COLUMNS: [(&str, CsvColumn); 27] = [
+    ("timestamp", CsvColumn::Timestamp),
+    ("interface", CsvColumn::Interface),
+    ("src", CsvColumn::Src),
+    ("dst", CsvColumn::Dst),
+    ("src_mac", CsvColumn::SrcMac),
+    ("length", CsvColumn::Length),
+    ("op", CsvColumn::Op),
+    ("msgtype", CsvColumn::MsgType),
+    ("xid", CsvColumn::Xid),
+    ("chaddr", CsvColumn::Chaddr),
+    ("ciaddr", CsvColumn::Ciaddr),
+    ("yiaddr", CsvColumn::Yiaddr),
+    ("siaddr", CsvColumn::Siaddr),
+    ("giaddr", CsvColumn::Giaddr),
+    ("hostname", CsvColumn::Hostname),
+    ("requested_ip", CsvColumn::RequestedIp),
+    ("server_id", CsvColumn::ServerId),
+    ("vendor_class", CsvColumn::VendorClass),
+    ("client_id", CsvColumn::ClientId),
+    ("lease_time", CsvColumn::LeaseTime),
+    ("params", CsvColumn::Params),
+    ("device", CsvColumn::Device),
+    ("device_class", CsvColumn::DeviceClass),
+    ("os", CsvColumn::Os),
+    ("device_vendor", CsvColumn::DeviceVendor),
+    ("fingerprint", CsvColumn::Fingerprint),
+    ("error", CsvColumn::Error),
+];
+
+pub const CSV_DEFAULT_COLUMNS: &str = "timestamp,src,dst,msgtype,xid,chaddr,hostname,requested_ip,vendor_class,device,error";
+
+impl FromStr for CsvColumn {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some((_, column)) = CSV_COLUMNS.iter().find(|(x, _)| *x == name) {
+            return Ok(*column);
+        }
+        match name.strip_prefix("option").map(u8::from_str) {
+            Some(Ok(code)) => Ok(CsvColumn::Option(code)),
+            _ => {
+                let names: Vec<_> = CSV_COLUMNS.iter().map(|(x, _)| *x).collect();
+                Err(format!("unknown column {}, expected one of {} or option<code>", name, names.join(", ")))
+            }
+        }
+    }
+}
+
+impl fmt::Display for CsvColumn {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match (self, CSV_COLUMNS.iter().find(|(_, x)| x == self)) {
+            (_, Some((name, _))) => write!(w, "{}", name),
+            (CsvColumn::Option(code), None) => write!(w, "option{}", code),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// RFC 3339 in UTC, 2024-05-01T10:00:00.123456Z.
+fn timestamp(timestamp: Duration) -> String {
+    format!("{}Z", format_timestamp(timestamp).replacen(' ', "T", 1))
+}
+
+fn address(address: Option<Ipv4Addr>) -> Option<String> {
+    address.map(|x| x.to_string())
+}
+
+fn message_type(packet: &DhcpPacket) -> Option<String> {
+    packet.message_type().map(|x| x.to_string().to_uppercase())
+}
+
+// The value as the dissection prints it, unknown options as hex and the
+// requested parameters as their codes.
+fn option_value(option: &DhcpOption) -> String {
+    match option {
+        DhcpOption::Other(o) => o.option.to_string(),
+        DhcpOption::ParameterRequestList(p) =>
+            Vec::from(p).iter().map(|x| x.code().to_string()).collect::<Vec<_>>().join(" "),
+        o => o.to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn addresses(addresses: Vec<Ipv4Addr>) -> Value {
+    addresses.iter().map(|x| x.to_string()).collect::<Vec<_>>().into()
+}
+
+// The value with the type it has on the wire, addresses as strings, times in
+// seconds and bytes without a known meaning as hex. Codes go along with the
+// names of enumerated values, the names don't cover every code.
+fn option_json(option: &DhcpOption) -> Value {
+    match option {
+        DhcpOption::MessageType(t) => t.to_string().to_uppercase().into(),
+        DhcpOption::ClientIdentifier(b) | DhcpOption::DomainSearch(b) => hex(&Vec::from(b)).into(),
+        DhcpOption::RapidCommit => true.into(),
+        DhcpOption::MaxMsgSize(x) => (*x).into(),
+        DhcpOption::HostName(x) | DhcpOption::VendorClassId(x) | DhcpOption::DomainName(x) |
+        DhcpOption::Message(x) => x.clone().into(),
+        DhcpOption::BroadcastAddr(x) | DhcpOption::ServerID(x) | DhcpOption::RequestedIpAddr(x) =>
+            x.to_string().into(),
+        DhcpOption::SubNetMask(x) => Ipv4Addr::from(*x).to_string().into(),
+        DhcpOption::Router(l) | DhcpOption::DNSserver(l) | DhcpOption::AssociatedIp(l) => addresses(Vec::from(l)),
+        DhcpOption::LeaseTime(d) | DhcpOption::RenewalPeriod(d) | DhcpOption::RebindingPeriod(d) |
+        DhcpOption::ClientLastTransactionTime(d) | DhcpOption::StartTimeOfState(d) => d.as_secs().into(),
+        DhcpOption::BaseTime(x) | DhcpOption::QueryStartTime(x) | DhcpOption::QueryEndTime(x) => (*x).into(),
+        DhcpOption::InterfaceMTU(x) => (*x).into(),
+        DhcpOption::ParameterRequestList(p) => Vec::from(p).iter().map(|x| x.code()).collect::<Vec<_>>().into(),
+        DhcpOption::ForceRenewNonceCapable(n) =>
+            Vec::from(n).iter().map(|x| json!({ "code": x.code(), "name": x.to_string() })).collect::<Vec<_>>().into(),
+        DhcpOption::SipServers(DhcpSipServers::DomainNames(names)) => json!({ "names": names }),
+        DhcpOption::SipServers(DhcpSipServers::Addresses(l)) => json!({ "addresses": addresses(Vec::from(l)) }),
+        DhcpOption::StatusCode(s) =>
+            json!({ "code": s.status.code(), "name": s.status.to_string(), "message": s.message }),
+        DhcpOption::DhcpState(s) => json!({ "code": s.code(), "name": s.to_string() }),
+        DhcpOption::DataSource(s) => json!({ "flags": s.0, "remote": s.remote() }),
+        DhcpOption::Other(o) => hex(&Vec::from(&o.option)).into(),
+        DhcpOption::Malformed(m) => json!({ "malformed": m.reason.to_string(), "bytes": hex(&Vec::from(&m.bytes)) }),
+        DhcpOption::Pad | DhcpOption::End => Value::Null,
+    }
+}
+
+fn option(packet: &DhcpPacket, option_id: DhcpOptionID) -> String {
+    packet.options.get(&option_id).map(option_value).unwrap_or_default()
+}
+
+pub fn packet_json(packet: &DhcpPacket) -> Value {
+    let mut options: Vec<_> = packet.options.iter().collect();
+    options.sort_by_key(|(option_id, _)| option_id.code());
+    let options: Vec<_> = options.iter()
+        .map(|(option_id, option)| json!({
+            "code": option_id.code(),
+            "name": option_id.to_string(),
+            "value": option_json(option),
+        }))
+        .collect();
+    json!({
+        "op": packet.opcode.to_string(),
+        "msgtype": message_type(packet),
+        "htype": packet.htype.0,
+        "hlen": packet.hlen,
+        "hops": packet.hops,
+        "xid": format!("{:#010x}", packet.xid),
+        "secs": packet.secs.as_secs(),
+        "broadcast": packet.broadcast,
+        "ciaddr": address(packet.ciaddr),
+        "yiaddr": address(packet.yiaddr),
+        "siaddr": address(packet.siaddr),
+        "giaddr": address(packet.giaddr),
+        "chaddr": packet.chaddr.to_string(),
+        "sname": packet.sname,
+        "file": packet.file,
+        "options": options,
+    })
+}
+
+pub fn device_json(device: &Device) -> Value {
+    let signature = device.signature;
+    json!({
+        "name": signature.map(|x| x.name.clone()),
+        "class": signature.and_then(|x| x.class.clone()),
+        "os": signature.and_then(|x| x.os.clone()),
+        "vendor": signature.and_then(|x| x.vendor.clone()),
+        "prl": device.fingerprint.prl,
+        "options": device.fingerprint.options,
+        "vendor_class": device.fingerprint.vendor_class,
+        "max_msg_size": device.fingerprint.max_msg_size,
+    })
+}
+
+// The metadata of where the packet was seen followed by the packet, or the
+// reason it didn't parse, and the device that sent a request.
+pub fn record_json(origin: &Origin, buf: &[u8], packet: Result<&DhcpPacket, String>, device: Option<Device>)
+                   -> Value {
+    let mut record = Map::new();
+    if let Some(x) = origin.timestamp {
+        record.insert("timestamp".into(), timestamp(x).into());
+    }
+    if let Some(x) = &origin.interface {
+        record.insert("interface".into(), x.clone().into());
+    }
+    if let Some(x) = origin.src {
+        record.insert("src".into(), x.to_string().into());
+    }
+    if let Some(x) = origin.dst {
+        record.insert("dst".into(), x.to_string().into());
+    }
+    if let Some(x) = origin.src_mac {
+        record.insert("src_mac".into(), x.to_string().into());
+    }
+    if let Some(x) = origin.vlan {
+        record.insert("vlan".into(), x.into());
+    }
+    record.insert("length".into(), buf.len().into());
+    match packet {
+        Ok(packet) => {
+            let violations: Vec<_> = packet.validate().iter().map(|x| x.to_string()).collect();
+            record.insert("packet".into(), packet_json(packet));
+            record.insert("violations".into(), violations.into());
+        }
+        Err(e) => {
+            record.insert("error".into(), e.into());
+        }
+    }
+    if let Some(device) = device {
+        record.insert("device".into(), device_json(&device));
+    }
+    Value::Object(record)
+}
+
+// Quoted when it holds a separator, a quote or a line break, as RFC 4180 does.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn csv_header(columns: &[CsvColumn]) -> String {
+    columns.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",")
+}
+
+pub fn csv_row(columns: &[CsvColumn], origin: &Origin, buf: &[u8], packet: Result<&DhcpPacket, String>,
+               device: Option<Device>) -> String {
+    let signature = device.as_ref().and_then(|x| x.signature);
+    let fields: Vec<_> = columns.iter()
+        .map(|column| {
+            let field = match (column, &packet) {
+                (CsvColumn::Timestamp, _) => origin.timestamp.map(timestamp).unwrap_or_default(),
+                (CsvColumn::Interface, _) => origin.interface.clone().unwrap_or_default(),
+                (CsvColumn::Src, _) => origin.src.map(|x| x.to_string()).unwrap_or_default(),
+                (CsvColumn::Dst, _) => origin.dst.map(|x| x.to_string()).unwrap_or_default(),
+                (CsvColumn::SrcMac, _) => origin.src_mac.map(|x| x.to_string()).unwrap_or_default(),
+                (CsvColumn::Length, _) => buf.len().to_string(),
+                (CsvColumn::Device, _) => signature.map(|x| x.name.clone()).unwrap_or_default(),
+                (CsvColumn::DeviceClass, _) => signature.and_then(|x| x.class.clone()).unwrap_or_default(),
+                (CsvColumn::Os, _) => signature.and_then(|x| x.os.clone()).unwrap_or_default(),
+                (CsvColumn::DeviceVendor, _) => signature.and_then(|x| x.vendor.clone()).unwrap_or_default(),
+                (CsvColumn::Fingerprint, _) => device.as_ref().map(|x| x.fingerprint.to_string()).unwrap_or_default(),
+                (CsvColumn::Error, Err(e)) => e.clone(),
+                (_, Err(_)) | (CsvColumn::Error, Ok(_)) => String::new(),
+                (CsvColumn::Op, Ok(p)) => p.opcode.to_string(),
+                (CsvColumn::MsgType, Ok(p)) => message_type(p).unwrap_or_default(),
+                (CsvColumn::Xid, Ok(p)) => format!("{:#010x}", p.xid),
+                (CsvColumn::Chaddr, Ok(p)) => p.chaddr.to_string(),
+                (CsvColumn::Ciaddr, Ok(p)) => address(p.ciaddr).unwrap_or_default(),
+                (CsvColumn::Yiaddr, Ok(p)) => address(p.yiaddr).unwrap_or_default(),
+                (CsvColumn::Siaddr, Ok(p)) => address(p.siaddr).unwrap_or_default(),
+                (CsvColumn::Giaddr, Ok(p)) => address(p.giaddr).unwrap_or_default(),
+                (CsvColumn::Hostname, Ok(p)) => option(p, DhcpOptionID::HostName),
+                (CsvColumn::RequestedIp, Ok(p)) => option(p, DhcpOptionID::RequestedIpAddr),
+                (CsvColumn::ServerId, Ok(p)) => option(p, DhcpOptionID::ServerID),
+                (CsvColumn::VendorClass, Ok(p)) => option(p, DhcpOptionID::VendorClassId),
+                (CsvColumn::ClientId, Ok(p)) => option(p, DhcpOptionID::ClientIdentifier),
+                (CsvColumn::LeaseTime, Ok(p)) => option(p, DhcpOptionID::LeaseTime),
+                (CsvColumn::Params, Ok(p)) => option(p, DhcpOptionID::ParameterRequestList),
+                (CsvColumn::Option(code), Ok(p)) => option(p, DhcpOptionID::from(*code)),
+            };
+            csv_field(&field)
+        })
+        .collect();
+    fields.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcp_rs::dhcp_packet::{BootpOpcode, DhcpMessageType, MacAddr};
+    use std::net::SocketAddrV4;
+
+    fn discover() -> DhcpPacket {
+        let mut discover = DhcpPacket::new(BootpOpcode::BootRequest, 0x1234, MacAddr::new(2, 0, 0, 0, 0, 1));
+        discover.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        discover
+    }
+
+    fn origin() -> Origin {
+        Origin {
+            timestamp: Some(Duration::new(1_714_557_600, 123_456_000)),
+            src: Some(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 68)),
+            ..Origin::new("test")
+        }
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("laptop"), "laptop");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("lap,top"), "\"lap,top\"");
+        assert_eq!(csv_field("lap\"top"), "\"lap\"\"top\"");
+        assert_eq!(csv_field("lap\ntop"), "\"lap\ntop\"");
+        assert_eq!(csv_field("lap\rtop"), "\"lap\rtop\"");
+    }
+
+    #[test]
+    fn csv_hostname_quoted() {
+        let mut packet = discover();
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("a,\"b\"\nc".into()));
+        let columns = [CsvColumn::Xid, CsvColumn::Hostname, CsvColumn::Error];
+        assert_eq!(csv_row(&columns, &origin(), &[0; 300], Ok(&packet), None), "0x00001234,\"a,\"\"b\"\"\nc\",");
+    }
+
+    #[test]
+    fn csv_columns() {
+        for (name, column) in CSV_COLUMNS.iter() {
+            assert_eq!(name.parse::<CsvColumn>(), Ok(*column));
+            assert_eq!(column.to_string(), *name);
+        }
+        assert_eq!("option82".parse::<CsvColumn>(), Ok(CsvColumn::Option(82)));
+        assert_eq!(CsvColumn::Option(82).to_string(), "option82");
+        assert_eq!("option0".parse::<CsvColumn>(), Ok(CsvColumn::Option(0)));
+        assert!("option256".parse::<CsvColumn>().is_err());
+        assert!("option".parse::<CsvColumn>().is_err());
+        assert!("Hostname".parse::<CsvColumn>().is_err());
+        let columns: Result<Vec<CsvColumn>, _> = CSV_DEFAULT_COLUMNS.split(',').map(|x| x.parse()).collect();
+        assert_eq!(csv_header(&columns.unwrap()), CSV_DEFAULT_COLUMNS);
+    }
+
+    #[test]
+    fn csv_parse_error() {
+        let columns = [CsvColumn::Timestamp, CsvColumn::Src, CsvColumn::Xid, CsvColumn::Length, CsvColumn::Error];
+        assert_eq!(csv_row(&columns, &origin(), &[0; 10], Err("too short".into()), None),
+                   "2024-05-01T10:00:00.123456Z,10.0.0.2:68,,10,too short");
+    }
+
+    #[test]
+    fn packet_json_shape() {
+        let mut packet = discover();
+        packet.options.insert(DhcpOptionID::RequestedIpAddr,
+                              DhcpOption::RequestedIpAddr(Ipv4Addr::new(10, 0, 0, 2)));
+        let json = packet_json(&packet);
+        assert_eq!(json["op"], "BootRequest");
+        assert_eq!(json["msgtype"], "DISCOVER");
+        assert_eq!(json["xid"], "0x00001234");
+        assert_eq!(json["chaddr"], "02:00:00:00:00:01");
+        assert_eq!(json["ciaddr"], Value::Null);
+        assert_eq!(json["options"], json!([
+            { "code": 50, "name": DhcpOptionID::RequestedIpAddr.to_string(), "value": "10.0.0.2" },
+            { "code": 53, "name": DhcpOptionID::MsgType.to_string(), "value": "DISCOVER" },
+        ]));
+    }
+
+    #[test]
+    fn record_json_shape() {
+        let packet = discover();
+        let record = record_json(&origin(), &[0; 300], Ok(&packet), None);
+        let record = record.as_object().unwrap();
+        let keys: Vec<_> = record.keys().map(|x| x.as_str()).collect();
+        assert_eq!(keys.len(), 5);
+        for key in &["timestamp", "src", "length", "packet", "violations"] {
+            assert!(keys.contains(key), "{}", key);
+        }
+        assert_eq!(record["timestamp"], "2024-05-01T10:00:00.123456Z");
+        assert_eq!(record["src"], "10.0.0.2:68");
+        assert_eq!(record["length"], 300);
+        assert_eq!(record["packet"], packet_json(&packet));
+        assert!(record["violations"].is_array());
+    }
+
+    #[test]
+    fn record_json_parse_error() {
+        let record = record_json(&origin(), &[0; 10], Err("too short".into()), None);
+        assert_eq!(record, json!({
+            "timestamp": "2024-05-01T10:00:00.123456Z",
+            "src": "10.0.0.2:68",
+            "length": 10,
+            "error": "too short",
+        }));
+    }
+}
@@ -0,0 +1,276 @@
+use base64::Engine;
+use std::fs;
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Binary when the input isn't text, otherwise hex and then base64
+    Auto,
+    /// Hex digits, optionally with offsets and an ASCII column like xxd or Wireshark
+    Hex,
+    /// Base64, packets separated by blank lines
+    Base64,
+    /// Raw bytes of a single packet
+    Binary,
+}
+
+// Reads the file, or stdin for - like most tools.
+pub fn read_input(path: &str) -> io::Result<Vec<u8>> {
+    if path == "-" {
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        Ok(buf)
+    } else {
+        fs::read(path)
+    }
+}
+
+// Splits off the offset of a dump line, "00000010: " from xxd, "0010   " from
+// Wireshark or "00000010  " from hexdump -C. Plain hex is separated by single
+// spaces, so an offset needs a colon or a wider gap to be recognized.
+fn split_offset(line: &str) -> Option<(usize, &str)> {
+    let end = line.find(|x: char| !x.is_ascii_hexdigit())?;
+    let rest = &line[end..];
+    let rest = match rest.strip_prefix(':') {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+        None if rest.starts_with("  ") || rest.starts_with('\t') => rest,
+        _ => return None,
+    };
+    if end < 4 {
+        return None;
+    }
+    Some((usize::from_str_radix(&line[..end], 16).ok()?, rest))
+}
+
+pub fn parse_hex_digits(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// The bytes of a dump line, up to `limit` of them. The ASCII column is
+// recognized by not being hex, or by the limit when it happens to be.
+fn parse_dump_line(line: &str, limit: Option<usize>) -> Option<Vec<u8>> {
+    let line = line.split('|').next().unwrap_or_default();
+    let mut bytes = Vec::new();
+    for group in line.split_whitespace() {
+        if limit.is_some_and(|x| bytes.len() >= x) {
+            break;
+        }
+        match parse_hex_digits(group) {
+            Some(group) => bytes.extend(group),
+            None if bytes.is_empty() => return None,
+            None => break,
+        }
+    }
+    if let Some(limit) = limit {
+        bytes.truncate(limit);
+    }
+    Some(bytes)
+}
+
+// Fills in the lines hexdump leaves out as * because they repeat the one before.
+fn expand_repeat(packet: &mut Vec<u8>, line: &[u8], offset: usize) {
+    let start = packet.len();
+    while !line.is_empty() && packet.len() < offset {
+        packet.extend_from_slice(line);
+    }
+    packet.truncate(offset.max(start));
+}
+
+// One packet per line, or a dump spread over several lines with offsets in
+// front. A dump starting again at offset 0 is the next packet. Blank lines
+// and lines starting with # are skipped.
+pub fn parse_hex(text: &str) -> Result<Vec<Vec<u8>>, String> {
+    let lines: Vec<_> = text.lines().enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut in_dump = false;
+    let mut last_line = Vec::new();
+    let mut repeat = false;
+    for (index, (number, line)) in lines.iter().enumerate() {
+        let (offset, rest) = match split_offset(line) {
+            Some(x) => x,
+            None if in_dump && *line == "*" => {
+                repeat = true;
+                continue;
+            }
+            // hexdump ends a dump with a line holding only the total length,
+            // far shorter than any packet in hex.
+            None if in_dump && line.len() <= 16 && usize::from_str_radix(line, 16).is_ok() => {
+                let packet = packets.last_mut().unwrap();
+                let end = usize::from_str_radix(line, 16).unwrap();
+                if repeat {
+                    expand_repeat(packet, &last_line, end);
+                }
+                if end != packet.len() {
+                    return Err(format!("line {}: length {:#x} doesn't match {:#x} bytes", number, end, packet.len()));
+                }
+                in_dump = false;
+                continue;
+            }
+            None => {
+                let digits: String = line.chars().filter(|x| !x.is_ascii_whitespace() && *x != ':').collect();
+                packets.push(parse_hex_digits(&digits).ok_or_else(|| format!("line {}: invalid hex", number))?);
+                in_dump = false;
+                continue;
+            }
+        };
+        if offset == 0 || !in_dump {
+            packets.push(Vec::new());
+            in_dump = true;
+            repeat = false;
+        }
+        let packet = packets.last_mut().unwrap();
+        if repeat {
+            expand_repeat(packet, &last_line, offset);
+            repeat = false;
+        }
+        if offset != packet.len() {
+            return Err(format!("line {}: offset {:#x} doesn't follow {:#x} bytes", number, offset, packet.len()));
+        }
+        // The next offset tells how many bytes this line holds.
+        let limit = lines.get(index + 1).and_then(|(_, x)| split_offset(x))
+            .and_then(|(next, _)| next.checked_sub(offset)).filter(|x| *x > 0);
+        let bytes = parse_dump_line(rest, limit).ok_or_else(|| format!("line {}: invalid hex", number))?;
+        if let Some(limit) = limit.filter(|x| bytes.len() != *x) {
+            return Err(format!("line {}: expected {} bytes, found {}", number, limit, bytes.len()));
+        }
+        packet.extend_from_slice(&bytes);
+        last_line = bytes;
+    }
+    Ok(packets)
+}
+
+pub fn parse_base64(text: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut packets = Vec::new();
+    for block in text.split("\n\n").map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let encoded: String = block.chars().filter(|x| !x.is_ascii_whitespace()).collect();
+        let packet = base64::engine::general_purpose::STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+        packets.push(packet);
+    }
+    Ok(packets)
+}
+
+fn is_text(buf: &[u8]) -> bool {
+    buf.iter().all(|x| x.is_ascii_graphic() || x.is_ascii_whitespace())
+}
+
+pub fn parse_input(buf: &[u8], format: InputFormat) -> Result<Vec<Vec<u8>>, String> {
+    let text = || std::str::from_utf8(buf).map_err(|_| "input isn't text, use --input binary".to_string());
+    match format {
+        InputFormat::Binary => Ok(vec![buf.to_vec()]),
+        InputFormat::Hex => parse_hex(text()?),
+        InputFormat::Base64 => parse_base64(text()?),
+        InputFormat::Auto if !is_text(buf) => Ok(vec![buf.to_vec()]),
+        InputFormat::Auto => {
+            let text = text()?;
+            parse_hex(text).or_else(|e| parse_base64(text).map_err(|_| format!("neither hex nor base64, {}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first 16 bytes are ASCII hex digits, so is their ASCII column.
+    const XXD: &str = "\
+00000000: 6465 6164 6265 6566 6361 6665 3031 3233  deadbeefcafe0123
+00000010: 4041 4243 4445 4647 0000 0000 0000 0000  @ABCDEFG........
+00000020: 0000 0000 0000 0000 0000 0000 0000 0000  ................
+";
+
+    const WIRESHARK: &str = "\
+0000   64 65 61 64 62 65 65 66 63 61 66 65 30 31 32 33   deadbeefcafe0123
+0010   40 41 42 43 44 45 46 47 00 00 00 00 00 00 00 00   @ABCDEFG........
+0020   00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00   ................
+";
+
+    const HEXDUMP: &str = "\
+00000000  64 65 61 64 62 65 65 66  63 61 66 65 30 31 32 33  |deadbeefcafe0123|
+00000010  40 41 42 43 44 45 46 47  00 00 00 00 00 00 00 00  |@ABCDEFG........|
+00000020  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000040  00 00 00 00 00 00 00 00  01 02 03                 |...........|
+0000004b
+";
+
+    fn packet() -> Vec<u8> {
+        let mut packet = b"deadbeefcafe0123".to_vec();
+        packet.extend(0x40..0x48);
+        packet.resize(48, 0);
+        packet
+    }
+
+    #[test]
+    fn dumps() {
+        assert_eq!(parse_hex(XXD), Ok(vec![packet()]));
+        assert_eq!(parse_hex(WIRESHARK), Ok(vec![packet()]));
+        let mut long = packet();
+        long.resize(72, 0);
+        long.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(parse_hex(HEXDUMP), Ok(vec![long]));
+    }
+
+    #[test]
+    fn repeated_lines_at_the_end() {
+        let text = "\
+00000000  01 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000040
+";
+        let mut packet = vec![0; 64];
+        packet[0] = 1;
+        assert_eq!(parse_hex(text), Ok(vec![packet]));
+    }
+
+    #[test]
+    fn plain_hex() {
+        let text = "# two packets\n01:02:03:04\n\n0a0b 0c0d\n";
+        assert_eq!(parse_hex(text), Ok(vec![vec![1, 2, 3, 4], vec![10, 11, 12, 13]]));
+        assert_eq!(parse_hex("01 02 0"), Err("line 1: invalid hex".to_string()));
+        assert_eq!(parse_hex("01\nzz"), Err("line 2: invalid hex".to_string()));
+    }
+
+    #[test]
+    fn several_dumps() {
+        // A plain line right after a dump needs a space, "0102" alone would
+        // be taken for the length hexdump ends with.
+        let text = format!("{}\n{}{}", XXD, WIRESHARK, "01 02\n");
+        assert_eq!(parse_hex(&text), Ok(vec![packet(), packet(), vec![1, 2]]));
+    }
+
+    #[test]
+    fn offsets_must_follow() {
+        let missing_line = XXD.lines().filter(|x| !x.starts_with("00000010")).collect::<Vec<_>>().join("\n");
+        // Expecting 32 bytes, the ASCII column of the first line reads as 8 more.
+        assert_eq!(parse_hex(&missing_line), Err("line 1: expected 32 bytes, found 24".to_string()));
+        let backwards = XXD.replace("00000020", "00000008");
+        assert_eq!(parse_hex(&backwards), Err("line 3: offset 0x8 doesn't follow 0x20 bytes".to_string()));
+        let short_line = WIRESHARK.replace(" 00 00 00 00 00 00 00 00   @", "   @");
+        assert_eq!(parse_hex(&short_line), Err("line 2: expected 16 bytes, found 8".to_string()));
+        let wrong_length = HEXDUMP.replace("0000004b", "00000050");
+        assert_eq!(parse_hex(&wrong_length), Err("line 6: length 0x50 doesn't match 0x4b bytes".to_string()));
+        // A dump going on after a plain line.
+        assert_eq!(parse_hex("0102\n00000010: 0304"), Err("line 2: offset 0x10 doesn't follow 0x0 bytes".to_string()));
+    }
+
+    #[test]
+    fn auto_detection() {
+        let binary = [0x01, 0x00, 0xff, b'\n'];
+        assert_eq!(parse_input(&binary, InputFormat::Auto), Ok(vec![binary.to_vec()]));
+        assert_eq!(parse_input(XXD.as_bytes(), InputFormat::Auto), Ok(vec![packet()]));
+        assert_eq!(parse_input(b"AQID\n\nBAUG\nBw==\n", InputFormat::Auto), Ok(vec![vec![1, 2, 3], vec![4, 5, 6, 7]]));
+        assert_eq!(parse_input(b"0102", InputFormat::Auto), Ok(vec![vec![1, 2]]));
+        assert_eq!(parse_input(b"0102", InputFormat::Base64), Ok(vec![vec![0xd3, 0x5d, 0x36]]));
+        assert_eq!(parse_input(b"0102", InputFormat::Binary), Ok(vec![b"0102".to_vec()]));
+        assert_eq!(parse_input(b"not a packet!", InputFormat::Auto),
+                   Err("neither hex nor base64, line 1: invalid hex".to_string()));
+        assert_eq!(parse_input(&binary, InputFormat::Hex), Err("input isn't text, use --input binary".to_string()));
+    }
+}
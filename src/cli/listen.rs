@@ -0,0 +1,15 @@
+use crate::cli::{bind_udp, CliError, CliResult, Output, MAX_PACKET_SIZE};
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+// Only sees traffic addressed to this host on the port, usually client
+// requests on 67. See sniff for everything on the link.
+pub fn listen(output: &Output, address: Ipv4Addr, port: u16) -> CliResult {
+    let socket = bind_udp(SocketAddrV4::new(address, port))?;
+    output.info(format!("listening on {}:{}", address, port));
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    loop {
+        let (length, src_addr) = socket.recv_from(&mut buf)
+            .map_err(|e| CliError::new(format!("receiving on port {}: {}", port, e)))?;
+        output.received(src_addr, SocketAddrV4::new(address, port), &buf[..length]);
+    }
+}
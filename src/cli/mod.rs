@@ -0,0 +1,609 @@
+// println! that ends the program quietly once stdout is closed, like piped
+// into head, instead of panicking.
+macro_rules! outln {
+    ($($arg:tt)*) => { $crate::cli::write_stdout(format_args!($($arg)*)) };
+}
+
+pub mod client;
+pub mod craft;
+pub mod decode;
+pub mod diff;
+pub mod export;
+pub mod input;
+pub mod listen;
+pub mod pcap;
+pub mod probe;
+pub mod record;
+pub mod relay;
+pub mod rogue;
+pub mod send;
+pub mod serve;
+pub mod sniff;
+
+use dhcp_rs::dhcp_dissect::{dissect, DhcpHexDump};
+use dhcp_rs::dhcp_filter::DhcpFilter;
+use dhcp_rs::dhcp_fingerprint::{DhcpFingerprint, DhcpFingerprintDb, DhcpSignature};
+use export::CsvColumn;
+use dhcp_rs::dhcp_frame::{DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::{DhcpPacket, MacAddr};
+use dhcp_rs::dhcp_track::{DhcpTracker, DhcpTransaction};
+use pnet::datalink::{self, NetworkInterface};
+use record::Recorder;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// Large enough for any UDP datagram, DHCP packets are usually under 600 bytes.
+pub const MAX_PACKET_SIZE: usize = 65536;
+
+#[derive(Debug)]
+pub struct CliError {
+    pub message: String,
+    pub code: i32,
+}
+
+impl CliError {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        CliError { message: message.into(), code: EXIT_FAILURE }
+    }
+
+    pub fn usage<S: Into<String>>(message: S) -> Self {
+        CliError { message: message.into(), code: EXIT_USAGE }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{}", self.message)
+    }
+}
+
+// Commands return the exit code to use when they finish without an error.
+pub type CliResult = Result<i32, CliError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Full dissection of every packet
+    Text,
+    /// One line per packet
+    Summary,
+    /// Annotated hexdump of the packet bytes
+    Hexdump,
+    /// Pretty printed JSON array of the packets with where they were seen,
+    /// closed when the command ends; use jsonl for live commands
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// One line per packet with the columns from --columns
+    Csv,
+}
+
+impl Format {
+    // Formats that other programs read, everything else goes to stderr.
+    pub fn is_machine(&self) -> bool {
+        matches!(self, Format::Json | Format::Jsonl | Format::Csv)
+    }
+}
+
+// Where a packet was seen. Text formats print the label in front of the
+// packet, the machine readable ones the fields.
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+    pub label: String,
+    pub timestamp: Option<Duration>,
+    pub interface: Option<String>,
+    pub src: Option<SocketAddrV4>,
+    pub dst: Option<SocketAddrV4>,
+    pub src_mac: Option<MacAddr>,
+    pub vlan: Option<u16>,
+}
+
+impl Origin {
+    pub fn new<S: Into<String>>(label: S) -> Self {
+        Origin { label: label.into(), ..Default::default() }
+    }
+
+    pub fn frame<S: Into<String>>(label: S, header: &DhcpFrameHeader) -> Self {
+        Origin {
+            label: label.into(),
+            src: Some(SocketAddrV4::new(header.src_ip, header.src_port)),
+            dst: Some(SocketAddrV4::new(header.dst_ip, header.dst_port)),
+            src_mac: Some(header.src_mac),
+            vlan: header.vlan,
+            ..Default::default()
+        }
+    }
+
+    // A frame captured on `interface` just now.
+    pub fn captured(interface: &str, header: &DhcpFrameHeader) -> Self {
+        Origin {
+            timestamp: Some(now()),
+            interface: Some(interface.to_string()),
+            ..Origin::frame(frame_origin(header), header)
+        }
+    }
+}
+
+pub struct Output {
+    pub format: Format,
+    // -1 with --quiet, raised by every -v.
+    pub verbosity: i8,
+    pub recorder: Option<RefCell<Recorder>>,
+    // Recorded as the interface packets were seen on, "any" without --interface.
+    pub interface: String,
+    pub tracking: Option<RefCell<Tracking>>,
+    pub columns: Vec<CsvColumn>,
+    // Packets it rejects aren't printed, recorded or tracked.
+    pub filter: Option<DhcpFilter>,
+    // The built-in signatures and those of --fingerprints.
+    pub fingerprints: DhcpFingerprintDb,
+    // The CSV header goes in front of the first row, the json array opens
+    // with the first record.
+    pub header_written: Cell<bool>,
+}
+
+pub struct Tracking {
+    pub tracker: DhcpTracker,
+    // How often live commands print the statistics.
+    pub interval: Option<Duration>,
+    pub last_report: Duration,
+}
+
+// The client behind a request, the signature is None for unknown clients.
+pub struct Device<'a> {
+    pub fingerprint: DhcpFingerprint,
+    pub signature: Option<&'a DhcpSignature>,
+}
+
+impl fmt::Display for Device<'_> {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self.signature {
+            Some(signature) => write!(w, "{}", signature),
+            None => write!(w, "unknown"),
+        }
+    }
+}
+
+pub fn write_stdout(args: fmt::Arguments) {
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = writeln!(stdout, "{}", args) {
+        if e.kind() == io::ErrorKind::BrokenPipe {
+            process::exit(EXIT_OK);
+        }
+        eprintln!("dhcp-rs: writing output: {}", e);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+// Time since the Unix epoch, what capture files use as well.
+pub fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+// What a recorded packet gets as comment next to where it was seen.
+fn verdict(buf: &[u8], packet: Option<&DhcpPacket>) -> String {
+    let packet = match packet {
+        Some(packet) => packet,
+        None => return match DhcpPacket::parse(buf) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => format!("malformed: {}", e),
+            _ => "malformed: incomplete packet".to_string(),
+        },
+    };
+    let violations: Vec<_> = packet.validate().iter().map(|x| x.to_string()).collect();
+    if violations.is_empty() {
+        "ok".to_string()
+    } else {
+        format!("invalid: {}", violations.join("; "))
+    }
+}
+
+impl Output {
+    // Progress messages, suppressed by --quiet.
+    pub fn info<S: fmt::Display>(&self, message: S) {
+        if self.verbosity >= 0 {
+            eprintln!("{}", message);
+        }
+    }
+
+    pub fn debug<S: fmt::Display>(&self, message: S) {
+        if self.verbosity >= 1 {
+            eprintln!("{}", message);
+        }
+    }
+
+    pub fn error<S: fmt::Display>(&self, origin: &str, error: S) {
+        eprintln!("{}: {}", origin, error);
+    }
+
+    // Fingerprints requests, replies have no device.
+    pub fn device(&self, buf: &[u8]) -> Option<Device<'_>> {
+        let fingerprint = DhcpFingerprint::parse(buf)?;
+        let signature = self.fingerprints.lookup(&fingerprint);
+        Some(Device { fingerprint, signature })
+    }
+
+    // The comment of a recorded packet, with the device when it is known.
+    fn comment(&self, action: &str, interface: &str, buf: &[u8], packet: Option<&DhcpPacket>) -> String {
+        let comment = format!("{} on {}: {}", action, interface, verdict(buf, packet));
+        match self.device(buf).and_then(|x| x.signature) {
+            Some(signature) => format!("{}; device {}", comment, signature.name),
+            None => comment,
+        }
+    }
+
+    // The json format is one array of the records, opened by the first one
+    // and closed by finish.
+    fn json(&self, value: &serde_json::Value) {
+        match self.format {
+            Format::Json => match serde_json::to_string_pretty(value) {
+                Ok(json) => {
+                    let separator = if self.header_written.replace(true) { "," } else { "[" };
+                    outln!("{}\n  {}", separator, json.replace('\n', "\n  "))
+                }
+                Err(e) => self.error("json", e),
+            },
+            _ => outln!("{}", value),
+        }
+    }
+
+    // Ends what the format started, called once the command is done.
+    pub fn finish(&self) {
+        if self.format == Format::Json {
+            outln!("{}", if self.header_written.get() { "]" } else { "[]" });
+        }
+    }
+
+    // A packet, or why it didn't parse, in one of the machine readable formats.
+    fn export(&self, origin: &Origin, buf: &[u8], packet: Result<&DhcpPacket, String>) {
+        match self.format {
+            Format::Json | Format::Jsonl => self.json(&export::record_json(origin, buf, packet, self.device(buf))),
+            Format::Csv => {
+                if !self.header_written.replace(true) {
+                    outln!("{}", export::csv_header(&self.columns));
+                }
+                outln!("{}", export::csv_row(&self.columns, origin, buf, packet, self.device(buf)));
+            }
+            Format::Text | Format::Summary | Format::Hexdump => unreachable!(),
+        }
+    }
+
+    pub fn packet(&self, origin: &Origin, buf: &[u8], packet: &DhcpPacket) {
+        let device = self.device(buf);
+        match self.format {
+            Format::Text => {
+                outln!("{}\n{}", origin.label, packet);
+                if let Some(signature) = device.as_ref().and_then(|x| x.signature) {
+                    outln!("    Device: {}", signature);
+                }
+            }
+            Format::Summary => match device.as_ref().and_then(|x| x.signature) {
+                Some(signature) => outln!("{} {:#} device=\"{}\"", origin.label, packet, signature.name),
+                None => outln!("{} {:#}", origin.label, packet),
+            },
+            Format::Hexdump => outln!("{}\n{}", origin.label, DhcpHexDump { packet: buf, spans: &dissect(buf) }),
+            Format::Json | Format::Jsonl | Format::Csv => return self.export(origin, buf, Ok(packet)),
+        }
+        if self.verbosity >= 1 {
+            for violation in packet.validate() {
+                outln!("    ! {}", violation);
+            }
+            if let Some(device) = &device {
+                outln!("    Fingerprint: {}", device.fingerprint);
+            }
+        }
+        if self.verbosity >= 2 && self.format != Format::Hexdump {
+            outln!("{}", DhcpHexDump { packet: buf, spans: &dissect(buf) });
+        }
+    }
+
+    // Something to act on, printed with the packet that caused it. JSON
+    // carries it as the alert field of the packet.
+    // The packet is the parse error for replies that didn't decode.
+    pub fn alert(&self, origin: &Origin, buf: &[u8], packet: Result<&DhcpPacket, String>, message: &str) {
+        match (self.format, packet) {
+            (Format::Json | Format::Jsonl, packet) => {
+                let mut record = export::record_json(origin, buf, packet, self.device(buf));
+                record["alert"] = message.into();
+                self.json(&record);
+            }
+            (Format::Csv, packet) => {
+                eprintln!("ALERT {}", message);
+                match packet {
+                    Ok(packet) => self.packet(origin, buf, packet),
+                    Err(e) => self.export(origin, buf, Err(e)),
+                }
+            }
+            (Format::Text | Format::Summary | Format::Hexdump, Ok(packet)) => {
+                outln!("ALERT {}", message);
+                self.packet(origin, buf, packet);
+            }
+            (Format::Text | Format::Summary | Format::Hexdump, Err(e)) => {
+                outln!("ALERT {}", message);
+                outln!("{}: {}", origin.label, e);
+            }
+        }
+    }
+
+    // Reports that aren't packets, kept out of machine readable output.
+    fn report<S: fmt::Display>(&self, report: S) {
+        if self.format.is_machine() {
+            eprintln!("{}", report);
+        } else {
+            outln!("{}", report);
+        }
+    }
+
+    fn transactions(&self, transactions: &[DhcpTransaction]) {
+        for transaction in transactions {
+            self.report(format_args!("transaction {}", transaction));
+        }
+    }
+
+    // Feeds a packet to the transaction tracker, with --transactions.
+    pub fn track(&self, timestamp: Duration, packet: &DhcpPacket) {
+        if let Some(tracking) = &self.tracking {
+            let done = tracking.borrow_mut().tracker.observe(timestamp, packet);
+            self.transactions(&done);
+            self.tick(timestamp);
+        }
+    }
+
+    // Closes stale transactions and prints the statistics when they are due.
+    pub fn tick(&self, timestamp: Duration) {
+        if let Some(tracking) = &self.tracking {
+            let mut tracking = tracking.borrow_mut();
+            let done = tracking.tracker.expire(timestamp);
+            self.transactions(&done);
+            let due = tracking.interval.is_some_and(|x| timestamp >= tracking.last_report + x);
+            if due {
+                tracking.last_report = timestamp;
+                self.report(tracking.tracker.stats());
+            }
+        }
+    }
+
+    // Closes every transaction and prints the final statistics.
+    pub fn finish_tracking(&self) {
+        if let Some(tracking) = &self.tracking {
+            let mut tracking = tracking.borrow_mut();
+            let done = tracking.tracker.finish();
+            self.transactions(&done);
+            self.report(tracking.tracker.stats());
+        }
+    }
+
+    // Whether --filter lets the packet through, packets that don't parse
+    // can't match.
+    pub fn accepts(&self, packet: Option<&DhcpPacket>) -> bool {
+        match (&self.filter, packet) {
+            (None, _) => true,
+            (Some(filter), Some(packet)) => filter.matches(packet),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn record<F>(&self, write: F)
+        where F: FnOnce(&mut Recorder) -> io::Result<()> {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = write(&mut recorder.borrow_mut()) {
+                self.error("recording", e);
+            }
+        }
+    }
+
+    // Decodes and records a packet from a UDP socket bound to `local`.
+    pub fn received(&self, src: SocketAddr, local: SocketAddrV4, buf: &[u8]) -> Option<DhcpPacket> {
+        let packet = self.decode(&self.udp_origin(src.to_string(), src, local.into()), buf);
+        self.record_received(src, local, buf, packet.as_ref());
+        packet
+    }
+
+    // `packet` is None when `buf` doesn't parse.
+    pub fn record_received(&self, src: SocketAddr, local: SocketAddrV4, buf: &[u8], packet: Option<&DhcpPacket>) {
+        if !self.accepts(packet) {
+            return;
+        }
+        if let Some(packet) = packet {
+            self.track(now(), packet);
+        }
+        if let SocketAddr::V4(src) = src {
+            self.record(|recorder| {
+                let comment = self.comment("received", &self.interface, buf, packet);
+                recorder.write_udp(&self.interface, src, local, buf, packet, &comment)
+            });
+        }
+    }
+
+    pub fn sent(&self, local: SocketAddrV4, dst: SocketAddrV4, buf: &[u8], packet: Option<&DhcpPacket>) {
+        if !self.accepts(packet) {
+            return;
+        }
+        if let Some(packet) = packet {
+            self.track(now(), packet);
+        }
+        self.record(|recorder| {
+            let comment = self.comment("sent", &self.interface, buf, packet);
+            recorder.write_udp(&self.interface, local, dst, buf, packet, &comment)
+        });
+    }
+
+    // Where a datagram on a socket of ours came from or went to.
+    pub fn udp_origin<S: Into<String>>(&self, label: S, src: SocketAddr, dst: SocketAddr) -> Origin {
+        let v4 = |x| match x {
+            SocketAddr::V4(x) => Some(x),
+            SocketAddr::V6(_) => None,
+        };
+        Origin {
+            label: label.into(),
+            timestamp: Some(now()),
+            interface: Some(self.interface.clone()),
+            src: v4(src),
+            dst: v4(dst),
+            src_mac: None,
+            vlan: None,
+        }
+    }
+
+    // Decodes and records a frame captured on an interface as is.
+    pub fn captured(&self, interface: &str, frame: &[u8], header: &DhcpFrameHeader, payload: &[u8])
+                    -> Option<DhcpPacket> {
+        let packet = self.decode(&Origin::captured(interface, header), payload);
+        self.record_captured(interface, frame, payload, packet.as_ref());
+        packet
+    }
+
+    // `packet` is None when `payload` doesn't parse.
+    pub fn record_captured(&self, interface: &str, frame: &[u8], payload: &[u8], packet: Option<&DhcpPacket>) {
+        if !self.accepts(packet) {
+            return;
+        }
+        if let Some(packet) = packet {
+            self.track(now(), packet);
+        }
+        self.record(|recorder| {
+            let comment = self.comment("received", interface, payload, packet);
+            recorder.write(DhcpLinkType::Ethernet, interface, frame, &comment)
+        });
+    }
+
+    // Prints the packet, or the parse error and where it happened.
+    // Packets --filter rejects are returned without being printed, the ones
+    // that don't parse are only mentioned with -v then.
+    pub fn decode(&self, origin: &Origin, buf: &[u8]) -> Option<DhcpPacket> {
+        let error = match DhcpPacket::parse(buf) {
+            Ok((_, packet)) => {
+                if self.accepts(Some(&packet)) {
+                    self.packet(origin, buf, &packet);
+                }
+                return Some(packet);
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e.to_string(),
+            Err(nom::Err::Incomplete(_)) => "incomplete packet".to_string(),
+        };
+        if self.filter.is_some() {
+            self.debug(format_args!("{}: {}", origin.label, error));
+            return None;
+        }
+        self.error(&origin.label, &error);
+        if self.format.is_machine() {
+            self.export(origin, buf, Err(error));
+        }
+        None
+    }
+}
+
+pub fn frame_origin(header: &DhcpFrameHeader) -> String {
+    let origin = format!("{}:{} -> {}:{}", header.src_ip, header.src_port, header.dst_ip, header.dst_port);
+    match header.vlan {
+        Some(vlan) => format!("{} vlan {}", origin, vlan),
+        None => origin,
+    }
+}
+
+// UTC with microseconds, 2024-05-01 10:00:00.123456.
+pub fn format_timestamp(timestamp: Duration) -> String {
+    let secs = timestamp.as_secs();
+    let (days, time) = ((secs / 86400) as i64, secs % 86400);
+    // Days to a civil date, from Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}", year, month, day, time / 3600, time / 60 % 60, time % 60,
+            timestamp.subsec_micros())
+}
+
+pub fn bind_udp(addr: SocketAddrV4) -> Result<UdpSocket, CliError> {
+    let socket = UdpSocket::bind(addr).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => CliError::new(format!(
+            "permission denied binding port {}: run as root or grant CAP_NET_BIND_SERVICE", addr.port())),
+        io::ErrorKind::AddrInUse => CliError::new(format!(
+            "port {} is already in use, is a DHCP server or client running? \
+             `dhcp-rs sniff` watches traffic without binding a port", addr.port())),
+        io::ErrorKind::AddrNotAvailable => CliError::new(format!(
+            "{} is not an address of this host", addr.ip())),
+        _ => CliError::new(format!("binding {}: {}", addr, e)),
+    })?;
+    socket.set_broadcast(true).map_err(|e| CliError::new(format!("enabling broadcast on {}: {}", addr, e)))?;
+    Ok(socket)
+}
+
+// Waits for the next datagram, None once the deadline has passed.
+pub fn recv_until(socket: &UdpSocket, deadline: Instant, buf: &mut [u8])
+                  -> Result<Option<(usize, SocketAddr)>, CliError> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Ok(None);
+    }
+    socket.set_read_timeout(Some(deadline - now)).map_err(|e| CliError::new(format!("setting timeout: {}", e)))?;
+    match socket.recv_from(buf) {
+        Ok(x) => Ok(Some(x)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => Ok(None),
+        Err(e) => Err(CliError::new(format!("receiving: {}", e))),
+    }
+}
+
+pub fn find_interface(name: &str) -> Result<NetworkInterface, CliError> {
+    let interfaces = datalink::interfaces();
+    match interfaces.iter().find(|x| x.name == name) {
+        Some(interface) => Ok(interface.clone()),
+        None => {
+            let names: Vec<_> = interfaces.iter().map(|x| x.name.as_str()).collect();
+            Err(CliError::usage(format!("no interface named {}, available: {}", name, names.join(", "))))
+        }
+    }
+}
+
+// The first interface that is up, not loopback and has an IPv4 address.
+pub fn default_interface() -> Result<NetworkInterface, CliError> {
+    datalink::interfaces().into_iter()
+        .find(|x| x.is_up() && !x.is_loopback() && x.ips.iter().any(|x| x.is_ipv4()))
+        .ok_or_else(|| CliError::usage("no interface is up with an IPv4 address, pick one with --interface"))
+}
+
+pub fn interface_ipv4(interface: &NetworkInterface) -> Result<Ipv4Addr, CliError> {
+    interface.ips.iter()
+        .find_map(|x| match x.ip() {
+            IpAddr::V4(addr) => Some(addr),
+            IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| CliError::new(format!("{} has no IPv4 address", interface.name)))
+}
+
+pub fn parse_mac(s: &str) -> Result<MacAddr, CliError> {
+    let octets = s.split(&[':', '-'][..])
+        .map(|x| u8::from_str_radix(x, 16).ok())
+        .collect::<Option<Vec<u8>>>();
+    match octets {
+        Some(octets) if octets.len() == 6 => {
+            let mut mac = MacAddr::default();
+            mac.0.copy_from_slice(&octets);
+            Ok(mac)
+        }
+        _ => Err(CliError::usage(format!("{} is not a MAC address", s))),
+    }
+}
+
+pub fn interface_mac(interface: &NetworkInterface) -> Result<MacAddr, CliError> {
+    interface.mac.map(MacAddr::from)
+        .ok_or_else(|| CliError::new(format!("{} has no MAC address", interface.name)))
+}
+
+// One hex encoded packet per line or hex dumps, see input::parse_hex.
+pub fn read_hex_packets(path: &str) -> io::Result<Vec<Vec<u8>>> {
+    input::parse_hex(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+}
@@ -0,0 +1,75 @@
+use crate::cli::{format_timestamp, frame_origin, CliError, CliResult, Origin, Output, EXIT_FAILURE, EXIT_OK};
+use dhcp_rs::dhcp_frame::{DhcpFrameError, DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::ParseMode;
+use dhcp_rs::dhcp_pcap::DhcpPcapReader;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+
+// Frames are numbered from 1 like Wireshark does, so they can be looked up
+// there. Checksums aren't verified, captures from the sending host get them
+// from the NIC after the capture point. Exits with 1 when any frame or DHCP
+// packet was reported.
+pub fn pcap(output: &Output, path: &str) -> CliResult {
+    let file = File::open(path).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+    let reader = DhcpPcapReader::new(BufReader::new(file)).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+    let mut failed = false;
+    let mut unsupported = HashSet::new();
+    for (index, record) in reader.enumerate() {
+        let number = index + 1;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                output.error(path, format!("frame {}: {}", number, e));
+                failed = true;
+                break;
+            }
+        };
+        let link_type = match DhcpLinkType::from_linktype(record.link_type) {
+            Some(link_type) => link_type,
+            None => {
+                if unsupported.insert(record.link_type) {
+                    output.error(path, format!("skipping frames with unsupported link type {}", record.link_type));
+                }
+                continue;
+            }
+        };
+        let time = match &record.interface {
+            Some(interface) => format!("{} {}", format_timestamp(record.timestamp), interface),
+            None => format_timestamp(record.timestamp),
+        };
+        let error = match DhcpFrameHeader::parse_link_with_mode(link_type, &record.data, ParseMode::Lenient) {
+            Ok((header, payload)) => {
+                let origin = Origin {
+                    timestamp: Some(record.timestamp),
+                    interface: record.interface.clone(),
+                    ..Origin::frame(format!("#{} {} {}", number, time, frame_origin(&header)), &header)
+                };
+                match output.decode(&origin, payload) {
+                    Some(packet) if output.accepts(Some(&packet)) => output.track(record.timestamp, &packet),
+                    Some(_) => (),
+                    None => failed |= output.filter.is_none(),
+                }
+                continue;
+            }
+            Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) | Err(DhcpFrameError::WrongPorts { .. }) =>
+                continue,
+            // Later fragments don't carry the UDP header, there's no telling
+            // whether they are DHCP.
+            Err(DhcpFrameError::Fragmented) => {
+                output.debug(format!("#{} {}: fragmented IPv4 packet", number, time));
+                continue;
+            }
+            Err(e) => e,
+        };
+        failed = true;
+        if record.data.len() < record.original_len {
+            output.error(&format!("#{} {}", number, time),
+                         format!("{}, captured {} of {} bytes", error, record.data.len(), record.original_len));
+        } else {
+            output.error(&format!("#{} {}", number, time), error);
+        }
+    }
+    output.finish_tracking();
+    Ok(if failed { EXIT_FAILURE } else { EXIT_OK })
+}
@@ -0,0 +1,321 @@
+use crate::cli::input::parse_hex_digits;
+use crate::cli::sniff::open_channel;
+use crate::cli::{
+    default_interface, find_interface, interface_mac, now, parse_mac, CliError, CliResult, Origin, Output,
+    EXIT_FAILURE, EXIT_OK,
+};
+use dhcp_rs::dhcp_frame::{DhcpFrameError, DhcpFrameHeader, DhcpLinkType, DHCP_SERVER_PORT};
+use dhcp_rs::dhcp_packet::{
+    BootpOpcode, DhcpBytes, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpOptionIDs, DhcpPacket, MacAddr, ParseMode,
+};
+use pnet::datalink::{DataLinkReceiver, DataLinkSender};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::process;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, clap::Args)]
+pub struct ProbeArgs {
+    /// Hardware address to use, defaults to the MAC address of --interface
+    #[arg(long)]
+    pub chaddr: Option<String>,
+    /// Client identifier in hex, such as 01:02:fc:00:00:00:01
+    #[arg(long, value_name = "HEX")]
+    pub client_id: Option<String>,
+    /// Option codes to ask for, in this order
+    #[arg(long, value_delimiter = ',', default_value = "1,3,6,15,51,54")]
+    pub params: Vec<u8>,
+    /// Send an INFORM for this address instead of a DISCOVER
+    #[arg(long, value_name = "ADDRESS", conflicts_with = "request")]
+    pub inform: Option<Ipv4Addr>,
+    /// Seconds to collect replies for
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+    /// Request the offered address and wait for the ACK
+    #[arg(long)]
+    pub request: bool,
+    /// Server ID of the offer to request, the first one by default
+    #[arg(long, value_name = "ADDRESS", requires = "request")]
+    pub server: Option<Ipv4Addr>,
+    /// Release the address again once it is acknowledged
+    #[arg(long, requires = "request")]
+    pub release: bool,
+}
+
+struct Probe<'a> {
+    output: &'a Output,
+    args: &'a ProbeArgs,
+    interface: &'a str,
+    tx: Box<dyn DataLinkSender>,
+    rx: Box<dyn DataLinkReceiver>,
+    // Source of the frames, chaddr can be another client's.
+    mac: MacAddr,
+    port: u16,
+    chaddr: MacAddr,
+    client_id: Option<Vec<u8>>,
+    xid: u32,
+}
+
+fn server_id(packet: &DhcpPacket) -> Option<Ipv4Addr> {
+    match packet.options.get(&DhcpOptionID::ServerID) {
+        Some(DhcpOption::ServerID(x)) => Some(*x),
+        _ => None,
+    }
+}
+
+// The options of a reply, one per line, for the report of the probe.
+fn options(packet: &DhcpPacket) -> String {
+    let mut options: Vec<_> = packet.options.iter().filter(|(x, _)| **x != DhcpOptionID::MsgType).collect();
+    options.sort_by_key(|(x, _)| x.code());
+    options.iter().map(|(option_id, option)| format!("\n    {}: {}", option_id, option)).collect()
+}
+
+impl<'a> Probe<'a> {
+    fn request(&self, message_type: DhcpMessageType) -> DhcpPacket {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, self.xid, self.chaddr);
+        packet.broadcast = true;
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        if let Some(client_id) = &self.client_id {
+            packet.options.insert(DhcpOptionID::ClientIdentifier,
+                                  DhcpOption::ClientIdentifier(DhcpBytes::from(client_id.clone())));
+        }
+        let requested: Vec<_> = self.args.params.iter().map(|x| DhcpOptionID::from(*x)).collect();
+        packet.options.insert(DhcpOptionID::ParameterRequestList,
+                              DhcpOption::ParameterRequestList(DhcpOptionIDs::from(&requested)));
+        packet
+    }
+
+    // Unicasts go to the broadcast MAC as well, there's no ARP here.
+    fn send(&mut self, packet: &DhcpPacket, dst: SocketAddrV4) -> Result<(), CliError> {
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).map_err(|e| CliError::new(format!("encoding the packet: {}", e)))?;
+        let header = DhcpFrameHeader {
+            src_ip: packet.ciaddr.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            dst_ip: *dst.ip(),
+            src_port: self.port,
+            dst_port: dst.port(),
+            ..DhcpFrameHeader::for_request(self.mac)
+        };
+        let mut frame = Vec::new();
+        header.encode(&buf, &mut frame).map_err(|e| CliError::new(format!("encoding the frame: {}", e)))?;
+        self.output.packet(&Origin::frame(format!("-> {}", dst), &header), &buf, packet);
+        self.tx.send_to(&frame, None)
+            .unwrap_or_else(|| Err(io::Error::other("no room in the send buffer")))
+            .map_err(|e| CliError::new(format!("sending on {}: {}", self.interface, e)))?;
+        self.output.record_captured(self.interface, &frame, &buf, Some(packet));
+        Ok(())
+    }
+
+    fn broadcast(&mut self, packet: &DhcpPacket) -> Result<(), CliError> {
+        self.send(packet, SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_SERVER_PORT))
+    }
+
+    // The next reply to our transaction of one of the expected types, None
+    // once the deadline passes.
+    fn reply(&mut self, deadline: Instant, expected: &[DhcpMessageType])
+             -> Result<Option<(DhcpFrameHeader, DhcpPacket)>, CliError> {
+        while Instant::now() < deadline {
+            let frame = match self.rx.next() {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => return Err(CliError::new(format!("capturing on {}: {}", self.interface, e))),
+            };
+            let (header, payload) =
+                match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, frame, ParseMode::Lenient) {
+                    Ok(x) => x,
+                    Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) |
+                    Err(DhcpFrameError::WrongPorts { .. }) => continue,
+                    Err(e) => {
+                        self.output.debug(format!("{}: {}", self.interface, e));
+                        continue;
+                    }
+                };
+            // Our own requests come back on the channel too. A reply to us
+            // that doesn't decode even leniently is reported, not skipped.
+            let origin = Origin::captured(self.interface, &header);
+            let reply = match DhcpPacket::parse_with_mode(payload, ParseMode::Lenient) {
+                Ok((_, reply)) if reply.opcode == BootpOpcode::BootReply => reply,
+                Ok(_) => continue,
+                Err(e) => {
+                    self.output.record_captured(self.interface, frame, payload, None);
+                    let error = match e {
+                        nom::Err::Error(e) | nom::Err::Failure(e) => e.to_string(),
+                        nom::Err::Incomplete(_) => "incomplete packet".to_string(),
+                    };
+                    match payload {
+                        [op, _, _, _, a, b, c, d, ..] if *op == BootpOpcode::BootReply.code() &&
+                            u32::from_be_bytes([*a, *b, *c, *d]) == self.xid => self.output.error(&origin.label, error),
+                        _ => self.output.debug(format!("{}: {}", origin.label, error)),
+                    }
+                    continue;
+                }
+            };
+            self.output.record_captured(self.interface, frame, payload, Some(&reply));
+            if reply.xid == self.xid && reply.message_type().is_some_and(|x| expected.contains(x)) {
+                self.output.packet(&origin, payload, &reply);
+                return Ok(Some((header, reply)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Keeps the first reply of every server. Servers are told apart by their
+// Server ID, or by the addresses they sent from when they leave it out.
+fn add_reply(replies: &mut Vec<(DhcpFrameHeader, DhcpPacket)>, header: DhcpFrameHeader, reply: DhcpPacket) {
+    let key = |header: &DhcpFrameHeader, reply: &DhcpPacket| match server_id(reply) {
+        Some(server_id) => (Some(server_id), None),
+        None => (None, Some((header.src_ip, header.src_mac))),
+    };
+    if replies.iter().all(|(x, y)| key(x, y) != key(&header, &reply)) {
+        replies.push((header, reply));
+    }
+}
+
+// How the report names a server.
+fn server_name(header: &DhcpFrameHeader, reply: &DhcpPacket) -> String {
+    match server_id(reply) {
+        Some(server_id) => server_id.to_string(),
+        None => format!("{} ({}) without server id", header.src_ip, header.src_mac),
+    }
+}
+
+// Sends one DISCOVER, or INFORM, and lists every server answering it within
+// --timeout. Servers answering more than once are listed once. Frames go
+// straight to the interface, so no port is bound and a DHCP client running
+// on the host is left alone.
+pub fn probe(output: &Output, args: &ProbeArgs, interface: Option<&str>, port: u16) -> CliResult {
+    let interface = match interface {
+        Some(name) => find_interface(name)?,
+        None => default_interface()?,
+    };
+    let mac = interface_mac(&interface)?;
+    let chaddr = match &args.chaddr {
+        Some(chaddr) => parse_mac(chaddr)?,
+        None => mac,
+    };
+    let client_id = match &args.client_id {
+        Some(hex) => Some(parse_hex_digits(&hex.replace(':', ""))
+            .ok_or_else(|| CliError::usage(format!("--client-id {} is not hex", hex)))?),
+        None => None,
+    };
+    let xid = now().subsec_nanos() ^ process::id();
+    let (tx, rx) = open_channel(&interface)?;
+    let mut probe = Probe { output, args, interface: &interface.name, tx, rx, mac, port, chaddr, client_id, xid };
+    let timeout = Duration::from_secs(args.timeout);
+
+    let (packet, expected) = match args.inform {
+        Some(addr) => {
+            let mut inform = probe.request(DhcpMessageType::DhcpInform);
+            inform.ciaddr = Some(addr);
+            (inform, [DhcpMessageType::DhcpAck])
+        }
+        None => (probe.request(DhcpMessageType::DhcpDiscover), [DhcpMessageType::DhcpOffer]),
+    };
+    probe.broadcast(&packet)?;
+    let deadline = Instant::now() + timeout;
+    let mut replies = Vec::new();
+    while let Some((header, reply)) = probe.reply(deadline, &expected)? {
+        add_reply(&mut replies, header, reply);
+    }
+    if replies.is_empty() {
+        output.error("probe", format!("no {} within {}s", expected[0].to_string().to_uppercase(), args.timeout));
+        return Ok(EXIT_FAILURE);
+    }
+    for (header, reply) in &replies {
+        let server = server_name(header, reply);
+        match reply.yiaddr {
+            Some(addr) => output.report(format_args!("server {} offered {}{}", server, addr, options(reply))),
+            None => output.report(format_args!("server {} answered{}", server, options(reply))),
+        }
+    }
+    if !args.request {
+        return Ok(EXIT_OK);
+    }
+
+    let offer = match replies.iter().map(|(_, x)| x).find(|x| args.server.is_none() || server_id(x) == args.server) {
+        Some(offer) => offer,
+        None => {
+            output.error("probe", format!("no offer from {}", args.server.unwrap_or(Ipv4Addr::UNSPECIFIED)));
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    let (offered, server_id) = match (offer.yiaddr, server_id(offer)) {
+        (Some(addr), Some(server_id)) => (addr, server_id),
+        _ => {
+            output.error("probe", "offer without an address or server identifier");
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    let mut request = probe.request(DhcpMessageType::DhcpRequest);
+    request.options.insert(DhcpOptionID::RequestedIpAddr, DhcpOption::RequestedIpAddr(offered));
+    request.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(server_id));
+    probe.broadcast(&request)?;
+    let expected = [DhcpMessageType::DhcpAck, DhcpMessageType::DhcpNak];
+    let ack = match probe.reply(Instant::now() + timeout, &expected)? {
+        Some((_, ack)) => ack,
+        None => {
+            output.error("probe", "no answer to the request");
+            return Ok(EXIT_FAILURE);
+        }
+    };
+    if ack.message_type() == Some(&DhcpMessageType::DhcpNak) {
+        output.error("probe", format!("{} refused {}", server_id, offered));
+        return Ok(EXIT_FAILURE);
+    }
+    output.report(format_args!("lease {} from {}{}", offered, server_id, options(&ack)));
+
+    if args.release {
+        let mut release = DhcpPacket::new(BootpOpcode::BootRequest, xid, chaddr);
+        release.ciaddr = Some(offered);
+        release.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpRelease));
+        release.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(server_id));
+        if let Some(client_id) = &probe.client_id {
+            release.options.insert(DhcpOptionID::ClientIdentifier,
+                                   DhcpOption::ClientIdentifier(DhcpBytes::from(client_id.clone())));
+        }
+        probe.send(&release, SocketAddrV4::new(server_id, DHCP_SERVER_PORT))?;
+    }
+    Ok(EXIT_OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(server_id: Option<Ipv4Addr>) -> DhcpPacket {
+        let mut offer = DhcpPacket::new(BootpOpcode::BootReply, 1, MacAddr::default());
+        offer.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpOffer));
+        if let Some(server_id) = server_id {
+            offer.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(server_id));
+        }
+        offer
+    }
+
+    fn header(src_ip: [u8; 4], src_mac: u8) -> DhcpFrameHeader {
+        DhcpFrameHeader {
+            src_mac: MacAddr([0x02, 0, 0, 0, 0, src_mac]),
+            src_ip: src_ip.into(),
+            ..DhcpFrameHeader::for_request(MacAddr::default())
+        }
+    }
+
+    #[test]
+    fn one_reply_per_server() {
+        let mut replies = Vec::new();
+        let server = Some(Ipv4Addr::new(192, 0, 2, 1));
+        add_reply(&mut replies, header([192, 0, 2, 1], 1), offer(server));
+        // The same Server ID through another relay is the same server.
+        add_reply(&mut replies, header([192, 0, 2, 9], 9), offer(server));
+        add_reply(&mut replies, header([192, 0, 2, 2], 2), offer(Some(Ipv4Addr::new(192, 0, 2, 2))));
+        // Without a Server ID the source tells servers apart.
+        add_reply(&mut replies, header([192, 0, 2, 3], 3), offer(None));
+        add_reply(&mut replies, header([192, 0, 2, 3], 3), offer(None));
+        add_reply(&mut replies, header([192, 0, 2, 3], 4), offer(None));
+        add_reply(&mut replies, header([192, 0, 2, 4], 3), offer(None));
+        let names: Vec<_> = replies.iter().map(|(x, y)| server_name(x, y)).collect();
+        assert_eq!(names, ["192.0.2.1", "192.0.2.2", "192.0.2.3 (02:00:00:00:00:03) without server id",
+                           "192.0.2.3 (02:00:00:00:00:04) without server id",
+                           "192.0.2.4 (02:00:00:00:00:03) without server id"]);
+    }
+}
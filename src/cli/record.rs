@@ -0,0 +1,95 @@
+use crate::cli::{now, CliError};
+use dhcp_rs::dhcp_frame::{DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::{BootpOpcode, DhcpPacket, MacAddr};
+use dhcp_rs::dhcp_pcap::DhcpPcapWriter;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::net::SocketAddrV4;
+use std::path::{Path, PathBuf};
+
+// Writes to `path` until it grows past `max_size` bytes, then moves it to
+// name.1.pcapng, the previous name.1.pcapng to name.2.pcapng and so on,
+// keeping at most `max_files` files.
+pub struct Recorder {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    writer: DhcpPcapWriter<BufWriter<File>>,
+    // Interfaces declared in the current file, by link type and name.
+    interfaces: Vec<(DhcpLinkType, String)>,
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+fn create(path: &Path) -> io::Result<DhcpPcapWriter<BufWriter<File>>> {
+    DhcpPcapWriter::new(BufWriter::new(File::create(path)?))
+}
+
+impl Recorder {
+    pub fn new(path: &str, max_size: u64, max_files: usize) -> Result<Self, CliError> {
+        let path = PathBuf::from(path);
+        let writer = create(&path).map_err(|e| CliError::new(format!("{}: {}", path.display(), e)))?;
+        Ok(Recorder { path, max_size, max_files: max_files.max(1), writer, interfaces: Vec::new() })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        for index in (1..self.max_files).rev() {
+            let from = if index == 1 { self.path.clone() } else { rotated_path(&self.path, index - 1) };
+            match fs::rename(&from, rotated_path(&self.path, index)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        self.writer = create(&self.path)?;
+        self.interfaces.clear();
+        Ok(())
+    }
+
+    pub fn write(&mut self, link_type: DhcpLinkType, interface: &str, data: &[u8], comment: &str) -> io::Result<()> {
+        if self.writer.written() >= self.max_size {
+            self.rotate()?;
+        }
+        let id = match self.interfaces.iter().position(|(x, name)| *x == link_type && name == interface) {
+            Some(id) => id as u32,
+            None => {
+                self.interfaces.push((link_type, interface.to_string()));
+                self.writer.add_interface(link_type.linktype(), Some(interface))?
+            }
+        };
+        self.writer.write_record(id, now(), data, Some(comment))?;
+        // Commands run until they are killed, keep the file complete.
+        self.writer.flush()
+    }
+
+    // UDP sockets only hand over the payload, the Ethernet, IPv4 and UDP
+    // headers are made up from the addresses. The source MAC address is the
+    // client's for requests and zero otherwise.
+    pub fn write_udp(&mut self, interface: &str, src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8],
+                     packet: Option<&DhcpPacket>, comment: &str) -> io::Result<()> {
+        let src_mac = match packet {
+            Some(packet) if packet.opcode == BootpOpcode::BootRequest && packet.giaddr.is_none() => packet.chaddr,
+            _ => MacAddr::default(),
+        };
+        let dst_mac = if dst.ip().is_broadcast() { MacAddr([0xff; 6]) } else { MacAddr::default() };
+        let header = DhcpFrameHeader {
+            src_mac,
+            dst_mac,
+            vlan: None,
+            src_ip: *src.ip(),
+            dst_ip: *dst.ip(),
+            src_port: src.port(),
+            dst_port: dst.port(),
+        };
+        let mut frame = Vec::new();
+        header.encode(payload, &mut frame).map_err(|e| io::Error::other(e.to_string()))?;
+        self.write(DhcpLinkType::Ethernet, interface, &frame, comment)
+    }
+}
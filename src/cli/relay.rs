@@ -0,0 +1,77 @@
+use crate::cli::{bind_udp, find_interface, interface_ipv4, CliError, CliResult, Output, MAX_PACKET_SIZE};
+use dhcp_rs::dhcp_frame::{DHCP_CLIENT_PORT, DHCP_SERVER_PORT};
+use dhcp_rs::dhcp_packet::{BootpOpcode, DhcpPacket};
+use dhcp_rs::dhcp_view::DhcpPacketView;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+// RFC 1542 section 4.1.1 suggests discarding requests that went through
+// this many relays.
+const MAX_HOPS: u8 = 16;
+
+const HOPS_OFFSET: usize = 3;
+const GIADDR_OFFSET: usize = 24;
+
+#[derive(Debug, clap::Args)]
+pub struct RelayArgs {
+    /// Server to forward requests to, can be given more than once
+    #[arg(long, required = true)]
+    pub server: Vec<Ipv4Addr>,
+    /// Address put in giaddr, defaults to the address of --interface
+    #[arg(long)]
+    pub giaddr: Option<Ipv4Addr>,
+}
+
+// Packets are forwarded as received apart from hops and giaddr, so options
+// this crate doesn't know survive the trip.
+pub fn relay(output: &Output, args: &RelayArgs, interface: Option<&str>, port: u16) -> CliResult {
+    let giaddr = match (args.giaddr, interface) {
+        (Some(addr), _) => addr,
+        (None, Some(name)) => interface_ipv4(&find_interface(name)?)?,
+        (None, None) => return Err(CliError::usage("relay needs --giaddr or --interface")),
+    };
+    let socket = bind_udp(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    let local = SocketAddrV4::new(giaddr, port);
+    output.info(format!("relaying as {} to {}", giaddr,
+                        args.server.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")));
+
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    loop {
+        let (length, src_addr) = socket.recv_from(&mut buf).map_err(|e| CliError::new(format!("receiving: {}", e)))?;
+        let origin = src_addr.to_string();
+        let buf = &mut buf[..length];
+        output.received(src_addr, local, buf);
+        let (opcode, hops, view_giaddr, ciaddr, broadcast) = match DhcpPacketView::parse(buf) {
+            Ok((_, view)) => (view.opcode(), view.hops(), view.giaddr(), view.ciaddr(), view.broadcast()),
+            Err(_) => continue,
+        };
+        let destinations: Vec<_> = match opcode {
+            BootpOpcode::BootRequest if hops >= MAX_HOPS => {
+                output.info(format!("{}: dropped after {} hops", origin, hops));
+                continue;
+            }
+            BootpOpcode::BootRequest => {
+                buf[HOPS_OFFSET] = hops + 1;
+                if view_giaddr.is_none() {
+                    buf[GIADDR_OFFSET..GIADDR_OFFSET + 4].copy_from_slice(&giaddr.octets());
+                }
+                args.server.iter().map(|x| SocketAddrV4::new(*x, DHCP_SERVER_PORT)).collect()
+            }
+            BootpOpcode::BootReply if view_giaddr == Some(giaddr) => {
+                let dst_ip = match ciaddr {
+                    Some(addr) if !broadcast => addr,
+                    _ => Ipv4Addr::BROADCAST,
+                };
+                vec![SocketAddrV4::new(dst_ip, DHCP_CLIENT_PORT)]
+            }
+            BootpOpcode::BootReply => continue,
+        };
+        let forwarded = DhcpPacket::parse(buf).ok().map(|(_, x)| x);
+        for dst in destinations {
+            output.debug(format!("-> {}", dst));
+            if let Err(e) = socket.send_to(buf, dst) {
+                output.error(&dst.to_string(), e);
+            }
+            output.sent(local, dst, buf, forwarded.as_ref());
+        }
+    }
+}
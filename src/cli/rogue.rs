@@ -0,0 +1,205 @@
+use crate::cli::sniff::open_channel;
+use crate::cli::{interface_mac, now, parse_mac, CliError, CliResult, Origin, Output, EXIT_FAILURE, EXIT_OK};
+use dhcp_rs::dhcp_frame::{DhcpFrame, DhcpFrameError, DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::{
+    BootpOpcode, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpOptionIDs, DhcpPacket, MacAddr, ParseMode,
+};
+use dhcp_rs::dhcp_view::DhcpPacketView;
+use pnet::datalink::{DataLinkSender, NetworkInterface};
+use std::collections::HashSet;
+use std::io;
+use std::net::Ipv4Addr;
+use std::process;
+use std::time::{Duration, Instant};
+
+// DISCOVERs whose offers are still expected, older ones are forgotten.
+const ROGUE_PROBES: usize = 16;
+const BOOTP_REPLY: u8 = 2;
+
+#[derive(Debug, clap::Args)]
+pub struct RogueArgs {
+    /// A legitimate server or relay agent, by IPv4 or MAC address
+    #[arg(long = "allow", value_name = "SERVER", required = true)]
+    pub allowed: Vec<String>,
+    /// Send a DISCOVER, print the servers offering within --timeout and exit 1 if any is rogue
+    #[arg(long)]
+    pub probe: bool,
+    /// Also send a DISCOVER every this many seconds while watching
+    #[arg(long, value_name = "SECS")]
+    pub probe_interval: Option<u64>,
+    /// Seconds to collect offers for with --probe
+    #[arg(long, default_value_t = 5)]
+    pub timeout: u64,
+}
+
+struct AllowList {
+    addresses: Vec<Ipv4Addr>,
+    macs: Vec<MacAddr>,
+}
+
+impl AllowList {
+    fn new(allowed: &[String]) -> Result<Self, CliError> {
+        let mut list = AllowList { addresses: Vec::new(), macs: Vec::new() };
+        for server in allowed {
+            match server.parse() {
+                Ok(address) => list.addresses.push(address),
+                Err(_) => list.macs.push(parse_mac(server).map_err(|_| CliError::usage(
+                    format!("--allow {} is neither an IPv4 nor a MAC address", server)))?),
+            }
+        }
+        Ok(list)
+    }
+
+    // Relayed replies come from the relay agent while the Server ID still
+    // names the server, both have to be known.
+    fn allows(&self, header: &DhcpFrameHeader, server_id: Option<Ipv4Addr>) -> bool {
+        let known_mac = self.macs.contains(&header.src_mac);
+        let known_source = known_mac || self.addresses.contains(&header.src_ip);
+        let known_server = known_mac || server_id.is_none_or(|x| self.addresses.contains(&x));
+        known_source && known_server
+    }
+}
+
+struct Watch<'a> {
+    output: &'a Output,
+    interface: &'a str,
+    allowed: AllowList,
+    // Servers already reported, by MAC, source and Server ID.
+    alerted: HashSet<(MacAddr, Ipv4Addr, Option<Ipv4Addr>)>,
+    // Transactions of the DISCOVERs we sent.
+    probes: Vec<u32>,
+    offers: usize,
+    rogue: bool,
+}
+
+impl<'a> Watch<'a> {
+    fn frame(&mut self, frame: &[u8]) {
+        let (header, payload) =
+            match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, frame, ParseMode::Lenient) {
+                Ok(x) => x,
+                Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) |
+                Err(DhcpFrameError::WrongPorts { .. }) => return,
+                Err(e) => return self.output.debug(format!("{}: {}", self.interface, e)),
+            };
+        let parsed = DhcpPacket::parse_with_mode(payload, ParseMode::Lenient).map(|(_, x)| x);
+        self.output.record_captured(self.interface, frame, payload, parsed.as_ref().ok());
+        // A reply too broken to decode still gives away its server: the op
+        // and xid are read where they sit, the Server ID when the options
+        // can be walked.
+        let (xid, server_id, reply) = match &parsed {
+            Ok(packet) if packet.opcode == BootpOpcode::BootReply => {
+                let server_id = match packet.options.get(&DhcpOptionID::ServerID) {
+                    Some(DhcpOption::ServerID(x)) => Some(*x),
+                    _ => None,
+                };
+                (packet.xid, server_id, Ok(packet))
+            }
+            Ok(_) => return,
+            Err(e) => match payload {
+                [BOOTP_REPLY, _, _, _, a, b, c, d, ..] => {
+                    let view = DhcpPacketView::parse(payload).ok().map(|(_, x)| x);
+                    let server_id = match view.as_ref().and_then(|x| x.option(DhcpOptionID::ServerID.code())) {
+                        Some(&[a, b, c, d]) => Some(Ipv4Addr::new(a, b, c, d)),
+                        _ => None,
+                    };
+                    let error = match e {
+                        nom::Err::Error(e) | nom::Err::Failure(e) => e.to_string(),
+                        nom::Err::Incomplete(_) => "incomplete packet".to_string(),
+                    };
+                    (u32::from_be_bytes([*a, *b, *c, *d]), server_id, Err(error))
+                }
+                _ => return,
+            },
+        };
+        let origin = Origin::captured(self.interface, &header);
+        let probed = self.probes.contains(&xid);
+        self.offers += probed as usize;
+        let summary = match &reply {
+            Ok(reply) => format!("{} {:#}", origin.label, reply),
+            Err(e) => format!("{} xid={:#010x}: {}", origin.label, xid, e),
+        };
+        if self.allowed.allows(&header, server_id) {
+            match &reply {
+                Ok(reply) if probed => self.output.packet(&origin, payload, reply),
+                Err(e) if probed => self.output.error(&origin.label, e),
+                _ => self.output.debug(summary),
+            }
+            return;
+        }
+        self.rogue = true;
+        if !self.alerted.insert((header.src_mac, header.src_ip, server_id)) && !probed {
+            return self.output.debug(summary);
+        }
+        let message = match server_id {
+            Some(server_id) if server_id != header.src_ip =>
+                format!("rogue DHCP server {} ({}) with server id {}", header.src_ip, header.src_mac, server_id),
+            _ => format!("rogue DHCP server {} ({})", header.src_ip, header.src_mac),
+        };
+        self.output.alert(&origin, payload, reply, &message);
+    }
+
+    fn probe(&mut self, tx: &mut dyn DataLinkSender, mac: MacAddr) -> Result<(), CliError> {
+        let xid = now().subsec_nanos() ^ process::id();
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, xid, mac);
+        packet.broadcast = true;
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        let requested = vec![DhcpOptionID::SubnetMask, DhcpOptionID::Router, DhcpOptionID::DNSserver,
+                             DhcpOptionID::DomainName, DhcpOptionID::LeaseTime];
+        packet.options.insert(DhcpOptionID::ParameterRequestList,
+                              DhcpOption::ParameterRequestList(DhcpOptionIDs::from(&requested)));
+        let mut frame = Vec::new();
+        DhcpFrame { header: DhcpFrameHeader::for_request(mac), packet }.encode(&mut frame)
+            .map_err(|e| CliError::new(format!("encoding DISCOVER: {}", e)))?;
+        tx.send_to(&frame, None)
+            .unwrap_or_else(|| Err(io::Error::other("no room in the send buffer")))
+            .map_err(|e| CliError::new(format!("sending DISCOVER on {}: {}", self.interface, e)))?;
+        self.output.debug(format!("sent DISCOVER xid={:#010x} on {}", xid, self.interface));
+        if self.probes.len() == ROGUE_PROBES {
+            self.probes.remove(0);
+        }
+        self.probes.push(xid);
+        Ok(())
+    }
+}
+
+// Watches for replies from servers missing from --allow and raises an alert
+// for every new one. With --probe it asks the servers itself and exits once
+// the offers are in.
+pub fn rogue(output: &Output, args: &RogueArgs, interface: &NetworkInterface) -> CliResult {
+    let allowed = AllowList::new(&args.allowed)?;
+    let (mut tx, mut rx) = open_channel(interface)?;
+    let probing = args.probe || args.probe_interval.is_some();
+    let mac = if probing { interface_mac(interface)? } else { MacAddr::default() };
+    let mut watch = Watch {
+        output,
+        interface: &interface.name,
+        allowed,
+        alerted: HashSet::new(),
+        probes: Vec::new(),
+        offers: 0,
+        rogue: false,
+    };
+    let deadline = Some(Instant::now() + Duration::from_secs(args.timeout)).filter(|_| args.probe);
+    let interval = args.probe_interval.map(|x| Duration::from_secs(x.max(1)));
+    let mut next_probe = Some(Instant::now()).filter(|_| probing);
+    output.info(format!("watching for rogue DHCP servers on {}", interface.name));
+    loop {
+        let instant = Instant::now();
+        if deadline.is_some_and(|x| instant >= x) {
+            break;
+        }
+        if next_probe.is_some_and(|x| instant >= x) {
+            watch.probe(tx.as_mut(), mac)?;
+            next_probe = interval.filter(|_| !args.probe).map(|x| instant + x);
+        }
+        match rx.next() {
+            Ok(frame) => watch.frame(frame),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => output.tick(now()),
+            Err(e) => return Err(CliError::new(format!("capturing on {}: {}", interface.name, e))),
+        }
+    }
+    if watch.offers == 0 {
+        output.info(format!("no offers on {} within {}s", interface.name, args.timeout));
+    }
+    Ok(if watch.rogue { EXIT_FAILURE } else { EXIT_OK })
+}
@@ -0,0 +1,33 @@
+use crate::cli::{bind_udp, read_hex_packets, recv_until, CliError, CliResult, Output, EXIT_OK, MAX_PACKET_SIZE};
+use dhcp_rs::dhcp_packet::DhcpPacket;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, clap::Args)]
+pub struct SendArgs {
+    /// Packets to send, one hex encoded packet per line
+    pub file: String,
+    /// Destination address
+    #[arg(long, default_value = "255.255.255.255:67")]
+    pub to: SocketAddrV4,
+    /// Seconds to wait for replies after the last packet
+    #[arg(long, default_value_t = 2)]
+    pub wait: u64,
+}
+
+pub fn send(output: &Output, args: &SendArgs, port: u16) -> CliResult {
+    let packets = read_hex_packets(&args.file).map_err(|e| CliError::new(e.to_string()))?;
+    let local = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
+    let socket = bind_udp(local)?;
+    for buf in &packets {
+        socket.send_to(buf, args.to).map_err(|e| CliError::new(format!("sending to {}: {}", args.to, e)))?;
+        output.info(format!("sent {} bytes to {}", buf.len(), args.to));
+        output.sent(local, args.to, buf, DhcpPacket::parse(buf).ok().map(|(_, x)| x).as_ref());
+    }
+    let deadline = Instant::now() + Duration::from_secs(args.wait);
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    while let Some((length, src_addr)) = recv_until(&socket, deadline, &mut buf)? {
+        output.received(src_addr, local, &buf[..length]);
+    }
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,286 @@
+use crate::cli::{bind_udp, find_interface, interface_ipv4, CliError, CliResult, Output, MAX_PACKET_SIZE};
+use dhcp_rs::dhcp_frame::DhcpFrameHeader;
+use dhcp_rs::dhcp_packet::{
+    BootpOpcode, DhcpDuration, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpPacket, MacAddr,
+};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// How long an offered address is held for the client it was offered to, so a
+// DISCOVER from another client in the meantime gets a different one.
+const SERVE_OFFER_HOLD: Duration = Duration::from_secs(60);
+// How long a declined address stays out of the pool, RFC 2131 section 4.3.3
+// only says to mark it unavailable.
+const SERVE_DECLINE_HOLD: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy)]
+pub struct AddrRange {
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+}
+
+impl FromStr for AddrRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').ok_or("expected START-END")?;
+        let start: Ipv4Addr = start.trim().parse().map_err(|e| format!("{}: {}", start, e))?;
+        let end: Ipv4Addr = end.trim().parse().map_err(|e| format!("{}: {}", end, e))?;
+        if u32::from(start) > u32::from(end) {
+            return Err(format!("{} comes after {}", start, end));
+        }
+        Ok(AddrRange { start, end })
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ServeArgs {
+    /// Addresses to hand out, for example 192.168.1.100-192.168.1.200
+    #[arg(long)]
+    pub range: AddrRange,
+    #[arg(long, default_value = "255.255.255.0")]
+    pub netmask: Ipv4Addr,
+    #[arg(long)]
+    pub router: Option<Ipv4Addr>,
+    /// DNS server, can be given more than once
+    #[arg(long)]
+    pub dns: Vec<Ipv4Addr>,
+    #[arg(long)]
+    pub domain: Option<String>,
+    /// Lease time in seconds
+    #[arg(long, default_value_t = 3600)]
+    pub lease_time: u64,
+    /// Server identifier, defaults to the address of --interface
+    #[arg(long)]
+    pub server_ip: Option<Ipv4Addr>,
+}
+
+struct Lease {
+    addr: Ipv4Addr,
+    expires: Instant,
+    // The matching signature of the client, if any.
+    device: Option<String>,
+}
+
+// Leases only live in memory, they are forgotten when the server stops.
+struct Server<'a> {
+    args: &'a ServeArgs,
+    server_ip: Ipv4Addr,
+    leases: HashMap<MacAddr, Lease>,
+    // Addresses offered and not requested yet, with the end of their hold.
+    offers: HashMap<MacAddr, (Ipv4Addr, Instant)>,
+    // Addresses a client found in use, with the end of their quarantine.
+    declined: HashMap<Ipv4Addr, Instant>,
+}
+
+impl<'a> Server<'a> {
+    fn is_free(&self, addr: Ipv4Addr, chaddr: MacAddr, now: Instant) -> bool {
+        self.declined.get(&addr).is_none_or(|x| *x <= now) &&
+            self.leases.iter().all(|(mac, lease)| lease.addr != addr || *mac == chaddr || lease.expires <= now) &&
+            self.offers.iter().all(|(mac, (offered, expires))| *offered != addr || *mac == chaddr || *expires <= now)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.offers.retain(|_, (_, expires)| *expires > now);
+        self.declined.retain(|_, expires| *expires > now);
+    }
+
+    fn in_range(&self, addr: Ipv4Addr) -> bool {
+        (u32::from(self.args.range.start)..=u32::from(self.args.range.end)).contains(&u32::from(addr))
+    }
+
+    // Keeps the address a client had or was offered before unless it went to
+    // someone else since, then honours the address it asks for and otherwise
+    // takes the first free one.
+    fn pick(&mut self, request: &DhcpPacket, now: Instant) -> Option<Ipv4Addr> {
+        if let Some((addr, _)) = self.offers.get(&request.chaddr) {
+            return Some(*addr);
+        }
+        if let Some(lease) = self.leases.get(&request.chaddr) {
+            if self.is_free(lease.addr, request.chaddr, now) {
+                return Some(lease.addr);
+            }
+            self.leases.remove(&request.chaddr);
+        }
+        if let Some(DhcpOption::RequestedIpAddr(addr)) = request.options.get(&DhcpOptionID::RequestedIpAddr) {
+            if self.in_range(*addr) && self.is_free(*addr, request.chaddr, now) {
+                return Some(*addr);
+            }
+        }
+        (u32::from(self.args.range.start)..=u32::from(self.args.range.end))
+            .map(Ipv4Addr::from)
+            .find(|x| self.is_free(*x, request.chaddr, now))
+    }
+
+    fn reply(&self, request: &DhcpPacket, message_type: DhcpMessageType) -> DhcpPacket {
+        let mut reply = DhcpPacket::new(BootpOpcode::BootReply, request.xid, request.chaddr);
+        reply.htype = request.htype;
+        reply.hlen = request.hlen;
+        reply.broadcast = request.broadcast;
+        reply.giaddr = request.giaddr;
+        reply.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        reply.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(self.server_ip));
+        reply
+    }
+
+    fn configure(&self, reply: &mut DhcpPacket, with_lease: bool) {
+        let args = self.args;
+        reply.options.insert(DhcpOptionID::SubnetMask, DhcpOption::SubNetMask(args.netmask.into()));
+        if let Some(router) = args.router {
+            reply.options.insert(DhcpOptionID::Router, DhcpOption::Router(vec![router].into()));
+        }
+        if !args.dns.is_empty() {
+            reply.options.insert(DhcpOptionID::DNSserver, DhcpOption::DNSserver(args.dns.clone().into()));
+        }
+        if let Some(domain) = &args.domain {
+            reply.options.insert(DhcpOptionID::DomainName, DhcpOption::DomainName(domain.clone()));
+        }
+        if with_lease {
+            reply.options.insert(DhcpOptionID::LeaseTime, DhcpOption::LeaseTime(DhcpDuration::new(args.lease_time, 0)));
+        }
+    }
+
+    fn offer(&mut self, request: &DhcpPacket, now: Instant) -> Option<DhcpPacket> {
+        let addr = self.pick(request, now)?;
+        self.offers.insert(request.chaddr, (addr, now + SERVE_OFFER_HOLD));
+        let mut reply = self.reply(request, DhcpMessageType::DhcpOffer);
+        reply.yiaddr = Some(addr);
+        self.configure(&mut reply, true);
+        Some(reply)
+    }
+
+    fn ack(&mut self, request: &DhcpPacket, device: Option<String>, now: Instant) -> Option<DhcpPacket> {
+        match request.options.get(&DhcpOptionID::ServerID) {
+            // The client picked another server's offer.
+            Some(DhcpOption::ServerID(addr)) if *addr != self.server_ip => {
+                self.leases.remove(&request.chaddr);
+                self.offers.remove(&request.chaddr);
+                return None;
+            }
+            _ => (),
+        }
+        let wanted = match request.options.get(&DhcpOptionID::RequestedIpAddr) {
+            Some(DhcpOption::RequestedIpAddr(addr)) => Some(*addr),
+            _ => request.ciaddr,
+        };
+        let addr = wanted.filter(|x| self.in_range(*x) && self.is_free(*x, request.chaddr, now));
+        let addr = match addr {
+            Some(addr) => addr,
+            None => {
+                let mut reply = self.reply(request, DhcpMessageType::DhcpNak);
+                reply.broadcast = true;
+                return Some(reply);
+            }
+        };
+        let expires = now + Duration::from_secs(self.args.lease_time);
+        self.offers.remove(&request.chaddr);
+        self.leases.insert(request.chaddr, Lease { addr, expires, device });
+        let mut reply = self.reply(request, DhcpMessageType::DhcpAck);
+        reply.ciaddr = request.ciaddr;
+        reply.yiaddr = Some(addr);
+        self.configure(&mut reply, true);
+        Some(reply)
+    }
+
+    fn handle(&mut self, output: &Output, request: &DhcpPacket, buf: &[u8]) -> Option<DhcpPacket> {
+        if request.opcode != BootpOpcode::BootRequest {
+            return None;
+        }
+        let now = Instant::now();
+        self.expire(now);
+        match request.message_type()? {
+            DhcpMessageType::DhcpDiscover => {
+                let reply = self.offer(request, now);
+                if reply.is_none() {
+                    output.info(format!("no free address for {}", request.chaddr));
+                }
+                reply
+            }
+            DhcpMessageType::DhcpRequest => {
+                let device = output.device(buf).and_then(|x| x.signature).map(|x| x.name.clone());
+                let reply = self.ack(request, device, now);
+                let acked = reply.as_ref().and_then(|x| x.message_type()) == Some(&DhcpMessageType::DhcpAck);
+                if let Some(lease) = self.leases.get(&request.chaddr).filter(|_| acked) {
+                    output.info(format!("leased {} to {} ({})", lease.addr, request.chaddr,
+                                        lease.device.as_deref().unwrap_or("unknown device")));
+                }
+                reply
+            }
+            DhcpMessageType::DhcpRelease => {
+                self.leases.remove(&request.chaddr);
+                self.offers.remove(&request.chaddr);
+                None
+            }
+            // The client found the address in use, handing it out again
+            // would only get it declined again.
+            DhcpMessageType::DhcpDecline => {
+                let lease = self.leases.remove(&request.chaddr);
+                self.offers.remove(&request.chaddr);
+                let addr = match request.options.get(&DhcpOptionID::RequestedIpAddr) {
+                    Some(DhcpOption::RequestedIpAddr(addr)) => Some(*addr),
+                    _ => lease.map(|x| x.addr),
+                };
+                if let Some(addr) = addr.filter(|x| self.in_range(*x)) {
+                    output.info(format!("{} declined {}, holding it back for {}s", request.chaddr, addr,
+                                        SERVE_DECLINE_HOLD.as_secs()));
+                    self.declined.insert(addr, now + SERVE_DECLINE_HOLD);
+                }
+                None
+            }
+            DhcpMessageType::DhcpInform => {
+                let mut reply = self.reply(request, DhcpMessageType::DhcpAck);
+                reply.ciaddr = request.ciaddr;
+                self.configure(&mut reply, false);
+                Some(reply)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn serve(output: &Output, args: &ServeArgs, interface: Option<&str>, port: u16) -> CliResult {
+    let server_ip = match (args.server_ip, interface) {
+        (Some(addr), _) => addr,
+        (None, Some(name)) => interface_ipv4(&find_interface(name)?)?,
+        (None, None) => return Err(CliError::usage("serve needs --server-ip or --interface")),
+    };
+    let socket = bind_udp(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    let local = SocketAddrV4::new(server_ip, port);
+    let mut server = Server { args, server_ip, leases: HashMap::new(), offers: HashMap::new(),
+                              declined: HashMap::new() };
+    output.info(format!("serving {}-{} as {}", args.range.start, args.range.end, server_ip));
+
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    let mut encoded = Vec::new();
+    loop {
+        let (length, src_addr) = socket.recv_from(&mut buf).map_err(|e| CliError::new(format!("receiving: {}", e)))?;
+        let request = match output.received(src_addr, local, &buf[..length]) {
+            Some(request) => request,
+            None => continue,
+        };
+        let reply = match server.handle(output, &request, &buf[..length]) {
+            Some(reply) => reply,
+            None => continue,
+        };
+        // Unicast to yiaddr needs an ARP entry the client can't answer for
+        // yet, without a raw socket the reply is broadcast instead.
+        let header = DhcpFrameHeader::for_reply(&reply, MacAddr::default(), server_ip);
+        let dst_ip = match (header.dst_ip, reply.ciaddr) {
+            (addr, None) if Some(addr) == reply.yiaddr => Ipv4Addr::BROADCAST,
+            (addr, _) => addr,
+        };
+        let dst = SocketAddrV4::new(dst_ip, header.dst_port);
+        encoded.clear();
+        if let Err(e) = reply.encode(&mut encoded) {
+            output.error(&dst.to_string(), e);
+            continue;
+        }
+        output.packet(&output.udp_origin(format!("-> {}", dst), local.into(), dst.into()), &encoded, &reply);
+        if let Err(e) = socket.send_to(&encoded, dst) {
+            output.error(&dst.to_string(), e);
+        }
+        output.sent(local, dst, &encoded, Some(&reply));
+    }
+}
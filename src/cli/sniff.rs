@@ -0,0 +1,46 @@
+use crate::cli::{now, CliError, CliResult, Output};
+use dhcp_rs::dhcp_frame::{DhcpFrameError, DhcpFrameHeader, DhcpLinkType};
+use dhcp_rs::dhcp_packet::ParseMode;
+use pnet::datalink::{self, Channel, DataLinkReceiver, DataLinkSender, NetworkInterface};
+use std::io;
+use std::time::Duration;
+
+pub type EthernetChannel = (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>);
+
+// Reads time out every second so callers get to do periodic work.
+pub fn open_channel(interface: &NetworkInterface) -> Result<EthernetChannel, CliError> {
+    let config = datalink::Config { read_timeout: Some(Duration::from_secs(1)), ..Default::default() };
+    match datalink::channel(interface, config) {
+        Ok(Channel::Ethernet(tx, rx)) => Ok((tx, rx)),
+        Ok(_) => Err(CliError::new(format!("{}: not an Ethernet interface", interface.name))),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Err(CliError::new(format!(
+            "permission denied capturing on {}: run as root or grant CAP_NET_RAW", interface.name))),
+        Err(e) => Err(CliError::new(format!("capturing on {}: {}", interface.name, e))),
+    }
+}
+
+// Reads every frame on the interface and decodes the DHCP ones in both
+// directions, unicast included. No port is bound, so this runs fine next to
+// a DHCP server or client on the same host.
+pub fn sniff(output: &Output, interface: &NetworkInterface) -> CliResult {
+    // Wakes up now and then to close transactions that went quiet.
+    let (_, mut rx) = open_channel(interface)?;
+    output.info(format!("sniffing on {}", interface.name));
+    loop {
+        let frame = match rx.next() {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                output.tick(now());
+                continue;
+            }
+            Err(e) => return Err(CliError::new(format!("capturing on {}: {}", interface.name, e))),
+        };
+        match DhcpFrameHeader::parse_link_with_mode(DhcpLinkType::Ethernet, frame, ParseMode::Lenient) {
+            Ok((header, payload)) => {
+                output.captured(&interface.name, frame, &header, payload);
+            }
+            Err(DhcpFrameError::NotIpv4(_)) | Err(DhcpFrameError::NotUdp) | Err(DhcpFrameError::WrongPorts { .. }) => (),
+            Err(e) => output.debug(format!("{}: {}", interface.name, e)),
+        }
+    }
+}
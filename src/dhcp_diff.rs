@@ -0,0 +1,164 @@
+use crate::dhcp_packet::{DhcpOption, DhcpOptionID, DhcpPacket};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::net::Ipv4Addr;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpDifference {
+    Header { field: &'static str, old: String, new: String },
+    OptionAdded { option_id: DhcpOptionID, option: DhcpOption },
+    OptionRemoved { option_id: DhcpOptionID, option: DhcpOption },
+    OptionChanged { option_id: DhcpOptionID, old: DhcpOption, new: DhcpOption },
+}
+
+impl fmt::Display for DhcpDifference {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Header { field, old, new } =>
+                write!(w, "~ {}: {} -> {}", field, old, new),
+            Self::OptionAdded { option_id, option } =>
+                write!(w, "+ option {} ({}): {}", option_id.code(), option_id, option),
+            Self::OptionRemoved { option_id, option } =>
+                write!(w, "- option {} ({}): {}", option_id.code(), option_id, option),
+            Self::OptionChanged { option_id, old, new } =>
+                write!(w, "~ option {} ({}): {} -> {}", option_id.code(), option_id, old, new),
+        }
+    }
+}
+
+fn display_addr(addr: &Option<Ipv4Addr>) -> String {
+    addr.unwrap_or(Ipv4Addr::UNSPECIFIED).to_string()
+}
+
+impl DhcpPacket {
+    // Header fields first, then options in code order.
+    pub fn diff(&self, other: &DhcpPacket) -> Vec<DhcpDifference> {
+        let mut differences = Vec::new();
+        let mut header = |field: &'static str, old: String, new: String| {
+            if old != new {
+                differences.push(DhcpDifference::Header { field, old, new });
+            }
+        };
+        header("op", self.opcode.to_string(), other.opcode.to_string());
+        header("htype", self.htype.to_string(), other.htype.to_string());
+        header("hlen", self.hlen.to_string(), other.hlen.to_string());
+        header("hops", self.hops.to_string(), other.hops.to_string());
+        header("xid", format!("{:#010x}", self.xid), format!("{:#010x}", other.xid));
+        header("secs", self.secs.to_string(), other.secs.to_string());
+        header("broadcast", self.broadcast.to_string(), other.broadcast.to_string());
+        header("ciaddr", display_addr(&self.ciaddr), display_addr(&other.ciaddr));
+        header("yiaddr", display_addr(&self.yiaddr), display_addr(&other.yiaddr));
+        header("siaddr", display_addr(&self.siaddr), display_addr(&other.siaddr));
+        header("giaddr", display_addr(&self.giaddr), display_addr(&other.giaddr));
+        header("chaddr", self.chaddr.to_string(), other.chaddr.to_string());
+        header("sname", self.sname.clone(), other.sname.clone());
+        header("file", self.file.clone(), other.file.clone());
+
+        let mut option_ids: Vec<_> = self.options.keys().chain(other.options.keys()).collect();
+        option_ids.sort_by_key(|x| x.code());
+        option_ids.dedup();
+        for option_id in option_ids {
+            let difference = match (self.options.get(option_id), other.options.get(option_id)) {
+                (Some(old), Some(new)) if old != new => DhcpDifference::OptionChanged {
+                    option_id: option_id.clone(), old: old.clone(), new: new.clone() },
+                (Some(old), None) => DhcpDifference::OptionRemoved {
+                    option_id: option_id.clone(), option: old.clone() },
+                (None, Some(new)) => DhcpDifference::OptionAdded {
+                    option_id: option_id.clone(), option: new.clone() },
+                _ => continue,
+            };
+            differences.push(difference);
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_packet::{BootpOpcode, DhcpDuration, DhcpMessageType, MacAddr};
+
+    fn offer() -> DhcpPacket {
+        let mut offer = DhcpPacket::new(BootpOpcode::BootReply, 0x1234, MacAddr::new(2, 0, 0, 0, 0, 1));
+        offer.yiaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        offer.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpOffer));
+        offer.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        offer.options.insert(DhcpOptionID::LeaseTime, DhcpOption::LeaseTime(DhcpDuration::new(3600, 0)));
+        offer
+    }
+
+    #[test]
+    fn identical_packets() {
+        assert_eq!(offer().diff(&offer()), []);
+        let packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        assert_eq!(packet.diff(&packet.clone()), []);
+    }
+
+    #[test]
+    fn header_changes() {
+        let mut other = offer();
+        other.xid = 0x5678;
+        other.broadcast = true;
+        other.yiaddr = None;
+        other.giaddr = Some(Ipv4Addr::new(192, 0, 2, 254));
+        other.sname = "server".to_string();
+        let differences = offer().diff(&other);
+        let header = |field, old: &str, new: &str| DhcpDifference::Header { field, old: old.into(), new: new.into() };
+        assert_eq!(differences, [
+            header("xid", "0x00001234", "0x00005678"),
+            header("broadcast", "false", "true"),
+            header("yiaddr", "192.0.2.10", "0.0.0.0"),
+            header("giaddr", "0.0.0.0", "192.0.2.254"),
+            header("sname", "", "server"),
+        ]);
+        assert_eq!(differences[0].to_string(), "~ xid: 0x00001234 -> 0x00005678");
+    }
+
+    #[test]
+    fn option_changes() {
+        let mut other = offer();
+        other.options.remove(&DhcpOptionID::LeaseTime);
+        other.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 2)));
+        other.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("laptop".to_string()));
+        let differences = offer().diff(&other);
+        assert_eq!(differences, [
+            DhcpDifference::OptionAdded {
+                option_id: DhcpOptionID::HostName,
+                option: DhcpOption::HostName("laptop".to_string()),
+            },
+            DhcpDifference::OptionRemoved {
+                option_id: DhcpOptionID::LeaseTime,
+                option: DhcpOption::LeaseTime(DhcpDuration::new(3600, 0)),
+            },
+            DhcpDifference::OptionChanged {
+                option_id: DhcpOptionID::ServerID,
+                old: DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)),
+                new: DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 2)),
+            },
+        ]);
+        let lines: Vec<_> = differences.iter().map(|x| x.to_string()).collect();
+        assert_eq!(lines, [
+            "+ option 12 (Host Name): laptop",
+            "- option 51 (Lease Time): 3600s",
+            "~ option 54 (Server ID): 192.0.2.1 -> 192.0.2.2",
+        ]);
+        // Compared the other way round, additions become removals.
+        assert!(matches!(other.diff(&offer())[0], DhcpDifference::OptionRemoved { .. }));
+    }
+
+    #[test]
+    fn header_before_options() {
+        let mut other = offer();
+        other.hops = 1;
+        other.options.remove(&DhcpOptionID::MsgType);
+        let differences = offer().diff(&other);
+        assert_eq!(differences.len(), 2);
+        assert!(matches!(differences[0], DhcpDifference::Header { field: "hops", .. }));
+        assert!(matches!(differences[1], DhcpDifference::OptionRemoved { option_id: DhcpOptionID::MsgType, .. }));
+    }
+}
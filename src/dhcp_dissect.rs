@@ -0,0 +1,300 @@
+use crate::dhcp_packet::{
+    new_macaddr, parse_cstring, parse_dhcp_hwarp, parse_dhcp_option, parse_flags, parse_ipv4, BootpOpcode, DhcpOptionID,
+    Input, ParseMode, Result, DHCP_MAGIC_COOKIE,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use nom::bytes::complete::{tag, take};
+use nom::combinator::map;
+use nom::number::complete::{be_u16, be_u32, be_u8};
+
+const HEXDUMP_WIDTH: usize = 16;
+const DHCP_OPTION_OVERLOAD: u8 = 52;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpFieldSpan {
+    pub range: Range<usize>,
+    pub field: String,
+    pub value: String,
+}
+
+struct Dissector<'a> {
+    packet: Input<'a>,
+    buf: Input<'a>,
+    spans: Vec<DhcpFieldSpan>,
+    // The Option Overload value, 1 when file holds options, 2 for sname and 3
+    // for both (RFC 2132 section 9.3).
+    overload: u8,
+}
+
+impl<'a> Dissector<'a> {
+    fn offset(&self) -> usize {
+        self.buf.as_ptr() as usize - self.packet.as_ptr() as usize
+    }
+
+    fn push(&mut self, length: usize, field: String, value: String) {
+        let start = self.offset();
+        self.spans.push(DhcpFieldSpan { range: start..start + length, field, value });
+        self.buf = &self.buf[length..];
+    }
+
+    // Runs one of the packet parsers and records the bytes it consumed. When
+    // it fails the rest of the packet is recorded as undecoded.
+    fn field<T, P, F>(&mut self, field: &str, parser: P, value: F) -> Option<T>
+        where P: Fn(Input<'a>) -> Result<'a, T>, F: Fn(&T) -> String {
+        match parser(self.buf) {
+            Ok((rest, v)) => {
+                self.push(self.buf.len() - rest.len(), field.to_string(), value(&v));
+                Some(v)
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                self.push(self.buf.len(), format!("{} (undecoded)", field), e.reason().to_string());
+                None
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                self.push(self.buf.len(), format!("{} (undecoded)", field), "truncated".to_string());
+                None
+            }
+        }
+    }
+
+    fn header(&mut self) -> Option<()> {
+        self.field("op", BootpOpcode::parse, |x| x.to_string())?;
+        self.field("htype", parse_dhcp_hwarp, |x| x.to_string())?;
+        self.field("hlen", be_u8, |x| x.to_string())?;
+        self.field("hops", be_u8, |x| x.to_string())?;
+        self.field("xid", be_u32, |x| format!("{:#010x}", x))?;
+        self.field("secs", be_u16, |x| x.to_string())?;
+        self.field("flags", parse_flags, |x| if *x { "Broadcast" } else { "Unicast" }.to_string())?;
+        for name in &["ciaddr", "yiaddr", "siaddr", "giaddr"] {
+            self.field(name, parse_ipv4, |x| x.map(|a| a.to_string()).unwrap_or_else(|| "0.0.0.0".to_string()))?;
+        }
+        self.field("chaddr", map(take(6_usize), new_macaddr), |x| x.to_string())?;
+        self.field("chaddr padding", take(10_usize), |_| String::new())?;
+        self.field("sname", map(take(64_usize), parse_cstring), |x| x.clone())?;
+        self.field("file", map(take(128_usize), parse_cstring), |x| x.clone())?;
+        self.field("magic cookie", tag(DHCP_MAGIC_COOKIE), |_| "DHCP".to_string())?;
+        Some(())
+    }
+
+    fn options(&mut self) {
+        while !self.buf.is_empty() {
+            let code = self.buf[0];
+            let (rest, (option_id, option)) = match parse_dhcp_option(self.buf, ParseMode::Lenient) {
+                Ok(x) => x,
+                Err(_) => break,
+            };
+            let name = format!("option {} ({})", code, option_id);
+            let consumed = self.buf.len() - rest.len();
+            if code == DHCP_OPTION_OVERLOAD && consumed == 3 {
+                self.overload = self.buf[2];
+            }
+            match option_id {
+                DhcpOptionID::Pad | DhcpOptionID::OptionEnd =>
+                    self.push(consumed, name, String::new()),
+                _ => {
+                    self.push(1, format!("{} code", name), String::new());
+                    if consumed > 1 {
+                        let length = self.buf[0];
+                        self.push(1, format!("{} length", name), length.to_string());
+                        self.push(consumed - 2, format!("{} value", name), option.to_string());
+                    }
+                }
+            }
+            if option_id == DhcpOptionID::OptionEnd {
+                break;
+            }
+        }
+        if !self.buf.is_empty() {
+            self.push(self.buf.len(), "padding".to_string(), String::new());
+        }
+    }
+
+    // Dissects the options an overloaded sname or file field holds in place
+    // of its string.
+    fn overloaded(&mut self, field: &str) {
+        if let Some(i) = self.spans.iter().position(|x| x.field == field) {
+            let span = self.spans.remove(i);
+            let rest = self.buf;
+            self.buf = &self.packet[span.range];
+            self.options();
+            self.buf = rest;
+        }
+    }
+}
+
+// Maps every byte of the packet to the header field or option it belongs to.
+// Decoding continues past malformed options, see ParseMode::Lenient.
+pub fn dissect(buf: &[u8]) -> Vec<DhcpFieldSpan> {
+    let mut dissector = Dissector { packet: buf, buf, spans: Vec::new(), overload: 0 };
+    if dissector.header().is_some() {
+        dissector.options();
+        // The file field is read before sname.
+        let overload = dissector.overload;
+        for (field, bit) in &[("file", 1), ("sname", 2)] {
+            if overload & bit != 0 {
+                dissector.overloaded(field);
+            }
+        }
+        dissector.spans.sort_by_key(|x| x.range.start);
+    }
+    dissector.spans
+}
+
+pub struct DhcpHexDump<'a> {
+    pub packet: &'a [u8],
+    pub spans: &'a [DhcpFieldSpan],
+}
+
+impl<'a> fmt::Display for DhcpHexDump<'a> {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        for span in self.spans {
+            let bytes = &self.packet[span.range.clone()];
+            let annotation = if span.value.is_empty() {
+                span.field.clone()
+            } else {
+                format!("{}: {}", span.field, span.value)
+            };
+            if bytes.is_empty() {
+                writeln!(w, "{:04x}  {:<48} {}", span.range.start, "", annotation)?;
+            }
+            for (i, line) in bytes.chunks(HEXDUMP_WIDTH).enumerate() {
+                let hex: String = line.iter().map(|x| format!("{:02x} ", x)).collect();
+                let offset = span.range.start + i * HEXDUMP_WIDTH;
+                if i == 0 {
+                    writeln!(w, "{:04x}  {:<48} {}", offset, hex, annotation)?;
+                } else {
+                    writeln!(w, "{:04x}  {}", offset, hex.trim_end())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_packet::{DhcpMessageType, DhcpOption, DhcpOptionOther, DhcpPacket, MacAddr};
+    use alloc::vec;
+
+    fn discover() -> Vec<u8> {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 0x1234, MacAddr::new(2, 0, 0, 0, 0, 1));
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("laptop".to_string()));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        buf
+    }
+
+    // Every byte belongs to exactly one span, in order.
+    fn assert_covers(spans: &[DhcpFieldSpan], length: usize) {
+        let mut offset = 0;
+        for span in spans {
+            assert_eq!(span.range.start, offset, "{:?}", span);
+            offset = span.range.end;
+        }
+        assert_eq!(offset, length);
+    }
+
+    fn span<'a>(spans: &'a [DhcpFieldSpan], field: &str) -> &'a DhcpFieldSpan {
+        spans.iter().find(|x| x.field == field).unwrap_or_else(|| panic!("no {}", field))
+    }
+
+    #[test]
+    fn spans_cover_packet() {
+        let buf = discover();
+        let spans = dissect(&buf);
+        assert_covers(&spans, buf.len());
+        assert_eq!(span(&spans, "op").range, 0..1);
+        assert_eq!(span(&spans, "xid").range, 4..8);
+        assert_eq!(span(&spans, "xid").value, "0x00001234");
+        assert_eq!(span(&spans, "flags").value, "Unicast");
+        assert_eq!(span(&spans, "giaddr").range, 24..28);
+        assert_eq!(span(&spans, "chaddr").range, 28..34);
+        assert_eq!(span(&spans, "chaddr").value, "02:00:00:00:00:01");
+        assert_eq!(span(&spans, "chaddr padding").range, 34..44);
+        assert_eq!(span(&spans, "sname").range, 44..108);
+        assert_eq!(span(&spans, "file").range, 108..236);
+        assert_eq!(span(&spans, "magic cookie").range, 236..240);
+        assert_eq!(span(&spans, "option 53 (Message Type) code").range, 240..241);
+        assert_eq!(span(&spans, "option 53 (Message Type) length").value, "1");
+        assert_eq!(span(&spans, "option 53 (Message Type) value").range, 242..243);
+        let hostname = span(&spans, "option 12 (Host Name) value");
+        assert_eq!((hostname.range.len(), hostname.value.as_str()), (6, "laptop"));
+        let end = span(&spans, "option 255 (OptionEnd)").range.end;
+        assert_eq!(span(&spans, "padding").range, end..buf.len());
+    }
+
+    #[test]
+    fn truncated_packet() {
+        let buf = discover();
+        let spans = dissect(&buf[..100]);
+        assert_covers(&spans, 100);
+        assert_eq!(spans.last().unwrap().field, "sname (undecoded)");
+        assert_eq!(spans.last().unwrap().range, 44..100);
+
+        // An option longer than the packet keeps the bytes that are there.
+        let mut buf = discover();
+        buf.truncate(243);
+        buf.extend_from_slice(&[12, 10, b'a']);
+        let spans = dissect(&buf);
+        assert_covers(&spans, buf.len());
+        assert_eq!(span(&spans, "option 12 (Host Name) length").value, "10");
+        let value = spans.last().unwrap();
+        assert_eq!((value.field.as_str(), value.range.clone()), ("option 12 (Host Name) value", 245..246));
+        assert!(value.value.starts_with("malformed"), "{}", value.value);
+    }
+
+    #[test]
+    fn option_overload() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootReply, 1, MacAddr::default());
+        packet.options.insert(DhcpOptionID::from(DHCP_OPTION_OVERLOAD), DhcpOption::Other(DhcpOptionOther {
+            option_id: DHCP_OPTION_OVERLOAD,
+            option: vec![3].into(),
+        }));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        buf[44..51].copy_from_slice(&[3, 4, 192, 0, 2, 1, 255]);
+        buf[108..114].copy_from_slice(&[15, 3, b'a', b'b', b'c', 255]);
+        let spans = dissect(&buf);
+        assert_covers(&spans, buf.len());
+        assert!(spans.iter().all(|x| x.field != "sname" && x.field != "file"));
+        let router = spans.iter().find(|x| x.field == "option 3 (Router) value").unwrap();
+        assert_eq!((router.range.clone(), router.value.as_str()), (46..50, "192.0.2.1"));
+        let domain = spans.iter().find(|x| x.field == "option 15 (Domain Name) value").unwrap();
+        assert_eq!((domain.range.clone(), domain.value.as_str()), (110..113, "abc"));
+        // Both fields end with their own End option and padding.
+        let ends: Vec<_> = spans.iter().filter(|x| x.field == "option 255 (OptionEnd)").map(|x| x.range.start).collect();
+        assert_eq!(&ends[..2], [50, 113]);
+        assert!(spans.iter().any(|x| x.field == "padding" && x.range == (51..108)));
+    }
+
+    #[test]
+    fn no_overload() {
+        // Without the Overload option the fields are strings, even when they
+        // look like options.
+        let mut buf = discover();
+        buf[44..49].copy_from_slice(&[b'b', b'o', b'o', b't', 0]);
+        let spans = dissect(&buf);
+        assert_eq!(span(&spans, "sname").value, "boot");
+        assert_eq!(span(&spans, "sname").range, 44..108);
+    }
+
+    #[test]
+    fn hex_dump() {
+        let packet: Vec<u8> = (0..20).collect();
+        let spans = [
+            DhcpFieldSpan { range: 0..1, field: "op".to_string(), value: "BootRequest".to_string() },
+            DhcpFieldSpan { range: 1..1, field: "empty".to_string(), value: String::new() },
+            DhcpFieldSpan { range: 1..20, field: "rest".to_string(), value: String::new() },
+        ];
+        let dump = DhcpHexDump { packet: &packet, spans: &spans }.to_string();
+        assert_eq!(dump, format!(
+            "0000  {:<48} op: BootRequest\n0001  {:<48} empty\n0001  {:<48} rest\n0011  11 12 13\n",
+            "00 ", "", "01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10 "));
+    }
+}
@@ -0,0 +1,185 @@
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt;
+use nom::error::{ErrorKind, ParseError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpErrorContext {
+    Field(&'static str),
+    Option(u8),
+}
+
+impl fmt::Display for DhcpErrorContext {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Field(name) => write!(w, "{}", name),
+            Self::Option(code) => write!(w, "option {}", code),
+        }
+    }
+}
+
+// Positions are kept as input slices while parsing and turned into offsets
+// relative to the packet by `locate` once the whole packet parse failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DhcpParseError<'a> {
+    input: &'a [u8],
+    kind: ErrorKind,
+    context: Vec<(&'a [u8], DhcpErrorContext)>,
+    packet: &'a [u8],
+}
+
+impl<'a> DhcpParseError<'a> {
+    pub(crate) fn add_option(input: &'a [u8], code: u8, mut other: Self) -> Self {
+        other.context.push((input, DhcpErrorContext::Option(code)));
+        other
+    }
+
+    pub(crate) fn locate(mut self, packet: &'a [u8]) -> Self {
+        self.packet = packet;
+        self
+    }
+
+    fn offset_of(&self, input: &[u8]) -> usize {
+        (input.as_ptr() as usize).saturating_sub(self.packet.as_ptr() as usize)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset_of(self.input)
+    }
+
+    // Innermost context first.
+    pub fn context(&self) -> impl Iterator<Item = (usize, DhcpErrorContext)> + '_ {
+        self.context.iter().map(move |(input, c)| (self.offset_of(input), *c))
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self.kind {
+            ErrorKind::Eof | ErrorKind::Complete => "length exceeds remaining buffer",
+            ErrorKind::Verify | ErrorKind::MapOpt | ErrorKind::MapRes => "invalid value",
+            ErrorKind::Tag => "unexpected value",
+            _ => "parse error",
+        }
+    }
+}
+
+impl<'a> ParseError<&'a [u8]> for DhcpParseError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        DhcpParseError { input, kind, context: Vec::new(), packet: input }
+    }
+
+    // The innermost error is the interesting one, keep it.
+    fn append(_input: &'a [u8], _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn add_context(input: &'a [u8], ctx: &'static str, mut other: Self) -> Self {
+        other.context.push((input, DhcpErrorContext::Field(ctx)));
+        other
+    }
+}
+
+// Reports where the innermost field or option starts, the offset the error
+// happened at is in the context path.
+impl<'a> fmt::Display for DhcpParseError<'a> {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self.context.first() {
+            Some((input, c)) => write!(w, "{} at offset {}: {}", c, self.offset_of(input), self.reason())?,
+            None => write!(w, "at offset {}: {}", self.offset(), self.reason())?,
+        }
+        if self.context.len() > 1 {
+            let path: Vec<_> = self.context.iter().rev()
+                .map(|(input, c)| format!("{}@{}", c, self.offset_of(input))).collect();
+            write!(w, " ({})", path.join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+// Why an option or packet can't be encoded, the value doesn't fit its field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpEncodeError {
+    OptionTooLong { code: u8, length: usize },
+    LabelTooLong { code: u8, length: usize },
+    NameTooLong { code: u8, length: usize },
+    FieldTooLong { field: &'static str, length: usize, max: usize },
+    SecsTooLarge(u64),
+    PayloadTooLong(usize),
+}
+
+impl fmt::Display for DhcpEncodeError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OptionTooLong { code, length } =>
+                write!(w, "option {} is {} bytes long, at most 255 fit", code, length),
+            Self::LabelTooLong { code, length } =>
+                write!(w, "option {}: a {} bytes label, at most 63 fit", code, length),
+            Self::NameTooLong { code, length } =>
+                write!(w, "option {}: a {} bytes domain name, at most 255 fit", code, length),
+            Self::FieldTooLong { field, length, max } =>
+                write!(w, "{} is {} bytes long, at most {} fit", field, length, max),
+            Self::SecsTooLarge(secs) =>
+                write!(w, "secs {} doesn't fit in 16 bits", secs),
+            Self::PayloadTooLong(length) =>
+                write!(w, "a {} bytes UDP payload, at most 65507 fit", length),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dhcp_packet::{BootpOpcode, DhcpPacket, MacAddr};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    // A packet without options, followed by `options`.
+    fn packet(options: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default()).encode(&mut buf).unwrap();
+        buf.truncate(240);
+        buf.extend_from_slice(options);
+        buf
+    }
+
+    fn error(buf: &[u8]) -> String {
+        match DhcpPacket::parse(buf) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e.to_string(),
+            x => panic!("expected an error, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn option_offset() {
+        let buf = packet(&[53, 1, 1, 50, 9, 10, 0, 0, 5, 255]);
+        assert_eq!(error(&buf), "option 50 at offset 243: invalid value (options@240 > option 50@243)");
+    }
+
+    #[test]
+    fn truncated_option() {
+        let buf = packet(&[53, 1, 1, 55, 10, 1, 3]);
+        assert_eq!(error(&buf), "option 55 at offset 243: length exceeds remaining buffer (options@240 > option 55@243)");
+    }
+
+    #[test]
+    fn header_field() {
+        let mut buf = packet(&[255]);
+        buf[236] = 0;
+        assert_eq!(error(&buf), "magic cookie at offset 236: unexpected value");
+        assert_eq!(error(&buf[..100]), "sname at offset 44: length exceeds remaining buffer");
+    }
+
+    #[test]
+    fn context_and_offset() {
+        let buf = packet(&[53, 1, 1, 50, 9, 10, 0, 0, 5, 255]);
+        let e = match DhcpPacket::parse(&buf) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => e,
+            x => panic!("expected an error, got {:?}", x),
+        };
+        let context: Vec<_> = e.context().map(|(offset, c)| (offset, c.to_string())).collect();
+        assert_eq!(context, [(243, "option 50".to_string()), (240, "options".to_string())]);
+        assert!(e.offset() >= 243);
+    }
+}
@@ -0,0 +1,701 @@
+use crate::dhcp_packet::{BootpOpcode, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpPacket, MacAddr};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::net::Ipv4Addr;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpFilterField {
+    Op,
+    MsgType,
+    Htype,
+    Hops,
+    Xid,
+    Secs,
+    Broadcast,
+    Ciaddr,
+    Yiaddr,
+    Siaddr,
+    Giaddr,
+    Chaddr,
+    Sname,
+    File,
+    Hostname,
+    VendorClass,
+    DomainName,
+    Message,
+    ClientId,
+    RequestedIp,
+    ServerId,
+    LeaseTime,
+    Params,
+    // The raw value of any option, `option 82`.
+    Option(u8),
+}
+
+const DHCP_FILTER_FIELDS: [(&str, DhcpFilterField); 23] = [
+    ("op", DhcpFilterField::Op),
+    ("msgtype", DhcpFilterField::MsgType),
+    ("htype", DhcpFilterField::Htype),
+    ("hops", DhcpFilterField::Hops),
+    ("xid", DhcpFilterField::Xid),
+    ("secs", DhcpFilterField::Secs),
+    ("broadcast", DhcpFilterField::Broadcast),
+    ("ciaddr", DhcpFilterField::Ciaddr),
+    ("yiaddr", DhcpFilterField::Yiaddr),
+    ("siaddr", DhcpFilterField::Siaddr),
+    ("giaddr", DhcpFilterField::Giaddr),
+    ("chaddr", DhcpFilterField::Chaddr),
+    ("sname", DhcpFilterField::Sname),
+    ("file", DhcpFilterField::File),
+    ("hostname", DhcpFilterField::Hostname),
+    ("vendor_class", DhcpFilterField::VendorClass),
+    ("domain_name", DhcpFilterField::DomainName),
+    ("message", DhcpFilterField::Message),
+    ("client_id", DhcpFilterField::ClientId),
+    ("requested_ip", DhcpFilterField::RequestedIp),
+    ("server_id", DhcpFilterField::ServerId),
+    ("lease_time", DhcpFilterField::LeaseTime),
+    ("params", DhcpFilterField::Params),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DhcpFilterKind {
+    Opcode,
+    MsgType,
+    Number,
+    Bool,
+    Address,
+    Mac,
+    Text,
+    Bytes,
+    Codes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpFilterValue {
+    Opcode(BootpOpcode),
+    MsgType(DhcpMessageType),
+    Number(u64),
+    Bool(bool),
+    Address(Ipv4Addr),
+    Network(Ipv4Addr, u8),
+    Mac(MacAddr),
+    MacPrefix(MacAddr, u8),
+    Text(String),
+    Bytes(Vec<u8>),
+    Codes(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DhcpFilterOp {
+    #[strum(to_string = "==")]
+    Eq,
+    #[strum(to_string = "!=")]
+    Ne,
+    #[strum(to_string = "<")]
+    Lt,
+    #[strum(to_string = "<=")]
+    Le,
+    #[strum(to_string = ">")]
+    Gt,
+    #[strum(to_string = ">=")]
+    Ge,
+    // Substring ignoring case, byte sequence or requested parameter.
+    #[strum(to_string = "~")]
+    Contains,
+    // Address in a network or MAC address under a prefix.
+    #[strum(to_string = "in")]
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpFilter {
+    And(Box<DhcpFilter>, Box<DhcpFilter>),
+    Or(Box<DhcpFilter>, Box<DhcpFilter>),
+    Not(Box<DhcpFilter>),
+    Has(DhcpFilterField),
+    // Options missing from the packet compare false, whatever the operator.
+    Compare(DhcpFilterField, DhcpFilterOp, DhcpFilterValue),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpFilterError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DhcpFilterError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl fmt::Display for DhcpFilterField {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match (self, DHCP_FILTER_FIELDS.iter().find(|(_, x)| x == self)) {
+            (_, Some((name, _))) => write!(w, "{}", name),
+            (DhcpFilterField::Option(code), None) => write!(w, "option {}", code),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// The value of an option as it is on the wire, without code and length.
+fn raw_value(option: &DhcpOption) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match option.encode(&mut buf) {
+        Ok(()) => buf.split_off(2.min(buf.len())),
+        Err(_) => Vec::new(),
+    }
+}
+
+impl DhcpFilterField {
+    fn kind(&self) -> DhcpFilterKind {
+        match self {
+            Self::Op => DhcpFilterKind::Opcode,
+            Self::MsgType => DhcpFilterKind::MsgType,
+            Self::Htype | Self::Hops | Self::Xid | Self::Secs | Self::LeaseTime => DhcpFilterKind::Number,
+            Self::Broadcast => DhcpFilterKind::Bool,
+            Self::Ciaddr | Self::Yiaddr | Self::Siaddr | Self::Giaddr | Self::RequestedIp | Self::ServerId =>
+                DhcpFilterKind::Address,
+            Self::Chaddr => DhcpFilterKind::Mac,
+            Self::Sname | Self::File | Self::Hostname | Self::VendorClass | Self::DomainName | Self::Message =>
+                DhcpFilterKind::Text,
+            Self::ClientId | Self::Option(_) => DhcpFilterKind::Bytes,
+            Self::Params => DhcpFilterKind::Codes,
+        }
+    }
+
+    // The option holding the field, None for the fixed BOOTP fields.
+    pub fn option_id(&self) -> Option<DhcpOptionID> {
+        match self {
+            Self::MsgType => Some(DhcpOptionID::MsgType),
+            Self::Hostname => Some(DhcpOptionID::HostName),
+            Self::VendorClass => Some(DhcpOptionID::VendorClassId),
+            Self::DomainName => Some(DhcpOptionID::DomainName),
+            Self::Message => Some(DhcpOptionID::Message),
+            Self::ClientId => Some(DhcpOptionID::ClientIdentifier),
+            Self::RequestedIp => Some(DhcpOptionID::RequestedIpAddr),
+            Self::ServerId => Some(DhcpOptionID::ServerID),
+            Self::LeaseTime => Some(DhcpOptionID::LeaseTime),
+            Self::Params => Some(DhcpOptionID::ParameterRequestList),
+            Self::Option(code) => Some(DhcpOptionID::from(*code)),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self, packet: &DhcpPacket) -> Option<DhcpFilterValue> {
+        let address = |x: Option<Ipv4Addr>| Some(DhcpFilterValue::Address(x.unwrap_or(Ipv4Addr::UNSPECIFIED)));
+        let option = self.option_id().and_then(|x| packet.options.get(&x));
+        match (self, option) {
+            (Self::Op, _) => Some(DhcpFilterValue::Opcode(packet.opcode)),
+            (Self::Htype, _) => Some(DhcpFilterValue::Number(packet.htype.0.into())),
+            (Self::Hops, _) => Some(DhcpFilterValue::Number(packet.hops as u64)),
+            (Self::Xid, _) => Some(DhcpFilterValue::Number(packet.xid.into())),
+            (Self::Secs, _) => Some(DhcpFilterValue::Number(packet.secs.as_secs())),
+            (Self::Broadcast, _) => Some(DhcpFilterValue::Bool(packet.broadcast)),
+            (Self::Ciaddr, _) => address(packet.ciaddr),
+            (Self::Yiaddr, _) => address(packet.yiaddr),
+            (Self::Siaddr, _) => address(packet.siaddr),
+            (Self::Giaddr, _) => address(packet.giaddr),
+            (Self::Chaddr, _) => Some(DhcpFilterValue::Mac(packet.chaddr)),
+            (Self::Sname, _) => Some(DhcpFilterValue::Text(packet.sname.clone())),
+            (Self::File, _) => Some(DhcpFilterValue::Text(packet.file.clone())),
+            (Self::MsgType, Some(DhcpOption::MessageType(x))) => Some(DhcpFilterValue::MsgType(x.clone())),
+            (Self::Hostname, Some(DhcpOption::HostName(x))) |
+            (Self::VendorClass, Some(DhcpOption::VendorClassId(x))) |
+            (Self::DomainName, Some(DhcpOption::DomainName(x))) |
+            (Self::Message, Some(DhcpOption::Message(x))) => Some(DhcpFilterValue::Text(x.clone())),
+            (Self::RequestedIp, Some(DhcpOption::RequestedIpAddr(x))) |
+            (Self::ServerId, Some(DhcpOption::ServerID(x))) => Some(DhcpFilterValue::Address(*x)),
+            (Self::LeaseTime, Some(DhcpOption::LeaseTime(x))) => Some(DhcpFilterValue::Number(x.as_secs())),
+            (Self::Params, Some(DhcpOption::ParameterRequestList(x))) =>
+                Some(DhcpFilterValue::Codes(Vec::from(x).iter().map(|x| x.code()).collect())),
+            (Self::ClientId, Some(x)) | (Self::Option(_), Some(x)) => Some(DhcpFilterValue::Bytes(raw_value(x))),
+            _ => None,
+        }
+    }
+}
+
+fn prefix_matches(value: u64, prefix: u64, length: u8, bits: u8) -> bool {
+    let shift = bits - length;
+    length == 0 || value >> shift == prefix >> shift
+}
+
+fn mac_bits(mac: &MacAddr) -> u64 {
+    mac.octets().iter().fold(0, |x, y| x << 8 | u64::from(*y))
+}
+
+fn compare(actual: &DhcpFilterValue, op: DhcpFilterOp, expected: &DhcpFilterValue) -> bool {
+    use DhcpFilterValue::*;
+    match (op, actual, expected) {
+        (DhcpFilterOp::Eq, a, e) => a == e,
+        (DhcpFilterOp::Ne, a, e) => a != e,
+        (DhcpFilterOp::Lt, Number(a), Number(e)) => a < e,
+        (DhcpFilterOp::Le, Number(a), Number(e)) => a <= e,
+        (DhcpFilterOp::Gt, Number(a), Number(e)) => a > e,
+        (DhcpFilterOp::Ge, Number(a), Number(e)) => a >= e,
+        (DhcpFilterOp::Contains, Text(a), Text(e)) => a.to_ascii_lowercase().contains(&e.to_ascii_lowercase()),
+        (DhcpFilterOp::Contains, Bytes(a), Bytes(e)) => e.is_empty() || a.windows(e.len()).any(|x| x == &e[..]),
+        (DhcpFilterOp::Contains, Codes(a), Number(e)) => a.iter().any(|x| u64::from(*x) == *e),
+        (DhcpFilterOp::In, Address(a), Network(n, length)) =>
+            prefix_matches(u32::from(*a).into(), u32::from(*n).into(), *length, 32),
+        (DhcpFilterOp::In, Mac(a), MacPrefix(p, length)) => prefix_matches(mac_bits(a), mac_bits(p), *length, 48),
+        _ => false,
+    }
+}
+
+impl DhcpFilter {
+    pub fn parse(text: &str) -> Result<Self, DhcpFilterError> {
+        let mut parser = DhcpFilterParser { tokens: tokenize(text)?, position: 0, end: text.len() };
+        let filter = parser.parse_or()?;
+        match parser.tokens.get(parser.position) {
+            Some((offset, _)) => Err(DhcpFilterError { offset: *offset, message: "expected && or ||".to_string() }),
+            None => Ok(filter),
+        }
+    }
+
+    pub fn matches(&self, packet: &DhcpPacket) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(packet) && b.matches(packet),
+            Self::Or(a, b) => a.matches(packet) || b.matches(packet),
+            Self::Not(a) => !a.matches(packet),
+            Self::Has(field) => field.option_id().is_some_and(|x| packet.options.contains_key(&x)),
+            Self::Compare(field, op, expected) =>
+                field.value(packet).is_some_and(|actual| compare(&actual, *op, expected)),
+        }
+    }
+}
+
+impl FromStr for DhcpFilter {
+    type Err = DhcpFilterError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DhcpFilterToken {
+    // Names, numbers and addresses.
+    Word(String),
+    // A double quoted string.
+    Text(String),
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Op(DhcpFilterOp),
+}
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._:/-".contains(c)
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, DhcpFilterToken)>, DhcpFilterError> {
+    const SYMBOLS: [(&str, DhcpFilterToken); 12] = [
+        ("&&", DhcpFilterToken::And),
+        ("||", DhcpFilterToken::Or),
+        ("==", DhcpFilterToken::Op(DhcpFilterOp::Eq)),
+        ("!=", DhcpFilterToken::Op(DhcpFilterOp::Ne)),
+        ("<=", DhcpFilterToken::Op(DhcpFilterOp::Le)),
+        (">=", DhcpFilterToken::Op(DhcpFilterOp::Ge)),
+        ("<", DhcpFilterToken::Op(DhcpFilterOp::Lt)),
+        (">", DhcpFilterToken::Op(DhcpFilterOp::Gt)),
+        ("~", DhcpFilterToken::Op(DhcpFilterOp::Contains)),
+        ("!", DhcpFilterToken::Not),
+        ("(", DhcpFilterToken::Open),
+        (")", DhcpFilterToken::Close),
+    ];
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while let Some(c) = text[offset..].chars().next() {
+        let rest = &text[offset..];
+        if c.is_whitespace() {
+            offset += c.len_utf8();
+        } else if c == '"' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i + 1,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, x)) => value.push(x),
+                        None => break rest.len() + 1,
+                    },
+                    Some((_, x)) => value.push(x),
+                    None => break rest.len() + 1,
+                }
+            };
+            if end > rest.len() {
+                return Err(DhcpFilterError { offset, message: "unterminated string".to_string() });
+            }
+            tokens.push((offset, DhcpFilterToken::Text(value)));
+            offset += end;
+        } else if is_word(c) {
+            let word: String = rest.chars().take_while(|x| is_word(*x)).collect();
+            let token = match word.as_str() {
+                "and" => DhcpFilterToken::And,
+                "or" => DhcpFilterToken::Or,
+                "not" => DhcpFilterToken::Not,
+                "in" => DhcpFilterToken::Op(DhcpFilterOp::In),
+                _ => DhcpFilterToken::Word(word.clone()),
+            };
+            tokens.push((offset, token));
+            offset += word.len();
+        } else {
+            match SYMBOLS.iter().find(|(x, _)| rest.starts_with(x)) {
+                Some((symbol, token)) => {
+                    tokens.push((offset, token.clone()));
+                    offset += symbol.len();
+                }
+                None => return Err(DhcpFilterError { offset, message: format!("unexpected {}", c) }),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn parse_mac(text: &str) -> Option<MacAddr> {
+    let octets: Vec<_> = text.split(&[':', '-'][..]).map(|x| u8::from_str_radix(x, 16).ok()).collect::<Option<_>>()?;
+    match octets[..] {
+        [a, b, c, d, e, f] => Some(MacAddr::new(a, b, c, d, e, f)),
+        _ => None,
+    }
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.chars().filter(|x| *x != ':' && *x != '-').collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// Address and prefix length, at most `bits`.
+fn split_prefix(text: &str, bits: u8) -> Option<(&str, u8)> {
+    let (address, length) = text.split_at(text.find('/')?);
+    let length = length[1..].parse().ok().filter(|x| *x <= bits)?;
+    Some((address, length))
+}
+
+fn parse_message_type(text: &str) -> Option<DhcpMessageType> {
+    if let Some(code) = parse_number(text) {
+        return DhcpMessageType::from_code(u8::try_from(code).ok()?);
+    }
+    DhcpMessageType::from_name(text)
+}
+
+fn parse_value(kind: DhcpFilterKind, op: DhcpFilterOp, token: &DhcpFilterToken) -> Option<DhcpFilterValue> {
+    let word = match token {
+        DhcpFilterToken::Word(word) => Some(word.as_str()),
+        _ => None,
+    };
+    match (kind, op, token) {
+        (DhcpFilterKind::Opcode, _, _) => match word?.to_ascii_lowercase().as_str() {
+            "1" | "request" | "bootrequest" => Some(DhcpFilterValue::Opcode(BootpOpcode::BootRequest)),
+            "2" | "reply" | "bootreply" => Some(DhcpFilterValue::Opcode(BootpOpcode::BootReply)),
+            _ => None,
+        },
+        (DhcpFilterKind::MsgType, _, _) => parse_message_type(word?).map(DhcpFilterValue::MsgType),
+        (DhcpFilterKind::Number, _, _) | (DhcpFilterKind::Codes, _, _) => parse_number(word?).map(DhcpFilterValue::Number),
+        (DhcpFilterKind::Bool, _, _) => match word? {
+            "true" | "1" => Some(DhcpFilterValue::Bool(true)),
+            "false" | "0" => Some(DhcpFilterValue::Bool(false)),
+            _ => None,
+        },
+        (DhcpFilterKind::Address, DhcpFilterOp::In, _) => {
+            let (address, length) = split_prefix(word?, 32)?;
+            Some(DhcpFilterValue::Network(address.parse().ok()?, length))
+        }
+        (DhcpFilterKind::Address, _, _) => word?.parse().ok().map(DhcpFilterValue::Address),
+        (DhcpFilterKind::Mac, DhcpFilterOp::In, _) => {
+            let (mac, length) = split_prefix(word?, 48)?;
+            Some(DhcpFilterValue::MacPrefix(parse_mac(mac)?, length))
+        }
+        (DhcpFilterKind::Mac, _, _) => parse_mac(word?).map(DhcpFilterValue::Mac),
+        (DhcpFilterKind::Text, _, DhcpFilterToken::Text(x)) | (DhcpFilterKind::Text, _, DhcpFilterToken::Word(x)) =>
+            Some(DhcpFilterValue::Text(x.clone())),
+        (DhcpFilterKind::Bytes, _, DhcpFilterToken::Text(x)) => Some(DhcpFilterValue::Bytes(x.as_bytes().to_vec())),
+        (DhcpFilterKind::Bytes, _, _) => parse_hex(word?).map(DhcpFilterValue::Bytes),
+        _ => None,
+    }
+}
+
+fn expected_value(kind: DhcpFilterKind, op: DhcpFilterOp) -> &'static str {
+    match (kind, op) {
+        (DhcpFilterKind::Opcode, _) => "request or reply",
+        (DhcpFilterKind::MsgType, _) => "a message type such as DISCOVER",
+        (DhcpFilterKind::Number, _) => "a number",
+        (DhcpFilterKind::Bool, _) => "true or false",
+        (DhcpFilterKind::Address, DhcpFilterOp::In) => "a network such as 10.0.0.0/8",
+        (DhcpFilterKind::Address, _) => "an IPv4 address",
+        (DhcpFilterKind::Mac, DhcpFilterOp::In) => "a MAC prefix such as 00:11:22:00:00:00/24",
+        (DhcpFilterKind::Mac, _) => "a MAC address",
+        (DhcpFilterKind::Text, _) => "a string",
+        (DhcpFilterKind::Bytes, _) => "hex bytes or a string",
+        (DhcpFilterKind::Codes, _) => "an option code",
+    }
+}
+
+fn operators(kind: DhcpFilterKind) -> &'static [DhcpFilterOp] {
+    use DhcpFilterOp::*;
+    match kind {
+        DhcpFilterKind::Opcode | DhcpFilterKind::MsgType | DhcpFilterKind::Bool => &[Eq, Ne],
+        DhcpFilterKind::Number => &[Eq, Ne, Lt, Le, Gt, Ge],
+        DhcpFilterKind::Address | DhcpFilterKind::Mac => &[Eq, Ne, In],
+        DhcpFilterKind::Text | DhcpFilterKind::Bytes => &[Eq, Ne, Contains],
+        DhcpFilterKind::Codes => &[Contains],
+    }
+}
+
+// Precedence from low to high is ||, && and !, comparisons bind tightest.
+struct DhcpFilterParser {
+    tokens: Vec<(usize, DhcpFilterToken)>,
+    position: usize,
+    end: usize,
+}
+
+impl DhcpFilterParser {
+    fn peek(&self) -> Option<&DhcpFilterToken> {
+        self.tokens.get(self.position).map(|(_, x)| x)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.position).map_or(self.end, |(x, _)| *x)
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> DhcpFilterError {
+        DhcpFilterError { offset: self.offset(), message: message.into() }
+    }
+
+    fn next(&mut self) -> Option<DhcpFilterToken> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, token: DhcpFilterToken, name: &str) -> Result<(), DhcpFilterError> {
+        if self.peek() == Some(&token) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", name)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<DhcpFilter, DhcpFilterError> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some(&DhcpFilterToken::Or) {
+            self.position += 1;
+            filter = DhcpFilter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<DhcpFilter, DhcpFilterError> {
+        let mut filter = self.parse_not()?;
+        while self.peek() == Some(&DhcpFilterToken::And) {
+            self.position += 1;
+            filter = DhcpFilter::And(Box::new(filter), Box::new(self.parse_not()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_not(&mut self) -> Result<DhcpFilter, DhcpFilterError> {
+        if self.peek() == Some(&DhcpFilterToken::Not) {
+            self.position += 1;
+            return Ok(DhcpFilter::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_field(&mut self) -> Result<DhcpFilterField, DhcpFilterError> {
+        let offset = self.offset();
+        let name = match self.next() {
+            Some(DhcpFilterToken::Word(name)) => name,
+            _ => return Err(DhcpFilterError { offset, message: "expected a field".to_string() }),
+        };
+        if name == "option" {
+            return match self.next() {
+                Some(DhcpFilterToken::Word(code)) => code.parse().map(DhcpFilterField::Option)
+                    .map_err(|_| DhcpFilterError { offset, message: format!("invalid option code {}", code) }),
+                _ => Err(DhcpFilterError { offset, message: "expected an option code after option".to_string() }),
+            };
+        }
+        match DHCP_FILTER_FIELDS.iter().find(|(x, _)| *x == name) {
+            Some((_, field)) => Ok(*field),
+            None => {
+                let names: Vec<_> = DHCP_FILTER_FIELDS.iter().map(|(x, _)| *x).collect();
+                Err(DhcpFilterError { offset, message: format!("unknown field {}, expected one of {} or option <code>",
+                                                              name, names.join(", ")) })
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<DhcpFilter, DhcpFilterError> {
+        match self.peek() {
+            Some(DhcpFilterToken::Open) => {
+                self.position += 1;
+                let filter = self.parse_or()?;
+                self.expect(DhcpFilterToken::Close, ")")?;
+                return Ok(filter);
+            }
+            Some(DhcpFilterToken::Word(x)) if x == "has" &&
+                self.tokens.get(self.position + 1).map(|(_, x)| x) == Some(&DhcpFilterToken::Open) => {
+                self.position += 2;
+                let offset = self.offset();
+                let field = self.parse_field()?;
+                if field.option_id().is_none() {
+                    return Err(DhcpFilterError { offset, message: format!("{} isn't an option", field) });
+                }
+                self.expect(DhcpFilterToken::Close, ")")?;
+                return Ok(DhcpFilter::Has(field));
+            }
+            _ => (),
+        }
+        let field = self.parse_field()?;
+        let kind = field.kind();
+        let op = match self.peek() {
+            Some(DhcpFilterToken::Op(op)) => *op,
+            // A flag on its own is a test for it being set.
+            _ if kind == DhcpFilterKind::Bool => return Ok(DhcpFilter::Compare(field, DhcpFilterOp::Eq,
+                                                                                DhcpFilterValue::Bool(true))),
+            _ => return Err(self.error(format!("expected an operator after {}", field))),
+        };
+        if !operators(kind).contains(&op) {
+            let allowed: Vec<_> = operators(kind).iter().map(|x| x.to_string()).collect();
+            return Err(self.error(format!("{} can't be compared with {}, only {}", field, op, allowed.join(" "))));
+        }
+        self.position += 1;
+        let offset = self.offset();
+        let value = match self.next() {
+            Some(token) => parse_value(kind, op, &token),
+            None => None,
+        };
+        match value {
+            Some(value) => Ok(DhcpFilter::Compare(field, op, value)),
+            None => Err(DhcpFilterError { offset, message: format!("expected {} after {} {}", expected_value(kind, op),
+                                                                   field, op) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn packet() -> DhcpPacket {
+        let chaddr = MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55);
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 0x1234, chaddr);
+        packet.ciaddr = Some(Ipv4Addr::new(10, 1, 2, 3));
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpRequest));
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("My \"Host\"".to_string()));
+        packet.options.insert(DhcpOptionID::ClientIdentifier,
+                              DhcpOption::ClientIdentifier(vec![0x01, 0x00, 0x11, 0x22].into()));
+        packet
+    }
+
+    fn matches(text: &str) -> bool {
+        DhcpFilter::parse(text).unwrap().matches(&packet())
+    }
+
+    fn error(text: &str) -> (usize, String) {
+        let e = DhcpFilter::parse(text).unwrap_err();
+        (e.offset, e.message)
+    }
+
+    fn compare(field: DhcpFilterField, value: u64) -> Box<DhcpFilter> {
+        Box::new(DhcpFilter::Compare(field, DhcpFilterOp::Eq, DhcpFilterValue::Number(value)))
+    }
+
+    #[test]
+    fn precedence() {
+        let (hops, secs, xid) = (DhcpFilterField::Hops, DhcpFilterField::Secs, DhcpFilterField::Xid);
+        assert_eq!(DhcpFilter::parse("hops == 1 || secs == 2 && xid == 3").unwrap(),
+                   DhcpFilter::Or(compare(hops, 1), Box::new(DhcpFilter::And(compare(secs, 2), compare(xid, 3)))));
+        assert_eq!(DhcpFilter::parse("!hops == 1 && secs == 2").unwrap(),
+                   DhcpFilter::And(Box::new(DhcpFilter::Not(compare(hops, 1))), compare(secs, 2)));
+        assert_eq!(DhcpFilter::parse("not (hops == 1 or secs == 2)").unwrap(),
+                   DhcpFilter::Not(Box::new(DhcpFilter::Or(compare(hops, 1), compare(secs, 2)))));
+
+        assert!(matches("xid == 0x1234 || hops == 1 && secs == 1"));
+        assert!(!matches("(xid == 0x1234 || hops == 1) && secs == 1"));
+        assert!(matches("!broadcast && msgtype == request"));
+        assert!(!matches("!(broadcast || msgtype == request)"));
+    }
+
+    #[test]
+    fn in_prefixes() {
+        assert!(matches("ciaddr in 10.0.0.0/8"));
+        assert!(matches("ciaddr in 10.1.2.3/32"));
+        assert!(!matches("ciaddr in 10.1.2.4/30 || ciaddr in 192.168.0.0/16"));
+        assert!(matches("yiaddr in 0.0.0.0/0"));
+        assert!(matches("chaddr in 00:11:22:00:00:00/24"));
+        assert!(matches("chaddr in 00-11-23-00-00-00/23"));
+        assert!(!matches("chaddr in 00:11:23:00:00:00/24"));
+        assert!(matches("chaddr in ff:ff:ff:ff:ff:ff/0"));
+        assert_eq!(error("ciaddr in 10.0.0.0/33").0, 10);
+        assert_eq!(error("chaddr in 00:11:22:00:00:00/49").0, 10);
+        assert_eq!(error("ciaddr in 10.0.0.0").0, 10);
+    }
+
+    #[test]
+    fn has_option() {
+        assert!(matches("has(option 12)"));
+        assert!(matches("has(hostname) && !has(option 82)"));
+        assert!(!matches("has(server_id)"));
+        assert!(matches("option 61 == 01:00:11:22"));
+        assert!(matches("option 61 ~ 0011"));
+        assert!(!matches("option 82 != 00"));
+        assert_eq!(error("has(secs)"), (4, "secs isn't an option".to_string()));
+        assert_eq!(error("has(option 256)"), (4, "invalid option code 256".to_string()));
+        assert_eq!(error("has(option)"), (4, "expected an option code after option".to_string()));
+    }
+
+    #[test]
+    fn quoting() {
+        assert!(matches(r#"hostname == "My \"Host\"""#));
+        assert!(matches(r#"hostname ~ "my \"h""#));
+        assert!(matches(r#"sname == """#));
+        assert!(matches(r#"option 12 ~ "\"Host\"""#));
+        assert_eq!(error(r#"hostname == "My "Host"""#), (17, "expected && or ||".to_string()));
+        assert_eq!(DhcpFilter::parse(r#"message == "a\\b && c""#).unwrap(),
+                   DhcpFilter::Compare(DhcpFilterField::Message, DhcpFilterOp::Eq,
+                                       DhcpFilterValue::Text(r"a\b && c".to_string())));
+        assert_eq!(error(r#"hostname == "open"#), (12, "unterminated string".to_string()));
+        assert_eq!(error(r#"hostname == "escaped\"#), (12, "unterminated string".to_string()));
+    }
+
+    #[test]
+    fn error_offsets() {
+        assert_eq!(error("bogus == 1").0, 0);
+        assert_eq!(error("hops == 1 && bogus == 1").0, 13);
+        assert_eq!(error("hops == 1 hops").0, 10);
+        assert_eq!(error("(hops == 1").0, 10);
+        assert_eq!(error("hops ==").0, 7);
+        assert_eq!(error("hops == x"), (8, "expected a number after hops ==".to_string()));
+        assert_eq!(error("hops ~ 1"), (5, "hops can't be compared with ~, only == != < <= > >=".to_string()));
+        assert_eq!(error("hostname"), (8, "expected an operator after hostname".to_string()));
+        assert_eq!(error("hops == 1 $"), (10, "unexpected $".to_string()));
+        assert_eq!(error("msgtype == nope").0, 11);
+        assert_eq!(error("").0, 0);
+    }
+}
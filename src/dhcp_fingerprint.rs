@@ -0,0 +1,324 @@
+use crate::dhcp_packet::{BootpOpcode, DhcpOptionID};
+use crate::dhcp_view::DhcpPacketView;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+
+// The signatures shipped with the crate, files given to parse can add to them.
+const DHCP_BUILTIN_FINGERPRINTS: &str = include_str!("fingerprints.conf");
+
+// What a client reveals about itself in a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpFingerprint {
+    // Parameter Request List codes, in the order asked for.
+    pub prl: Vec<u8>,
+    // Codes of the options sent, in packet order.
+    pub options: Vec<u8>,
+    pub vendor_class: Option<String>,
+    pub max_msg_size: Option<u16>,
+}
+
+fn codes(codes: &[u8]) -> String {
+    codes.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",")
+}
+
+impl DhcpFingerprint {
+    // None for replies, only clients are fingerprinted.
+    pub fn from_view(view: &DhcpPacketView) -> Option<Self> {
+        if view.opcode() != BootpOpcode::BootRequest {
+            return None;
+        }
+        Some(DhcpFingerprint {
+            prl: view.option(DhcpOptionID::ParameterRequestList.code()).unwrap_or_default().to_vec(),
+            options: view.options().map(|x| x.code).collect(),
+            vendor_class: view.option(DhcpOptionID::VendorClassId.code())
+                .map(|x| String::from_utf8_lossy(x).into_owned()),
+            max_msg_size: view.option(DhcpOptionID::MaxMsgSize.code())
+                .and_then(|x| x.try_into().ok()).map(u16::from_be_bytes),
+        })
+    }
+
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        DhcpPacketView::parse(buf).ok().and_then(|(_, view)| Self::from_view(&view))
+    }
+}
+
+// In the syntax of the signature file, to copy into a new signature.
+impl fmt::Display for DhcpFingerprint {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "prl={} options={}", codes(&self.prl), codes(&self.options))?;
+        if let Some(vendor_class) = &self.vendor_class {
+            write!(w, " vendor_class={}", vendor_class)?;
+        }
+        if let Some(max_msg_size) = self.max_msg_size {
+            write!(w, " max_msg_size={}", max_msg_size)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DhcpSignature {
+    pub name: String,
+    pub class: Option<String>,
+    pub os: Option<String>,
+    pub vendor: Option<String>,
+    pub prl: Option<Vec<u8>>,
+    pub options: Option<Vec<u8>>,
+    // May hold * for any run of characters.
+    pub vendor_class: Option<String>,
+    pub max_msg_size: Option<u16>,
+}
+
+// Glob match where * stands for any run of characters, including none.
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<_> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+impl DhcpSignature {
+    // How many features matched, None when any of them differs. Features the
+    // signature leaves out match anything.
+    pub fn score(&self, fingerprint: &DhcpFingerprint) -> Option<usize> {
+        let checks = [
+            self.prl.as_ref().map(|x| *x == fingerprint.prl),
+            self.options.as_ref().map(|x| *x == fingerprint.options),
+            self.vendor_class.as_ref().map(|x| fingerprint.vendor_class.as_ref().is_some_and(|y| glob(x, y))),
+            self.max_msg_size.map(|x| Some(x) == fingerprint.max_msg_size),
+        ];
+        let mut score = 0;
+        for check in checks.iter().flatten() {
+            if !check {
+                return None;
+            }
+            score += 1;
+        }
+        Some(score).filter(|x| *x > 0)
+    }
+}
+
+impl fmt::Display for DhcpSignature {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{}", self.name)?;
+        let details: Vec<_> = [&self.class, &self.os, &self.vendor].iter().filter_map(|x| x.as_deref()).collect();
+        if !details.is_empty() {
+            write!(w, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpFingerprintError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DhcpFingerprintError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DhcpFingerprintDb {
+    pub signatures: Vec<DhcpSignature>,
+}
+
+fn parse_codes(value: &str) -> Option<Vec<u8>> {
+    value.split(',').map(|x| x.trim().parse().ok()).collect()
+}
+
+impl DhcpFingerprintDb {
+    pub fn builtin() -> Self {
+        Self::parse(DHCP_BUILTIN_FINGERPRINTS).expect("built-in fingerprints")
+    }
+
+    // Sections named after the device hold one `key = value` per line:
+    //
+    //   [Windows 10]
+    //   class = Windows
+    //   prl = 1,3,6,15,31,33,43,44,46,47,119,121,249,252
+    //   vendor_class = MSFT 5.0
+    //
+    // Lines starting with # are comments.
+    pub fn parse(text: &str) -> Result<Self, DhcpFingerprintError> {
+        let mut signatures: Vec<DhcpSignature> = Vec::new();
+        for (number, line) in text.lines().enumerate().map(|(i, x)| (i + 1, x.trim())) {
+            let error = |message: String| DhcpFingerprintError { line: number, message };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                signatures.push(DhcpSignature { name: name.trim().to_string(), ..Default::default() });
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error("expected [name] or key = value".to_string())),
+            };
+            let signature = signatures.last_mut().ok_or_else(|| error(format!("{} outside of a [section]", key)))?;
+            let invalid = || error(format!("invalid {} {}", key, value));
+            match key {
+                "class" => signature.class = Some(value.to_string()),
+                "os" => signature.os = Some(value.to_string()),
+                "vendor" => signature.vendor = Some(value.to_string()),
+                "prl" => signature.prl = Some(parse_codes(value).ok_or_else(invalid)?),
+                "options" => signature.options = Some(parse_codes(value).ok_or_else(invalid)?),
+                "vendor_class" => signature.vendor_class = Some(value.to_string()),
+                "max_msg_size" => signature.max_msg_size = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(error(format!("unknown key {}", key))),
+            }
+        }
+        Ok(DhcpFingerprintDb { signatures })
+    }
+
+    // Signatures added later are preferred when two match equally well.
+    pub fn extend(&mut self, other: DhcpFingerprintDb) {
+        let mut signatures = other.signatures;
+        signatures.append(&mut self.signatures);
+        self.signatures = signatures;
+    }
+
+    // The signature matching the most features.
+    pub fn lookup(&self, fingerprint: &DhcpFingerprint) -> Option<&DhcpSignature> {
+        self.signatures.iter()
+            .filter_map(|x| Some((x.score(fingerprint)?, x)))
+            .fold(None, |best: Option<(usize, &DhcpSignature)>, (score, x)| match best {
+                Some((best_score, _)) if best_score >= score => best,
+                _ => Some((score, x)),
+            })
+            .map(|(_, x)| x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_packet::{DhcpMessageType, DhcpOption, DhcpPacket, MacAddr};
+    use alloc::vec;
+
+    const WINDOWS_10_PRL: [u8; 14] = [1, 3, 6, 15, 31, 33, 43, 44, 46, 47, 119, 121, 249, 252];
+
+    fn features(prl: &[u8], vendor_class: Option<&str>) -> DhcpFingerprint {
+        DhcpFingerprint { prl: prl.to_vec(), options: vec![53, 55], vendor_class: vendor_class.map(|x| x.to_string()),
+                          max_msg_size: None }
+    }
+
+    #[test]
+    fn builtin_signatures_parse() {
+        let db = DhcpFingerprintDb::parse(DHCP_BUILTIN_FINGERPRINTS).unwrap();
+        assert_eq!(db.signatures.len(), 13);
+        assert!(db.signatures.iter().all(|x| x.prl.is_some() || x.vendor_class.is_some()));
+    }
+
+    #[test]
+    fn windows_10() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        packet.options.insert(DhcpOptionID::ParameterRequestList,
+                              DhcpOption::decode(55, &WINDOWS_10_PRL).unwrap());
+        packet.options.insert(DhcpOptionID::VendorClassId, DhcpOption::VendorClassId("MSFT 5.0".to_string()));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        let fingerprint = DhcpFingerprint::parse(&buf).unwrap();
+        assert_eq!(fingerprint.prl, WINDOWS_10_PRL);
+        assert_eq!(fingerprint.vendor_class.as_deref(), Some("MSFT 5.0"));
+        let db = DhcpFingerprintDb::builtin();
+        let signature = db.lookup(&fingerprint).unwrap();
+        assert_eq!(signature.to_string(), "Windows 8/10/11 (Windows, Windows 8/10/11, Microsoft)");
+
+        // Another PRL only matches the vendor class.
+        assert_eq!(db.lookup(&features(&[1, 3, 6], Some("MSFT 5.0"))).unwrap().name, "Windows");
+    }
+
+    #[test]
+    fn replies_are_not_fingerprinted() {
+        let packet = DhcpPacket::new(BootpOpcode::BootReply, 1, MacAddr::default());
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(DhcpFingerprint::parse(&buf), None);
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob("MSFT 5.0", "MSFT 5.0"));
+        assert!(!glob("MSFT 5.0", "MSFT 5.01"));
+        assert!(glob("MSFT*", "MSFT"));
+        assert!(glob("MSFT*", "MSFT 5.0"));
+        assert!(!glob("MSFT*", "MSF"));
+        assert!(glob("*", ""));
+        assert!(glob("*5.0", "MSFT 5.0"));
+        assert!(glob("a*a", "aa"));
+        assert!(glob("a*a", "aba"));
+        assert!(!glob("a*a", "a"));
+        assert!(!glob("a*b*b", "ab"));
+        assert!(glob("a**b", "ab"));
+    }
+
+    #[test]
+    fn any_difference_is_no_match() {
+        let signature = DhcpSignature {
+            name: "test".to_string(),
+            prl: Some(vec![1, 3, 6]),
+            vendor_class: Some("udhcp*".to_string()),
+            max_msg_size: Some(576),
+            ..Default::default()
+        };
+        let mut fingerprint = features(&[1, 3, 6], Some("udhcp 1.30"));
+        fingerprint.max_msg_size = Some(576);
+        assert_eq!(signature.score(&fingerprint), Some(3));
+        fingerprint.max_msg_size = Some(1500);
+        assert_eq!(signature.score(&fingerprint), None);
+        fingerprint.max_msg_size = Some(576);
+        fingerprint.prl = vec![1, 6, 3];
+        assert_eq!(signature.score(&fingerprint), None);
+        fingerprint.prl = vec![1, 3, 6];
+        fingerprint.vendor_class = None;
+        assert_eq!(signature.score(&fingerprint), None);
+        // A signature without features matches nothing.
+        assert_eq!(DhcpSignature::default().score(&fingerprint), None);
+    }
+
+    #[test]
+    fn added_signatures_are_preferred() {
+        let mut db = DhcpFingerprintDb::parse("[first]\nprl = 1,3\n[second]\nprl = 1,3\n").unwrap();
+        let fingerprint = features(&[1, 3], None);
+        assert_eq!(db.lookup(&fingerprint).unwrap().name, "first");
+        db.extend(DhcpFingerprintDb::parse("[added]\nprl = 1,3\n").unwrap());
+        assert_eq!(db.lookup(&fingerprint).unwrap().name, "added");
+        // But a signature matching more features still wins.
+        db.extend(DhcpFingerprintDb::parse("[vendor]\nvendor_class = x\n").unwrap());
+        db.extend(DhcpFingerprintDb::parse("[both]\nprl = 1,3\nvendor_class = x\n").unwrap());
+        db.extend(DhcpFingerprintDb::parse("[prl]\nprl = 1,3\n").unwrap());
+        assert_eq!(db.lookup(&features(&[1, 3], Some("x"))).unwrap().name, "both");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text| DhcpFingerprintDb::parse(text).unwrap_err();
+        assert_eq!(error("prl = 1"), DhcpFingerprintError { line: 1, message: "prl outside of a [section]".to_string() });
+        assert_eq!(error("# comment\n\n[a]\nprl = 1,x").to_string(), "line 4: invalid prl 1,x");
+        assert_eq!(error("[a]\nprl = 1,256").line, 2);
+        assert_eq!(error("[a]\nmax_msg_size = big").to_string(), "line 2: invalid max_msg_size big");
+        assert_eq!(error("[a]\ncolour = red").to_string(), "line 2: unknown key colour");
+        assert_eq!(error("[a]\n\n  just text").to_string(), "line 3: expected [name] or key = value");
+    }
+}
@@ -0,0 +1,531 @@
+use crate::dhcp_error::{DhcpEncodeError, DhcpParseError};
+use crate::dhcp_packet::{DhcpMessageType, DhcpPacket, MacAddr, ParseMode};
+use pnet::packet::ethernet::{EtherType, EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::{self, Ipv4Flags, Ipv4Packet, MutableIpv4Packet};
+use pnet::packet::udp::{self, MutableUdpPacket, UdpPacket};
+use pnet::packet::vlan::{MutableVlanPacket, VlanPacket};
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::net::Ipv4Addr;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+// What fits an IPv4 packet after its header and the UDP header.
+pub const DHCP_MAX_UDP_PAYLOAD: usize = 65_507;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const VLAN_HEADER_LEN: usize = 4;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const SLL_HEADER_LEN: usize = 16;
+const SLL2_HEADER_LEN: usize = 20;
+const SLL_BROADCAST: u8 = 1;
+const IPV4_TTL: u8 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhcpFrameHeader {
+    pub src_mac: MacAddr,
+    pub dst_mac: MacAddr,
+    pub vlan: Option<u16>,
+    pub src_ip: Ipv4Addr,
+    pub dst_ip: Ipv4Addr,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+// The link layers DHCP is commonly captured on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpLinkType {
+    Ethernet,
+    LinuxSll,
+    LinuxSll2,
+    RawIpv4,
+}
+
+impl DhcpLinkType {
+    // From the LINKTYPE_ values used in pcap and pcapng files.
+    pub fn from_linktype(linktype: u32) -> Option<Self> {
+        match linktype {
+            1 => Some(Self::Ethernet),
+            113 => Some(Self::LinuxSll),
+            276 => Some(Self::LinuxSll2),
+            101 | 228 => Some(Self::RawIpv4),
+            _ => None,
+        }
+    }
+
+    pub fn linktype(&self) -> u32 {
+        match self {
+            Self::Ethernet => 1,
+            Self::LinuxSll => 113,
+            Self::LinuxSll2 => 276,
+            Self::RawIpv4 => 228,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpFrame {
+    pub header: DhcpFrameHeader,
+    pub packet: DhcpPacket,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhcpFrameError<'a> {
+    Truncated(&'static str),
+    NotIpv4(EtherType),
+    NotUdp,
+    Fragmented,
+    IpChecksum { expected: u16, found: u16 },
+    UdpChecksum { expected: u16, found: u16 },
+    WrongPorts { src_port: u16, dst_port: u16 },
+    Dhcp(DhcpParseError<'a>),
+}
+
+impl<'a> fmt::Display for DhcpFrameError<'a> {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated(layer) => write!(w, "truncated {} header", layer),
+            Self::NotIpv4(ethertype) => write!(w, "not IPv4 but {}", ethertype),
+            Self::NotUdp => write!(w, "not UDP"),
+            Self::Fragmented => write!(w, "fragmented IPv4 packet"),
+            Self::IpChecksum { expected, found } =>
+                write!(w, "bad IPv4 checksum {:#06x}, expected {:#06x}", found, expected),
+            Self::UdpChecksum { expected, found } =>
+                write!(w, "bad UDP checksum {:#06x}, expected {:#06x}", found, expected),
+            Self::WrongPorts { src_port, dst_port } =>
+                write!(w, "UDP {} -> {} is not DHCP", src_port, dst_port),
+            Self::Dhcp(e) => write!(w, "{}", e),
+        }
+    }
+}
+
+fn is_dhcp_port(port: u16) -> bool {
+    port == DHCP_SERVER_PORT || port == DHCP_CLIENT_PORT
+}
+
+// A computed checksum of 0 is sent as all ones, 0 means no checksum (RFC 768).
+fn udp_checksum(packet: &UdpPacket, src_ip: &Ipv4Addr, dst_ip: &Ipv4Addr) -> u16 {
+    match udp::ipv4_checksum(packet, src_ip, dst_ip) {
+        0 => 0xffff,
+        checksum => checksum,
+    }
+}
+
+impl DhcpFrameHeader {
+    // A client without an address broadcasts to the servers on its link.
+    pub fn for_request(client_mac: MacAddr) -> Self {
+        DhcpFrameHeader {
+            src_mac: client_mac,
+            dst_mac: MacAddr([0xff; 6]),
+            vlan: None,
+            src_ip: Ipv4Addr::UNSPECIFIED,
+            dst_ip: Ipv4Addr::BROADCAST,
+            src_port: DHCP_CLIENT_PORT,
+            dst_port: DHCP_SERVER_PORT,
+        }
+    }
+
+    // Where a server sends its reply, RFC 2131 section 4.1. The MAC address
+    // of a relay agent isn't known here, dst_mac is left as broadcast for the
+    // caller to fill in from ARP.
+    pub fn for_reply(reply: &DhcpPacket, server_mac: MacAddr, server_ip: Ipv4Addr) -> Self {
+        let mut header = DhcpFrameHeader {
+            src_mac: server_mac,
+            dst_mac: MacAddr([0xff; 6]),
+            vlan: None,
+            src_ip: server_ip,
+            dst_ip: Ipv4Addr::BROADCAST,
+            src_port: DHCP_SERVER_PORT,
+            dst_port: DHCP_CLIENT_PORT,
+        };
+        let nak = reply.message_type() == Some(&DhcpMessageType::DhcpNak);
+        match (reply.giaddr, reply.ciaddr, reply.yiaddr) {
+            (Some(giaddr), _, _) => {
+                header.dst_ip = giaddr;
+                header.dst_port = DHCP_SERVER_PORT;
+            }
+            _ if nak => (),
+            (None, Some(addr), _) => {
+                header.dst_ip = addr;
+                header.dst_mac = reply.chaddr;
+            }
+            _ if reply.broadcast => (),
+            (None, None, Some(addr)) => {
+                header.dst_ip = addr;
+                header.dst_mac = reply.chaddr;
+            }
+            _ => (),
+        }
+        header
+    }
+
+    // Takes an Ethernet frame, optionally 802.1Q tagged, and returns the
+    // UDP payload. Only the outer VLAN identifier of a stacked tag is kept.
+    pub fn parse(buf: &[u8]) -> Result<(Self, &[u8]), DhcpFrameError<'_>> {
+        Self::parse_link(DhcpLinkType::Ethernet, buf)
+    }
+
+    // Linux cooked captures only record the source address, the destination
+    // is broadcast for broadcast packets and left zero otherwise.
+    pub fn parse_link(link_type: DhcpLinkType, buf: &[u8]) -> Result<(Self, &[u8]), DhcpFrameError<'_>> {
+        Self::parse_link_with_mode(link_type, buf, ParseMode::Strict)
+    }
+
+    // Lenient mode skips the checksums, captures taken on the sending host
+    // usually have them wrong because the NIC fills them in later.
+    pub fn parse_link_with_mode(link_type: DhcpLinkType, buf: &[u8], mode: ParseMode)
+                                -> Result<(Self, &[u8]), DhcpFrameError<'_>> {
+        let cooked_mac = |addr_len: usize, addr: &[u8]| {
+            let mut mac = MacAddr::default();
+            if addr_len == mac.0.len() {
+                mac.0.copy_from_slice(&addr[..6]);
+            }
+            mac
+        };
+        let cooked_dst = |packet_type: u8| if packet_type == SLL_BROADCAST { MacAddr([0xff; 6]) } else { MacAddr::default() };
+        let be_u16 = |buf: &[u8]| u16::from_be_bytes([buf[0], buf[1]]);
+        match link_type {
+            DhcpLinkType::Ethernet => {
+                let ethernet = EthernetPacket::new(buf).ok_or(DhcpFrameError::Truncated("Ethernet"))?;
+                Self::parse_ethertype(ethernet.get_ethertype(), &buf[ETHERNET_HEADER_LEN..],
+                                      ethernet.get_source().into(), ethernet.get_destination().into(), mode)
+            }
+            DhcpLinkType::LinuxSll => {
+                if buf.len() < SLL_HEADER_LEN {
+                    return Err(DhcpFrameError::Truncated("SLL"));
+                }
+                let (src_mac, dst_mac) = (cooked_mac(be_u16(&buf[4..]).into(), &buf[6..14]), cooked_dst(buf[1]));
+                Self::parse_ethertype(EtherType(be_u16(&buf[14..])), &buf[SLL_HEADER_LEN..], src_mac, dst_mac, mode)
+            }
+            DhcpLinkType::LinuxSll2 => {
+                if buf.len() < SLL2_HEADER_LEN {
+                    return Err(DhcpFrameError::Truncated("SLL2"));
+                }
+                let (src_mac, dst_mac) = (cooked_mac(buf[11].into(), &buf[12..20]), cooked_dst(buf[10]));
+                Self::parse_ethertype(EtherType(be_u16(buf)), &buf[SLL2_HEADER_LEN..], src_mac, dst_mac, mode)
+            }
+            DhcpLinkType::RawIpv4 =>
+                Self::parse_ethertype(EtherTypes::Ipv4, buf, MacAddr::default(), MacAddr::default(), mode),
+        }
+    }
+
+    fn parse_ethertype(ethertype: EtherType, buf: &[u8], src_mac: MacAddr, dst_mac: MacAddr, mode: ParseMode)
+                       -> Result<(Self, &[u8]), DhcpFrameError<'_>> {
+        let mut ethertype = ethertype;
+        let mut payload = buf;
+        let mut vlan = None;
+        while ethertype == EtherTypes::Vlan || ethertype == EtherTypes::PBridge || ethertype == EtherTypes::QinQ {
+            let tag = VlanPacket::new(payload).ok_or(DhcpFrameError::Truncated("802.1Q"))?;
+            vlan = vlan.or_else(|| Some(tag.get_vlan_identifier()));
+            ethertype = tag.get_ethertype();
+            payload = &payload[VLAN_HEADER_LEN..];
+        }
+        if ethertype != EtherTypes::Ipv4 {
+            return Err(DhcpFrameError::NotIpv4(ethertype));
+        }
+
+        let ip = Ipv4Packet::new(payload).ok_or(DhcpFrameError::Truncated("IPv4"))?;
+        let ip_header_len = usize::from(ip.get_header_length()) * 4;
+        let ip_len = usize::from(ip.get_total_length());
+        if ip.get_version() != 4 || ip_header_len < IPV4_HEADER_LEN || ip_len < ip_header_len || ip_len > payload.len() {
+            return Err(DhcpFrameError::Truncated("IPv4"));
+        }
+        let checksum = ipv4::checksum(&ip);
+        if mode == ParseMode::Strict && checksum != ip.get_checksum() {
+            return Err(DhcpFrameError::IpChecksum { expected: checksum, found: ip.get_checksum() });
+        }
+        if ip.get_flags() & Ipv4Flags::MoreFragments != 0 || ip.get_fragment_offset() != 0 {
+            return Err(DhcpFrameError::Fragmented);
+        }
+        if ip.get_next_level_protocol() != IpNextHeaderProtocols::Udp {
+            return Err(DhcpFrameError::NotUdp);
+        }
+
+        // Ethernet pads short frames, the lengths in the headers are what counts.
+        let payload = &payload[ip_header_len..ip_len];
+        let udp = UdpPacket::new(payload).ok_or(DhcpFrameError::Truncated("UDP"))?;
+        let udp_len = usize::from(udp.get_length());
+        if udp_len < UDP_HEADER_LEN || udp_len > payload.len() {
+            return Err(DhcpFrameError::Truncated("UDP"));
+        }
+        let udp = UdpPacket::new(&payload[..udp_len]).unwrap();
+        let (src_ip, dst_ip) = (ip.get_source(), ip.get_destination());
+        if mode == ParseMode::Strict && udp.get_checksum() != 0 {
+            let checksum = udp_checksum(&udp, &src_ip, &dst_ip);
+            if checksum != udp.get_checksum() {
+                return Err(DhcpFrameError::UdpChecksum { expected: checksum, found: udp.get_checksum() });
+            }
+        }
+        let (src_port, dst_port) = (udp.get_source(), udp.get_destination());
+        if !is_dhcp_port(src_port) || !is_dhcp_port(dst_port) {
+            return Err(DhcpFrameError::WrongPorts { src_port, dst_port });
+        }
+
+        let header = DhcpFrameHeader {
+            src_mac,
+            dst_mac,
+            vlan,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+        };
+        Ok((header, &payload[UDP_HEADER_LEN..udp_len]))
+    }
+
+    // Wraps any payload, also one that doesn't parse as DHCP.
+    pub fn encode(&self, payload: &[u8], buf: &mut Vec<u8>) -> Result<(), DhcpEncodeError> {
+        if payload.len() > DHCP_MAX_UDP_PAYLOAD {
+            return Err(DhcpEncodeError::PayloadTooLong(payload.len()));
+        }
+        let start = buf.len();
+        let vlan_len = if self.vlan.is_some() { VLAN_HEADER_LEN } else { 0 };
+        let ip_start = start + ETHERNET_HEADER_LEN + vlan_len;
+        let udp_len = UDP_HEADER_LEN + payload.len();
+        buf.resize(ip_start + IPV4_HEADER_LEN + udp_len, 0);
+
+        let mut ethernet = MutableEthernetPacket::new(&mut buf[start..]).unwrap();
+        ethernet.set_destination(self.dst_mac.into());
+        ethernet.set_source(self.src_mac.into());
+        ethernet.set_ethertype(if self.vlan.is_some() { EtherTypes::Vlan } else { EtherTypes::Ipv4 });
+        if let Some(vlan_identifier) = self.vlan {
+            let mut vlan = MutableVlanPacket::new(&mut buf[start + ETHERNET_HEADER_LEN..]).unwrap();
+            vlan.set_vlan_identifier(vlan_identifier);
+            vlan.set_ethertype(EtherTypes::Ipv4);
+        }
+
+        let mut ip = MutableIpv4Packet::new(&mut buf[ip_start..]).unwrap();
+        ip.set_version(4);
+        ip.set_header_length((IPV4_HEADER_LEN / 4) as u8);
+        ip.set_total_length((IPV4_HEADER_LEN + udp_len) as u16);
+        ip.set_ttl(IPV4_TTL);
+        ip.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+        ip.set_source(self.src_ip);
+        ip.set_destination(self.dst_ip);
+        let checksum = ipv4::checksum(&ip.to_immutable());
+        ip.set_checksum(checksum);
+
+        let mut udp = MutableUdpPacket::new(&mut buf[ip_start + IPV4_HEADER_LEN..]).unwrap();
+        udp.set_source(self.src_port);
+        udp.set_destination(self.dst_port);
+        udp.set_length(udp_len as u16);
+        udp.set_payload(payload);
+        let checksum = udp_checksum(&udp.to_immutable(), &self.src_ip, &self.dst_ip);
+        udp.set_checksum(checksum);
+        Ok(())
+    }
+}
+
+impl DhcpFrame {
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<(), DhcpEncodeError> {
+        let mut payload = Vec::new();
+        self.packet.encode(&mut payload)?;
+        self.header.encode(&payload, buf)
+    }
+
+    pub fn parse(buf: &[u8]) -> Result<Self, DhcpFrameError<'_>> {
+        let (header, payload) = DhcpFrameHeader::parse(buf)?;
+        let (_, packet) = DhcpPacket::parse(payload).map_err(|e| match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => DhcpFrameError::Dhcp(e),
+            nom::Err::Incomplete(_) => DhcpFrameError::Truncated("DHCP"),
+        })?;
+        Ok(DhcpFrame { header, packet })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_packet::{BootpOpcode, DhcpOption, DhcpOptionID};
+    use alloc::vec;
+
+    const CLIENT: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 1]);
+    const SERVER: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 2]);
+    const IP_START: usize = ETHERNET_HEADER_LEN;
+    const UDP_START: usize = IP_START + IPV4_HEADER_LEN;
+
+    fn packet(opcode: BootpOpcode, message_type: DhcpMessageType) -> DhcpPacket {
+        let mut packet = DhcpPacket::new(opcode, 0x1234, CLIENT);
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        packet
+    }
+
+    fn discover() -> DhcpFrame {
+        DhcpFrame {
+            header: DhcpFrameHeader::for_request(CLIENT),
+            packet: packet(BootpOpcode::BootRequest, DhcpMessageType::DhcpDiscover),
+        }
+    }
+
+    fn encode(frame: &DhcpFrame) -> Vec<u8> {
+        let mut buf = Vec::new();
+        frame.encode(&mut buf).unwrap();
+        buf
+    }
+
+    fn parse(link_type: DhcpLinkType, buf: &[u8], mode: ParseMode) -> Result<DhcpFrameHeader, DhcpFrameError<'_>> {
+        DhcpFrameHeader::parse_link_with_mode(link_type, buf, mode).map(|(header, _)| header)
+    }
+
+    #[test]
+    fn round_trip() {
+        let frame = discover();
+        let buf = encode(&frame);
+        assert_eq!(buf.len(), UDP_START + UDP_HEADER_LEN + 300);
+        assert_eq!(DhcpFrame::parse(&buf), Ok(frame));
+    }
+
+    #[test]
+    fn vlan_round_trip() {
+        let mut frame = discover();
+        frame.header.vlan = Some(100);
+        let buf = encode(&frame);
+        assert_eq!(buf[12..18], [0x81, 0x00, 0x00, 100, 0x08, 0x00]);
+        assert_eq!(DhcpFrame::parse(&buf), Ok(frame.clone()));
+
+        // An outer 802.1ad tag in front, its identifier is the one kept.
+        let mut qinq = buf[..12].to_vec();
+        qinq.extend_from_slice(&[0x88, 0xa8, 0x00, 200]);
+        qinq.extend_from_slice(&buf[12..]);
+        frame.header.vlan = Some(200);
+        assert_eq!(DhcpFrame::parse(&qinq), Ok(frame));
+    }
+
+    #[test]
+    fn ethernet_padding_is_ignored() {
+        let mut buf = encode(&discover());
+        let (_, payload) = DhcpFrameHeader::parse(&buf).unwrap();
+        assert_eq!(payload.len(), 300);
+        buf.extend_from_slice(&[0; 4]);
+        assert_eq!(DhcpFrameHeader::parse(&buf).unwrap().1.len(), 300);
+    }
+
+    #[test]
+    fn checksums() {
+        let buf = encode(&discover());
+        let mut bad_ip = buf.clone();
+        bad_ip[IP_START + 10] ^= 0xff;
+        assert!(matches!(parse(DhcpLinkType::Ethernet, &bad_ip, ParseMode::Strict),
+                         Err(DhcpFrameError::IpChecksum { .. })));
+        assert!(parse(DhcpLinkType::Ethernet, &bad_ip, ParseMode::Lenient).is_ok());
+
+        let mut bad_udp = buf.clone();
+        bad_udp[UDP_START + 6] ^= 0xff;
+        assert!(matches!(parse(DhcpLinkType::Ethernet, &bad_udp, ParseMode::Strict),
+                         Err(DhcpFrameError::UdpChecksum { .. })));
+        assert!(parse(DhcpLinkType::Ethernet, &bad_udp, ParseMode::Lenient).is_ok());
+
+        // No UDP checksum at all is fine.
+        let mut no_udp = buf;
+        no_udp[UDP_START + 6..UDP_START + 8].copy_from_slice(&[0, 0]);
+        assert!(parse(DhcpLinkType::Ethernet, &no_udp, ParseMode::Strict).is_ok());
+    }
+
+    #[test]
+    fn cooked_captures() {
+        let buf = encode(&discover());
+        let ip = &buf[IP_START..];
+
+        let mut sll = vec![0, SLL_BROADCAST, 0, 1, 0, 6];
+        sll.extend_from_slice(&CLIENT.0);
+        sll.extend_from_slice(&[0, 0, 0x08, 0x00]);
+        sll.extend_from_slice(ip);
+        let header = parse(DhcpLinkType::LinuxSll, &sll, ParseMode::Strict).unwrap();
+        assert_eq!(header, DhcpFrameHeader::for_request(CLIENT));
+
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6];
+        sll2.extend_from_slice(&CLIENT.0);
+        sll2.extend_from_slice(&[0, 0]);
+        sll2.extend_from_slice(ip);
+        let header = parse(DhcpLinkType::LinuxSll2, &sll2, ParseMode::Strict).unwrap();
+        assert_eq!((header.src_mac, header.dst_mac), (CLIENT, MacAddr::default()));
+
+        assert_eq!(parse(DhcpLinkType::LinuxSll, &sll[..10], ParseMode::Strict), Err(DhcpFrameError::Truncated("SLL")));
+        assert_eq!(parse(DhcpLinkType::RawIpv4, ip, ParseMode::Strict).unwrap().dst_ip, Ipv4Addr::BROADCAST);
+    }
+
+    #[test]
+    fn not_dhcp() {
+        let mut frame = discover();
+        frame.header.dst_port = 53;
+        let buf = encode(&frame);
+        assert_eq!(parse(DhcpLinkType::Ethernet, &buf, ParseMode::Strict),
+                   Err(DhcpFrameError::WrongPorts { src_port: 68, dst_port: 53 }));
+
+        let mut arp = encode(&discover());
+        arp[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert_eq!(parse(DhcpLinkType::Ethernet, &arp, ParseMode::Strict), Err(DhcpFrameError::NotIpv4(EtherTypes::Arp)));
+        assert_eq!(parse(DhcpLinkType::Ethernet, &arp[..10], ParseMode::Strict),
+                   Err(DhcpFrameError::Truncated("Ethernet")));
+    }
+
+    #[test]
+    fn fragments() {
+        let buf = encode(&discover());
+        let mut first = buf.clone();
+        first[IP_START + 6] |= 0x20;
+        assert_eq!(parse(DhcpLinkType::Ethernet, &first, ParseMode::Lenient), Err(DhcpFrameError::Fragmented));
+        let mut last = buf;
+        last[IP_START + 7] = 1;
+        assert_eq!(parse(DhcpLinkType::Ethernet, &last, ParseMode::Lenient), Err(DhcpFrameError::Fragmented));
+    }
+
+    #[test]
+    fn long_payloads_are_not_encoded() {
+        let header = DhcpFrameHeader::for_request(CLIENT);
+        let mut buf = vec![1];
+        assert_eq!(header.encode(&vec![0; DHCP_MAX_UDP_PAYLOAD + 1], &mut buf),
+                   Err(DhcpEncodeError::PayloadTooLong(DHCP_MAX_UDP_PAYLOAD + 1)));
+        assert_eq!(buf, [1]);
+        header.encode(&vec![0; DHCP_MAX_UDP_PAYLOAD], &mut buf).unwrap();
+        let (parsed, payload) = DhcpFrameHeader::parse(&buf[1..]).unwrap();
+        assert_eq!((parsed, payload.len()), (header, DHCP_MAX_UDP_PAYLOAD));
+    }
+
+    #[test]
+    fn reply_destinations() {
+        let server_ip = Ipv4Addr::new(192, 0, 2, 1);
+        let relay = Ipv4Addr::new(198, 51, 100, 1);
+        let client_ip = Ipv4Addr::new(192, 0, 2, 10);
+        let destination = |reply: &DhcpPacket| {
+            let header = DhcpFrameHeader::for_reply(reply, SERVER, server_ip);
+            assert_eq!((header.src_mac, header.src_ip, header.src_port), (SERVER, server_ip, DHCP_SERVER_PORT));
+            (header.dst_mac, header.dst_ip, header.dst_port)
+        };
+        let broadcast = (MacAddr([0xff; 6]), Ipv4Addr::BROADCAST, DHCP_CLIENT_PORT);
+
+        // Through a relay agent, to its server port.
+        let mut ack = packet(BootpOpcode::BootReply, DhcpMessageType::DhcpAck);
+        ack.giaddr = Some(relay);
+        ack.ciaddr = Some(client_ip);
+        assert_eq!(destination(&ack), (MacAddr([0xff; 6]), relay, DHCP_SERVER_PORT));
+        let mut nak = packet(BootpOpcode::BootReply, DhcpMessageType::DhcpNak);
+        nak.giaddr = Some(relay);
+        assert_eq!(destination(&nak), (MacAddr([0xff; 6]), relay, DHCP_SERVER_PORT));
+
+        // A NAK on the link is always broadcast.
+        nak.giaddr = None;
+        nak.ciaddr = Some(client_ip);
+        assert_eq!(destination(&nak), broadcast);
+
+        // A client with an address gets it there, even when asking for broadcast.
+        ack.giaddr = None;
+        ack.broadcast = true;
+        assert_eq!(destination(&ack), (CLIENT, client_ip, DHCP_CLIENT_PORT));
+
+        // Otherwise the broadcast bit decides between broadcast and yiaddr.
+        let mut offer = packet(BootpOpcode::BootReply, DhcpMessageType::DhcpOffer);
+        offer.yiaddr = Some(client_ip);
+        assert_eq!(destination(&offer), (CLIENT, client_ip, DHCP_CLIENT_PORT));
+        offer.broadcast = true;
+        assert_eq!(destination(&offer), broadcast);
+        offer.broadcast = false;
+        offer.yiaddr = None;
+        assert_eq!(destination(&offer), broadcast);
+    }
+}
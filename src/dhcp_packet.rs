@@ -1,46 +1,74 @@
-extern crate byte_string;
-
+#[cfg(feature = "pnet")]
 use pnet::packet::arp;
+#[cfg(feature = "pnet")]
 use pnet::datalink;
 use nom::number::complete::{be_u8, be_u16, be_u32};
 use nom::sequence::{tuple, terminated, preceded};
 use nom::{bytes::complete::tag, bytes::complete::take, combinator::map, combinator::value, combinator::verify};
-use byte_string::ByteStr;
+#[cfg(feature = "std")]
 use std::net::Ipv4Addr;
-use std::time;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+use core::convert::TryFrom;
+use core::time;
 use nom::multi::{fold_many0, length_data};
-use nom::lib::std::collections::HashMap;
-use nom::combinator::map_parser;
-use std::fmt;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+use alloc::fmt::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use nom::combinator::{all_consuming, map_opt, map_parser, map_res, rest};
+use nom::branch::alt;
+use nom::error::{context, ErrorKind, ParseError};
+use crate::dhcp_error::{DhcpEncodeError, DhcpParseError};
+use crate::dhcp_view::split_option;
+use nom::multi::many1;
+use core::fmt;
 
-type Input<'a> = &'a [u8];
-type Result<'a, T> = nom::IResult<Input<'a>, T, ()>;
+pub(crate) type Input<'a> = &'a [u8];
+pub(crate) type Result<'a, T> = nom::IResult<Input<'a>, T, DhcpParseError<'a>>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpDuration(time::Duration);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpBytes(Vec<u8>);
 
-impl std::convert::From<Vec<u8>> for DhcpBytes {
+impl From<Vec<u8>> for DhcpBytes {
     fn from(v: Vec<u8>) -> Self {
         Self(v)
     }
 }
 
+impl From<&DhcpBytes> for Vec<u8> {
+    fn from(v: &DhcpBytes) -> Self {
+        v.0.clone()
+    }
+}
+
 impl DhcpDuration {
-    fn new(s: u64, n: u32) -> Self {
+    pub fn new(s: u64, n: u32) -> Self {
         DhcpDuration(time::Duration::new(s, n))
     }
+
+    pub fn as_secs(&self) -> u64 {
+        self.0.as_secs()
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.0.as_secs() as u32).to_be_bytes());
+    }
 }
 
-#[derive(Debug, Copy, Clone, Display)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display)]
 pub enum BootpOpcode {
     BootRequest,
     BootReply,
 }
 
-#[derive(Debug, Clone, Display)]
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
 pub enum DhcpMessageType {
     #[strum(to_string = "Discover")]
     DhcpDiscover,
@@ -60,23 +88,72 @@ pub enum DhcpMessageType {
     DhcpInform,
     #[strum(to_string = "Force Renew")]
     DhcpForceRenew,
+    #[strum(to_string = "Lease Query")]
+    DhcpLeaseQuery,
+    #[strum(to_string = "Lease Unassigned")]
+    DhcpLeaseUnassigned,
+    #[strum(to_string = "Lease Unknown")]
+    DhcpLeaseUnknown,
+    #[strum(to_string = "Lease Active")]
+    DhcpLeaseActive,
+    #[strum(to_string = "Bulk Lease Query")]
+    DhcpBulkLeaseQuery,
+    #[strum(to_string = "Lease Query Done")]
+    DhcpLeaseQueryDone,
 }
 
 impl DhcpMessageType {
     fn parse(buf: Input) -> Result<Self> {
-        map(preceded(verify_option_length(|x| x == 1), be_u8), |x|
-            match x {
-                1 => Self::DhcpDiscover,
-                2 => Self::DhcpOffer,
-                3 => Self::DhcpRequest,
-                4 => Self::DhcpDecline,
-                5 => Self::DhcpAck,
-                6 => Self::DhcpNak,
-                7 => Self::DhcpRelease,
-                8 => Self::DhcpInform,
-                9 => Self::DhcpForceRenew,
-                _ => panic!("Unknown DHCP message type {}", x),
-            })(buf)
+        map_opt(preceded(verify_option_length(|x| x == 1), be_u8), Self::from_code)(buf)
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::DhcpDiscover),
+            2 => Some(Self::DhcpOffer),
+            3 => Some(Self::DhcpRequest),
+            4 => Some(Self::DhcpDecline),
+            5 => Some(Self::DhcpAck),
+            6 => Some(Self::DhcpNak),
+            7 => Some(Self::DhcpRelease),
+            8 => Some(Self::DhcpInform),
+            9 => Some(Self::DhcpForceRenew),
+            10 => Some(Self::DhcpLeaseQuery),
+            11 => Some(Self::DhcpLeaseUnassigned),
+            12 => Some(Self::DhcpLeaseUnknown),
+            13 => Some(Self::DhcpLeaseActive),
+            14 => Some(Self::DhcpBulkLeaseQuery),
+            15 => Some(Self::DhcpLeaseQueryDone),
+            _ => None,
+        }
+    }
+
+    // The name as printed, in any case and with or without the space or an
+    // underscore in it, `forcerenew` or `FORCE_RENEW`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalize = |x: &str| x.chars().filter(|x| *x != ' ' && *x != '_').collect::<String>().to_ascii_lowercase();
+        let name = normalize(name);
+        (0..=u8::MAX).filter_map(Self::from_code).find(|x| normalize(&x.to_string()) == name)
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::DhcpDiscover => 1,
+            Self::DhcpOffer => 2,
+            Self::DhcpRequest => 3,
+            Self::DhcpDecline => 4,
+            Self::DhcpAck => 5,
+            Self::DhcpNak => 6,
+            Self::DhcpRelease => 7,
+            Self::DhcpInform => 8,
+            Self::DhcpForceRenew => 9,
+            Self::DhcpLeaseQuery => 10,
+            Self::DhcpLeaseUnassigned => 11,
+            Self::DhcpLeaseUnknown => 12,
+            Self::DhcpLeaseActive => 13,
+            Self::DhcpBulkLeaseQuery => 14,
+            Self::DhcpLeaseQueryDone => 15,
+        }
     }
 }
 
@@ -89,7 +166,7 @@ impl DhcpBytes {
     }
 }
 
-#[derive(Debug, Clone, Copy, Display)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Display)]
 pub enum DhcpForceRenewNonceAlgos {
     #[strum(to_string = "HMAC MD5")]
     HmacMd5,
@@ -104,25 +181,38 @@ impl DhcpForceRenewNonceAlgos {
             x => Self::Other(x),
         }
     }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::HmacMd5 => 1,
+            Self::Other(x) => *x,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpForceRenewNonceCapable(Vec<DhcpForceRenewNonceAlgos>);
 
 impl DhcpForceRenewNonceCapable {
     fn parse(buf: Input) -> Result<Self> {
         map(length_data(verify_option_length(|x| x > 0)),
-            |x| DhcpForceRenewNonceCapable(x.into_iter().
+            |x| DhcpForceRenewNonceCapable(x.iter().
                 map(|y| DhcpForceRenewNonceAlgos::parse(*y)).collect()),
         )(buf)
     }
 }
 
+impl From<&DhcpForceRenewNonceCapable> for Vec<DhcpForceRenewNonceAlgos> {
+    fn from(v: &DhcpForceRenewNonceCapable) -> Self {
+        v.0.clone()
+    }
+}
+
 impl fmt::Display for DhcpForceRenewNonceCapable {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         let output: String = self.0.iter().
-            map(|x| fmt::format(format_args!("{}, ", x))).collect();
-        write!(w, "{}", output.trim_end_matches(" ,"))
+            map(|x| format(format_args!("{}, ", x))).collect();
+        write!(w, "{}", output.trim_end_matches(", "))
     }
 }
 
@@ -133,21 +223,36 @@ const DHCP_OPTION_HOSTNAME: u8 = 12;
 const DHCP_OPTION_DOMAINNAME: u8 = 15;
 const DHCP_OPTION_INTERFACEMTU: u8 = 26;
 const DHCP_OPTION_BROADCAST_ADDR: u8 = 28;
+const DHCP_OPTION_REQUESTED_IP: u8 = 50;
 const DHCP_OPTION_LEASETIME: u8 = 51;
 const DHCP_OPTION_MSGTYPE: u8 = 53;
 const DHCP_OPTION_SERVERID: u8 = 54;
 const DHCP_OPTION_PARAM_REQUEST_LIST: u8 = 55;
+const DHCP_OPTION_MESSAGE: u8 = 56;
 const DHCP_OPTION_MAX_MSG_SIZE: u8 = 57;
 const DHCP_OPTION_RENEWAL_INTERVAL: u8 = 58;
 const DHCP_OPTION_REBINDING_INTERVAL: u8 = 59;
 const DHCP_OPTION_VENDOR_CLASS_ID: u8 = 60;
 const DHCP_OPTION_CLIENT_IDENTIFIER: u8 = 61;
 const DHCP_OPTION_RAPID_COMMIT: u8 = 80;
+const DHCP_OPTION_CLIENT_LAST_TRANSACTION_TIME: u8 = 91;
+const DHCP_OPTION_ASSOCIATED_IP: u8 = 92;
 const DHCP_OPTION_DOMAIN_SEARCH: u8 = 119;
+const DHCP_OPTION_SIP_SERVERS: u8 = 120;
 const DHCP_OPTION_FORCE_RENEW_NONCE_CAP: u8 = 145;
-const DHCP_OPTION_END: u8 = 255;
+const DHCP_OPTION_STATUS_CODE: u8 = 151;
+const DHCP_OPTION_BASE_TIME: u8 = 152;
+const DHCP_OPTION_START_TIME_OF_STATE: u8 = 153;
+const DHCP_OPTION_QUERY_START_TIME: u8 = 154;
+const DHCP_OPTION_QUERY_END_TIME: u8 = 155;
+const DHCP_OPTION_DHCP_STATE: u8 = 156;
+const DHCP_OPTION_DATA_SOURCE: u8 = 157;
+pub(crate) const DHCP_OPTION_END: u8 = 255;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Display)]
+pub(crate) const DHCP_MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const BOOTP_MIN_LEN: usize = 300;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
 pub enum DhcpOptionID {
     #[strum(to_string="Subnet Mask")]
     SubnetMask,
@@ -162,6 +267,8 @@ pub enum DhcpOptionID {
     InterfaceMTU,
     #[strum(to_string="Broadcast Address")]
     BroadcastAddr,
+    #[strum(to_string="Requested IP Address")]
+    RequestedIpAddr,
     #[strum(to_string="Lease Time")]
     LeaseTime,
     #[strum(to_string="Server ID")]
@@ -172,6 +279,8 @@ pub enum DhcpOptionID {
     RebindingInterval,
     #[strum(to_string="Domain Search")]
     DomainSearch,
+    #[strum(to_string="SIP Servers")]
+    SipServers,
     #[strum(to_string="Message Type")]
     MsgType,
     #[strum(to_string="Client Identifier")]
@@ -186,6 +295,25 @@ pub enum DhcpOptionID {
     ForceRenewNonceCap,
     #[strum(to_string="Parameter Request List")]
     ParameterRequestList,
+    Message,
+    #[strum(to_string="Client Last Transaction Time")]
+    ClientLastTransactionTime,
+    #[strum(to_string="Associated IP")]
+    AssociatedIp,
+    #[strum(to_string="Status Code")]
+    StatusCode,
+    #[strum(to_string="Base Time")]
+    BaseTime,
+    #[strum(to_string="Start Time of State")]
+    StartTimeOfState,
+    #[strum(to_string="Query Start Time")]
+    QueryStartTime,
+    #[strum(to_string="Query End Time")]
+    QueryEndTime,
+    #[strum(to_string="DHCP State")]
+    DhcpState,
+    #[strum(to_string="Data Source")]
+    DataSource,
     OptionEnd,
     Pad,
     #[strum(to_string="Unknown Parameter")]
@@ -202,10 +330,12 @@ impl DhcpOptionID {
             DHCP_OPTION_DOMAINNAME => DhcpOptionID::DomainName,
             DHCP_OPTION_INTERFACEMTU => DhcpOptionID::InterfaceMTU,
             DHCP_OPTION_BROADCAST_ADDR => DhcpOptionID::BroadcastAddr,
+            DHCP_OPTION_REQUESTED_IP => DhcpOptionID::RequestedIpAddr,
             DHCP_OPTION_LEASETIME => DhcpOptionID::LeaseTime,
             DHCP_OPTION_MSGTYPE => DhcpOptionID::MsgType,
             DHCP_OPTION_SERVERID => DhcpOptionID::ServerID,
             DHCP_OPTION_PARAM_REQUEST_LIST => DhcpOptionID::ParameterRequestList,
+            DHCP_OPTION_MESSAGE => DhcpOptionID::Message,
             DHCP_OPTION_MAX_MSG_SIZE => DhcpOptionID::MaxMsgSize,
             DHCP_OPTION_RENEWAL_INTERVAL => DhcpOptionID::RenewalInterval,
             DHCP_OPTION_REBINDING_INTERVAL => DhcpOptionID::RebindingInterval,
@@ -213,24 +343,73 @@ impl DhcpOptionID {
             DHCP_OPTION_CLIENT_IDENTIFIER => DhcpOptionID::ClientIdentifier,
             DHCP_OPTION_RAPID_COMMIT => DhcpOptionID::RapidCommit,
             DHCP_OPTION_DOMAIN_SEARCH => DhcpOptionID::DomainSearch,
+            DHCP_OPTION_SIP_SERVERS => DhcpOptionID::SipServers,
             DHCP_OPTION_FORCE_RENEW_NONCE_CAP => DhcpOptionID::ForceRenewNonceCap,
+            DHCP_OPTION_CLIENT_LAST_TRANSACTION_TIME => DhcpOptionID::ClientLastTransactionTime,
+            DHCP_OPTION_ASSOCIATED_IP => DhcpOptionID::AssociatedIp,
+            DHCP_OPTION_STATUS_CODE => DhcpOptionID::StatusCode,
+            DHCP_OPTION_BASE_TIME => DhcpOptionID::BaseTime,
+            DHCP_OPTION_START_TIME_OF_STATE => DhcpOptionID::StartTimeOfState,
+            DHCP_OPTION_QUERY_START_TIME => DhcpOptionID::QueryStartTime,
+            DHCP_OPTION_QUERY_END_TIME => DhcpOptionID::QueryEndTime,
+            DHCP_OPTION_DHCP_STATE => DhcpOptionID::DhcpState,
+            DHCP_OPTION_DATA_SOURCE => DhcpOptionID::DataSource,
             DHCP_OPTION_END => DhcpOptionID::OptionEnd,
             0 => DhcpOptionID::Pad,
             o => DhcpOptionID::Other(o),
         }
     }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            DhcpOptionID::SubnetMask => DHCP_OPTION_SUBNETMASK,
+            DhcpOptionID::Router => DHCP_OPTION_ROUTER,
+            DhcpOptionID::DNSserver => DHCP_OPTION_DNSSERVER,
+            DhcpOptionID::HostName => DHCP_OPTION_HOSTNAME,
+            DhcpOptionID::DomainName => DHCP_OPTION_DOMAINNAME,
+            DhcpOptionID::InterfaceMTU => DHCP_OPTION_INTERFACEMTU,
+            DhcpOptionID::BroadcastAddr => DHCP_OPTION_BROADCAST_ADDR,
+            DhcpOptionID::RequestedIpAddr => DHCP_OPTION_REQUESTED_IP,
+            DhcpOptionID::LeaseTime => DHCP_OPTION_LEASETIME,
+            DhcpOptionID::MsgType => DHCP_OPTION_MSGTYPE,
+            DhcpOptionID::ServerID => DHCP_OPTION_SERVERID,
+            DhcpOptionID::ParameterRequestList => DHCP_OPTION_PARAM_REQUEST_LIST,
+            DhcpOptionID::Message => DHCP_OPTION_MESSAGE,
+            DhcpOptionID::MaxMsgSize => DHCP_OPTION_MAX_MSG_SIZE,
+            DhcpOptionID::RenewalInterval => DHCP_OPTION_RENEWAL_INTERVAL,
+            DhcpOptionID::RebindingInterval => DHCP_OPTION_REBINDING_INTERVAL,
+            DhcpOptionID::VendorClassId => DHCP_OPTION_VENDOR_CLASS_ID,
+            DhcpOptionID::ClientIdentifier => DHCP_OPTION_CLIENT_IDENTIFIER,
+            DhcpOptionID::RapidCommit => DHCP_OPTION_RAPID_COMMIT,
+            DhcpOptionID::DomainSearch => DHCP_OPTION_DOMAIN_SEARCH,
+            DhcpOptionID::SipServers => DHCP_OPTION_SIP_SERVERS,
+            DhcpOptionID::ForceRenewNonceCap => DHCP_OPTION_FORCE_RENEW_NONCE_CAP,
+            DhcpOptionID::ClientLastTransactionTime => DHCP_OPTION_CLIENT_LAST_TRANSACTION_TIME,
+            DhcpOptionID::AssociatedIp => DHCP_OPTION_ASSOCIATED_IP,
+            DhcpOptionID::StatusCode => DHCP_OPTION_STATUS_CODE,
+            DhcpOptionID::BaseTime => DHCP_OPTION_BASE_TIME,
+            DhcpOptionID::StartTimeOfState => DHCP_OPTION_START_TIME_OF_STATE,
+            DhcpOptionID::QueryStartTime => DHCP_OPTION_QUERY_START_TIME,
+            DhcpOptionID::QueryEndTime => DHCP_OPTION_QUERY_END_TIME,
+            DhcpOptionID::DhcpState => DHCP_OPTION_DHCP_STATE,
+            DhcpOptionID::DataSource => DHCP_OPTION_DATA_SOURCE,
+            DhcpOptionID::OptionEnd => DHCP_OPTION_END,
+            DhcpOptionID::Pad => 0,
+            DhcpOptionID::Other(o) => *o,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpOptionIDs(Vec<DhcpOptionID>);
 
-impl std::convert::From<&Vec<DhcpOptionID>> for DhcpOptionIDs {
+impl From<&Vec<DhcpOptionID>> for DhcpOptionIDs {
     fn from(v: &Vec<DhcpOptionID>) -> Self {
         Self(v.to_vec())
     }
 }
 
-impl std::convert::From<&DhcpOptionIDs> for Vec<DhcpOptionID> {
+impl From<&DhcpOptionIDs> for Vec<DhcpOptionID> {
     fn from(v: &DhcpOptionIDs) -> Vec<DhcpOptionID> {
         v.0.to_vec()
     }
@@ -239,27 +418,21 @@ impl std::convert::From<&DhcpOptionIDs> for Vec<DhcpOptionID> {
 impl DhcpOptionIDs {
     fn parse(buf: Input) -> Result<Self> {
         map(length_data(be_u8),
-            |x| DhcpOptionIDs(x.into_iter()
+            |x| DhcpOptionIDs(x.iter()
                 .map(|y| DhcpOptionID::from(*y)).collect()),
         )(buf)
     }
 }
 
-fn display_vec_spaces<T>(w: &mut fmt::Formatter, vec: &Vec<T>) -> fmt::Result
-    where T: fmt::Display {
-    let output: String = vec.iter().map(|x| {
-        fmt::format(format_args!("{} ", x))
-    }).collect();
-    write!(w, "{}", output.trim_end())
-}
-
 impl fmt::Display for DhcpOptionIDs {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        display_vec_spaces(w, &self.into())
+        let output: String = self.0.iter().
+            map(|x| format(format_args!("{} ({}), ", x, x.code()))).collect();
+        write!(w, "{}", output.trim_end_matches(", "))
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpOptionOther {
     pub option: DhcpBytes,
     pub option_id: u8,
@@ -270,18 +443,280 @@ impl fmt::Display for DhcpOptionOther {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DhcpMalformedReason {
+    #[strum(to_string = "length exceeds remaining buffer")]
+    Truncated,
+    #[strum(to_string = "invalid length or value")]
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpOptionMalformed {
+    pub code: u8,
+    pub bytes: DhcpBytes,
+    pub reason: DhcpMalformedReason,
+}
+
+impl DhcpOptionMalformed {
+    // Takes the option as a raw code/length/value triple, keeping whatever
+    // is left of the buffer when the length is too large.
+    fn parse(code: u8, buf: Input) -> Result<Self> {
+        let (length, rest) = match buf.split_first() {
+            Some((length, rest)) => (*length as usize, rest),
+            None => return Ok((buf, DhcpOptionMalformed {
+                code, bytes: Vec::new().into(), reason: DhcpMalformedReason::Truncated })),
+        };
+        if length > rest.len() {
+            Ok((&rest[rest.len()..], DhcpOptionMalformed {
+                code, bytes: rest.to_vec().into(), reason: DhcpMalformedReason::Truncated }))
+        } else {
+            Ok((&rest[length..], DhcpOptionMalformed {
+                code, bytes: rest[..length].to_vec().into(), reason: DhcpMalformedReason::Invalid }))
+        }
+    }
+}
+
+impl fmt::Display for DhcpOptionMalformed {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "malformed, {}: {}", self.reason, self.bytes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ipv4AddrList(Vec<Ipv4Addr>);
 
-impl Ipv4AddrList {
+impl From<Vec<Ipv4Addr>> for Ipv4AddrList {
+    fn from(v: Vec<Ipv4Addr>) -> Self {
+        Self(v)
+    }
+}
+
+impl From<&Ipv4AddrList> for Vec<Ipv4Addr> {
+    fn from(v: &Ipv4AddrList) -> Self {
+        v.0.clone()
+    }
+}
+
+impl fmt::Display for Ipv4AddrList {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         let output: String = self.0.iter().
-            map(|x| fmt::format(format_args!("{}, ", x))).collect();
-        write!(w, "{}", output.trim_end_matches(" ,"))
+            map(|x| format(format_args!("{}, ", x))).collect();
+        write!(w, "{}", output.trim_end_matches(", "))
+    }
+}
+
+const DHCP_SIP_SERVERS_DOMAIN_NAMES: u8 = 0;
+const DHCP_SIP_SERVERS_ADDRESSES: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpSipServers {
+    DomainNames(Vec<String>),
+    Addresses(Ipv4AddrList),
+}
+
+impl DhcpSipServers {
+    fn parse(buf: Input) -> Result<Self> {
+        map_parser(length_data(verify_option_length(|x| x > 1)),
+                   all_consuming(alt((
+                       preceded(tag([DHCP_SIP_SERVERS_DOMAIN_NAMES]),
+                                map(parse_domain_name_list, DhcpSipServers::DomainNames)),
+                       preceded(tag([DHCP_SIP_SERVERS_ADDRESSES]),
+                                map(verify(parse_ipv4_list, |x: &Vec<_>| !x.is_empty()),
+                                    |x| DhcpSipServers::Addresses(Ipv4AddrList(x)))),
+                   ))))(buf)
+    }
+
+    fn check(&self) -> core::result::Result<(), DhcpEncodeError> {
+        match self {
+            Self::DomainNames(names) =>
+                names.iter().try_for_each(|x| check_domain_name(DHCP_OPTION_SIP_SERVERS, x)),
+            Self::Addresses(_) => Ok(()),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::DomainNames(names) => {
+                buf.push(DHCP_SIP_SERVERS_DOMAIN_NAMES);
+                names.iter().for_each(|x| encode_domain_name(buf, x));
+            }
+            Self::Addresses(addrs) => {
+                buf.push(DHCP_SIP_SERVERS_ADDRESSES);
+                encode_ipv4_list(buf, &addrs.0);
+            }
+        }
+    }
+}
+
+impl fmt::Display for DhcpSipServers {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DomainNames(names) => write!(w, "{}", names.join(", ")),
+            Self::Addresses(addrs) => addrs.fmt(w),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Display)]
+pub enum DhcpStatus {
+    Success,
+    #[strum(to_string = "Unspecified Failure")]
+    UnspecFail,
+    #[strum(to_string = "Query Terminated")]
+    QueryTerminated,
+    #[strum(to_string = "Malformed Query")]
+    MalformedQuery,
+    #[strum(to_string = "Not Allowed")]
+    NotAllowed,
+    #[strum(to_string = "Data Missing")]
+    DataMissing,
+    #[strum(to_string = "Connection Active")]
+    ConnectionActive,
+    #[strum(to_string = "Catch-up Complete")]
+    CatchUpComplete,
+    #[strum(to_string = "TLS Connection Refused")]
+    TlsConnectionRefused,
+    #[strum(to_string = "Unknown Status")]
+    Other(u8),
+}
+
+impl DhcpStatus {
+    fn parse(byte: u8) -> Self {
+        match byte {
+            0 => Self::Success,
+            1 => Self::UnspecFail,
+            2 => Self::QueryTerminated,
+            3 => Self::MalformedQuery,
+            4 => Self::NotAllowed,
+            5 => Self::DataMissing,
+            6 => Self::ConnectionActive,
+            7 => Self::CatchUpComplete,
+            8 => Self::TlsConnectionRefused,
+            x => Self::Other(x),
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::UnspecFail => 1,
+            Self::QueryTerminated => 2,
+            Self::MalformedQuery => 3,
+            Self::NotAllowed => 4,
+            Self::DataMissing => 5,
+            Self::ConnectionActive => 6,
+            Self::CatchUpComplete => 7,
+            Self::TlsConnectionRefused => 8,
+            Self::Other(x) => *x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpStatusCode {
+    pub status: DhcpStatus,
+    pub message: String,
+}
+
+impl DhcpStatusCode {
+    fn parse(buf: Input) -> Result<Self> {
+        map_parser(length_data(verify_option_length(|x| x > 0)),
+                   map(tuple((be_u8, rest)), |(status, message): (u8, &[u8])|
+                       DhcpStatusCode {
+                           status: DhcpStatus::parse(status),
+                           message: String::from_utf8_lossy(message).into_owned(),
+                       }))(buf)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.status.code());
+        buf.extend_from_slice(self.message.as_bytes());
+    }
+}
+
+impl fmt::Display for DhcpStatusCode {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(w, "{}", self.status)
+        } else {
+            write!(w, "{}: {}", self.status, self.message)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Display)]
+pub enum DhcpLeaseState {
+    Available,
+    Active,
+    Expired,
+    Released,
+    Abandoned,
+    Reset,
+    Remote,
+    Transitioning,
+    #[strum(to_string = "Unknown State")]
+    Other(u8),
+}
+
+impl DhcpLeaseState {
+    fn parse(byte: u8) -> Self {
+        match byte {
+            1 => Self::Available,
+            2 => Self::Active,
+            3 => Self::Expired,
+            4 => Self::Released,
+            5 => Self::Abandoned,
+            6 => Self::Reset,
+            7 => Self::Remote,
+            8 => Self::Transitioning,
+            x => Self::Other(x),
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Available => 1,
+            Self::Active => 2,
+            Self::Expired => 3,
+            Self::Released => 4,
+            Self::Abandoned => 5,
+            Self::Reset => 6,
+            Self::Remote => 7,
+            Self::Transitioning => 8,
+            Self::Other(x) => *x,
+        }
+    }
+}
+
+const DHCP_DATA_SOURCE_REMOTE: u8 = 0x01;
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub struct DhcpDataSource(pub u8);
+
+impl DhcpDataSource {
+    pub fn remote(&self) -> bool {
+        self.0 & DHCP_DATA_SOURCE_REMOTE != 0
+    }
+}
+
+impl fmt::Display for DhcpDataSource {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        if self.remote() {
+            write!(w, "Remote ({:#04x})", self.0)
+        } else {
+            write!(w, "Local ({:#04x})", self.0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DhcpOption {
     MessageType(DhcpMessageType),
     ClientIdentifier(DhcpClientIdentifier),
@@ -291,32 +726,51 @@ pub enum DhcpOption {
     HostName(String),
     ForceRenewNonceCapable(DhcpForceRenewNonceCapable),
     ParameterRequestList(DhcpOptionIDs),
+    Message(String),
     SubNetMask(u32),
     Router(Ipv4AddrList),
     DNSserver(Ipv4AddrList),
     DomainName(String),
     InterfaceMTU(u16),
     BroadcastAddr(Ipv4Addr),
+    RequestedIpAddr(Ipv4Addr),
     LeaseTime(DhcpDuration),
     Other(DhcpOptionOther),
+    Malformed(DhcpOptionMalformed),
     ServerID(Ipv4Addr),
     RenewalPeriod(DhcpDuration),
     RebindingPeriod(DhcpDuration),
     DomainSearch(DhcpBytes),
+    SipServers(DhcpSipServers),
+    ClientLastTransactionTime(DhcpDuration),
+    AssociatedIp(Ipv4AddrList),
+    StatusCode(DhcpStatusCode),
+    BaseTime(u32),
+    StartTimeOfState(DhcpDuration),
+    QueryStartTime(u32),
+    QueryEndTime(u32),
+    DhcpState(DhcpLeaseState),
+    DataSource(DhcpDataSource),
     Pad,
     End,
 }
 
 impl fmt::Display for DhcpDuration {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        write!(w, "{}.{}", self.0.as_secs(), self.0.as_nanos())
+        let nanos = self.0.subsec_nanos();
+        if nanos == 0 {
+            write!(w, "{}s", self.0.as_secs())
+        } else {
+            let fraction = format(format_args!("{:09}", nanos));
+            write!(w, "{}.{}s", self.0.as_secs(), fraction.trim_end_matches('0'))
+        }
     }
 }
 
 impl fmt::Display for DhcpBytes {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         let output: String = self.0.iter().map(|x| {
-            fmt::format(format_args!("{:02x} ", x))
+            format(format_args!("{:02x} ", x))
         }).collect();
         write!(w, "{}", output.trim_end())
     }
@@ -327,17 +781,24 @@ impl fmt::Display for DhcpOption {
         match self {
             Self::MessageType(t) => t.fmt(w),
             Self::ClientIdentifier(b) | Self::DomainSearch(b) => b.fmt(w),
-            Self::RapidCommit => write!(w, "Rapid Commit"),
+            Self::RapidCommit => write!(w, ""),
             Self::MaxMsgSize(t) => t.fmt(w),
-            Self::HostName(t) | Self::VendorClassId(t) | Self::DomainName(t) => t.fmt(w),
-            Self::BroadcastAddr(t) | Self::ServerID(t) => t.fmt(w),
-            Self::LeaseTime(t) | Self::RenewalPeriod(t) | Self::RebindingPeriod(t) => t.fmt(w),
-            Self::SubNetMask(m) => write!(w, "{:#08x}", m),
+            Self::HostName(t) | Self::VendorClassId(t) | Self::DomainName(t) | Self::Message(t) => t.fmt(w),
+            Self::BroadcastAddr(t) | Self::ServerID(t) | Self::RequestedIpAddr(t) => t.fmt(w),
+            Self::LeaseTime(t) | Self::RenewalPeriod(t) | Self::RebindingPeriod(t) |
+            Self::ClientLastTransactionTime(t) | Self::StartTimeOfState(t) => t.fmt(w),
+            Self::BaseTime(t) | Self::QueryStartTime(t) | Self::QueryEndTime(t) => t.fmt(w),
+            Self::SubNetMask(m) => Ipv4Addr::from(*m).fmt(w),
             Self::InterfaceMTU(m) => m.fmt(w),
-            Self::Router(l) | Self::DNSserver(l) => l.fmt(w),
+            Self::Router(l) | Self::DNSserver(l) | Self::AssociatedIp(l) => l.fmt(w),
             Self::ForceRenewNonceCapable(n) => n.fmt(w),
+            Self::SipServers(s) => s.fmt(w),
+            Self::StatusCode(s) => s.fmt(w),
+            Self::DhcpState(s) => s.fmt(w),
+            Self::DataSource(s) => s.fmt(w),
             Self::Pad | Self::End => write!(w,""),
             Self::Other(o) => write!(w,"{}", o),
+            Self::Malformed(m) => write!(w,"{}", m),
             Self::ParameterRequestList(p) => write!(w,"{}", p),
         }
     }
@@ -352,15 +813,39 @@ fn parse_ipv4_option_list(buf: Input) -> Result<Vec<Ipv4Addr>>
 fn parse_ipv4_list(buf: Input) -> Result<Vec<Ipv4Addr>>
 {
     fold_many0(parse_ipv4, Vec::new(), |mut addrs: Vec<_>, addr| {
-        addrs.push(addr.unwrap());
+        addrs.push(addr.unwrap_or(Ipv4Addr::UNSPECIFIED));
         addrs
     })(buf)
 }
 
+fn parse_domain_label<'a>(buf: Input<'a>) -> Result<'a, &'a [u8]>
+{
+    length_data(verify(be_u8, |x| *x > 0 && *x <= 63))(buf)
+}
+
+// RFC 1035 section 3.1 name without compression, limited to 255 octets on the wire.
+fn parse_domain_name(buf: Input) -> Result<String>
+{
+    map_opt(terminated(many1(parse_domain_label), tag([0])), |labels: Vec<&[u8]>| {
+        let wire_length = labels.iter().map(|x| x.len() + 1).sum::<usize>() + 1;
+        if wire_length > 255 {
+            return None;
+        }
+        labels.iter().map(|x| core::str::from_utf8(x).ok())
+            .collect::<Option<Vec<_>>>()
+            .map(|x| x.join("."))
+    })(buf)
+}
+
+fn parse_domain_name_list(buf: Input) -> Result<Vec<String>>
+{
+    many1(parse_domain_name)(buf)
+}
+
 fn parse_string(buf: Input) -> Result<String>
 {
-    map(length_data(verify_option_length(|x| x > 0)),
-        |x| String::from_utf8(x.to_vec()).unwrap())(buf)
+    map_res(length_data(verify_option_length(|x| x > 0)),
+        |x| String::from_utf8(x.to_vec()))(buf)
 }
 
 fn verify_option_length<'a>(function: fn(u8) -> bool) -> impl Fn(&'a [u8]) -> Result<u8>
@@ -369,7 +854,7 @@ fn verify_option_length<'a>(function: fn(u8) -> bool) -> impl Fn(&'a [u8]) -> Re
 }
 
 impl DhcpOption {
-    fn parse<'a, 'b>(option_id: &'b DhcpOptionID, buf: Input<'a>) -> Result<'a, Self> {
+    fn parse<'a>(option_id: &DhcpOptionID, buf: Input<'a>) -> Result<'a, Self> {
         match option_id {
             DhcpOptionID::Router =>
                 map(parse_ipv4_option_list,
@@ -378,30 +863,35 @@ impl DhcpOption {
                 map(parse_ipv4_option_list,
                     |x| DhcpOption::DNSserver(Ipv4AddrList(x)))(buf),
             DhcpOptionID::HostName =>
-                map(parse_string, |x| DhcpOption::HostName(x))(buf),
+                map(parse_string, DhcpOption::HostName)(buf),
             DhcpOptionID::DomainName =>
-                map(parse_string, |x| DhcpOption::DomainName(x))(buf),
+                map(parse_string, DhcpOption::DomainName)(buf),
             DhcpOptionID::InterfaceMTU =>
                 map(preceded(verify_option_length(|x| x == 2), be_u16),
-                    |x| DhcpOption::InterfaceMTU(x))(buf),
+                    DhcpOption::InterfaceMTU)(buf),
             DhcpOptionID::BroadcastAddr =>
-                map(preceded(verify_option_length(|x| x == 4), parse_ipv4),
-                    |x| DhcpOption::BroadcastAddr(x.unwrap()))(buf),
+                map_opt(preceded(verify_option_length(|x| x == 4), parse_ipv4),
+                    |x| x.map(DhcpOption::BroadcastAddr))(buf),
+            DhcpOptionID::RequestedIpAddr =>
+                map_opt(preceded(verify_option_length(|x| x == 4), parse_ipv4),
+                    |x| x.map(DhcpOption::RequestedIpAddr))(buf),
             DhcpOptionID::LeaseTime =>
                 map(preceded(verify_option_length(|x| x == 4), be_u32),
                     |x| DhcpOption::LeaseTime(DhcpDuration::new(x.into(), 0)))(buf),
             DhcpOptionID::MsgType =>
-                map(DhcpMessageType::parse, |x| DhcpOption::MessageType(x))(buf),
+                map(DhcpMessageType::parse, DhcpOption::MessageType)(buf),
             DhcpOptionID::OptionEnd =>
                 Ok((buf, DhcpOption::End)),
             DhcpOptionID::ServerID =>
-                map(preceded(verify_option_length(|x| x == 4), parse_ipv4),
-                    |x| DhcpOption::ServerID(x.unwrap()))(buf),
+                map_opt(preceded(verify_option_length(|x| x == 4), parse_ipv4),
+                    |x| x.map(DhcpOption::ServerID))(buf),
             DhcpOptionID::ParameterRequestList =>
-                map(DhcpOptionIDs::parse, |x| DhcpOption::ParameterRequestList(x))(buf),
+                map(DhcpOptionIDs::parse, DhcpOption::ParameterRequestList)(buf),
+            DhcpOptionID::Message =>
+                map(parse_string, DhcpOption::Message)(buf),
             DhcpOptionID::SubnetMask =>
                 map(preceded(verify_option_length(|x| x == 4), be_u32),
-                    |x| DhcpOption::SubNetMask(x))(buf),
+                    DhcpOption::SubNetMask)(buf),
             DhcpOptionID::MaxMsgSize =>
                 map(preceded(verify_option_length(|x| x == 2),
                              verify(be_u16, |x| *x >= 576)),
@@ -413,15 +903,40 @@ impl DhcpOption {
                 map(preceded(verify_option_length(|x| x == 4), be_u32),
                     |x| DhcpOption::RebindingPeriod(DhcpDuration::new(x.into(), 0)))(buf),
             DhcpOptionID::VendorClassId =>
-                map(parse_string, |x| DhcpOption::VendorClassId(x))(buf),
+                map(parse_string, DhcpOption::VendorClassId)(buf),
             DhcpOptionID::ClientIdentifier =>
-                map(DhcpClientIdentifier::parse, |x| DhcpOption::ClientIdentifier(x))(buf),
+                map(DhcpClientIdentifier::parse, DhcpOption::ClientIdentifier)(buf),
             DhcpOptionID::RapidCommit =>
                 value(DhcpOption::RapidCommit, verify_option_length(|x| x == 0))(buf),
             DhcpOptionID::ForceRenewNonceCap =>
-                map(DhcpForceRenewNonceCapable::parse, |x| DhcpOption::ForceRenewNonceCapable(x))(buf),
+                map(DhcpForceRenewNonceCapable::parse, DhcpOption::ForceRenewNonceCapable)(buf),
             DhcpOptionID::DomainSearch =>
                 map(length_data(be_u8), |x| DhcpOption::DomainSearch(x.to_vec().into()))(buf),
+            DhcpOptionID::SipServers =>
+                map(DhcpSipServers::parse, DhcpOption::SipServers)(buf),
+            DhcpOptionID::ClientLastTransactionTime =>
+                map(preceded(verify_option_length(|x| x == 4), be_u32),
+                    |x| DhcpOption::ClientLastTransactionTime(DhcpDuration::new(x.into(), 0)))(buf),
+            DhcpOptionID::AssociatedIp =>
+                map(parse_ipv4_option_list,
+                    |x| DhcpOption::AssociatedIp(Ipv4AddrList(x)))(buf),
+            DhcpOptionID::StatusCode =>
+                map(DhcpStatusCode::parse, DhcpOption::StatusCode)(buf),
+            DhcpOptionID::BaseTime =>
+                map(preceded(verify_option_length(|x| x == 4), be_u32), DhcpOption::BaseTime)(buf),
+            DhcpOptionID::StartTimeOfState =>
+                map(preceded(verify_option_length(|x| x == 4), be_u32),
+                    |x| DhcpOption::StartTimeOfState(DhcpDuration::new(x.into(), 0)))(buf),
+            DhcpOptionID::QueryStartTime =>
+                map(preceded(verify_option_length(|x| x == 4), be_u32), DhcpOption::QueryStartTime)(buf),
+            DhcpOptionID::QueryEndTime =>
+                map(preceded(verify_option_length(|x| x == 4), be_u32), DhcpOption::QueryEndTime)(buf),
+            DhcpOptionID::DhcpState =>
+                map(preceded(verify_option_length(|x| x == 1), be_u8),
+                    |x| DhcpOption::DhcpState(DhcpLeaseState::parse(x)))(buf),
+            DhcpOptionID::DataSource =>
+                map(preceded(verify_option_length(|x| x == 1), be_u8),
+                    |x| DhcpOption::DataSource(DhcpDataSource(x)))(buf),
             DhcpOptionID::Pad =>
                 Ok((buf, DhcpOption::Pad)),
             DhcpOptionID::Other(o) =>
@@ -430,7 +945,213 @@ impl DhcpOption {
     }
 }
 
-#[derive(Debug, Clone)]
+fn encode_option<F>(buf: &mut Vec<u8>, option_id: u8, value: F) -> core::result::Result<(), DhcpEncodeError>
+    where F: FnOnce(&mut Vec<u8>) {
+    let start = buf.len();
+    buf.push(option_id);
+    buf.push(0);
+    value(buf);
+    let length = buf.len() - start - 2;
+    if length > 255 {
+        buf.truncate(start);
+        return Err(DhcpEncodeError::OptionTooLong { code: option_id, length });
+    }
+    buf[start + 1] = length as u8;
+    Ok(())
+}
+
+fn encode_ipv4(buf: &mut Vec<u8>, addr: &Ipv4Addr) {
+    buf.extend_from_slice(&addr.octets());
+}
+
+fn encode_ipv4_list(buf: &mut Vec<u8>, addrs: &[Ipv4Addr]) {
+    addrs.iter().for_each(|x| encode_ipv4(buf, x));
+}
+
+// Labels of at most 63 bytes and names of at most 255 once encoded, RFC 1035
+// section 2.3.4.
+fn check_domain_name(code: u8, name: &str) -> core::result::Result<(), DhcpEncodeError> {
+    let mut length = 1;
+    for label in name.split('.').filter(|x| !x.is_empty()) {
+        if label.len() > 63 {
+            return Err(DhcpEncodeError::LabelTooLong { code, length: label.len() });
+        }
+        length += label.len() + 1;
+    }
+    match length {
+        length if length > 255 => Err(DhcpEncodeError::NameTooLong { code, length }),
+        _ => Ok(()),
+    }
+}
+
+fn encode_domain_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|x| !x.is_empty()) {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+}
+
+impl DhcpOption {
+    pub fn encode(&self, buf: &mut Vec<u8>) -> core::result::Result<(), DhcpEncodeError> {
+        match self {
+            Self::SubNetMask(m) =>
+                encode_option(buf, DHCP_OPTION_SUBNETMASK, |b| b.extend_from_slice(&m.to_be_bytes())),
+            Self::Router(l) =>
+                encode_option(buf, DHCP_OPTION_ROUTER, |b| encode_ipv4_list(b, &l.0)),
+            Self::DNSserver(l) =>
+                encode_option(buf, DHCP_OPTION_DNSSERVER, |b| encode_ipv4_list(b, &l.0)),
+            Self::HostName(s) =>
+                encode_option(buf, DHCP_OPTION_HOSTNAME, |b| b.extend_from_slice(s.as_bytes())),
+            Self::DomainName(s) =>
+                encode_option(buf, DHCP_OPTION_DOMAINNAME, |b| b.extend_from_slice(s.as_bytes())),
+            Self::InterfaceMTU(m) =>
+                encode_option(buf, DHCP_OPTION_INTERFACEMTU, |b| b.extend_from_slice(&m.to_be_bytes())),
+            Self::BroadcastAddr(a) =>
+                encode_option(buf, DHCP_OPTION_BROADCAST_ADDR, |b| encode_ipv4(b, a)),
+            Self::RequestedIpAddr(a) =>
+                encode_option(buf, DHCP_OPTION_REQUESTED_IP, |b| encode_ipv4(b, a)),
+            Self::LeaseTime(d) =>
+                encode_option(buf, DHCP_OPTION_LEASETIME, |b| d.encode(b)),
+            Self::MessageType(t) =>
+                encode_option(buf, DHCP_OPTION_MSGTYPE, |b| b.push(t.code())),
+            Self::ServerID(a) =>
+                encode_option(buf, DHCP_OPTION_SERVERID, |b| encode_ipv4(b, a)),
+            Self::ParameterRequestList(p) =>
+                encode_option(buf, DHCP_OPTION_PARAM_REQUEST_LIST, |b| b.extend(p.0.iter().map(|x| x.code()))),
+            Self::Message(s) =>
+                encode_option(buf, DHCP_OPTION_MESSAGE, |b| b.extend_from_slice(s.as_bytes())),
+            Self::MaxMsgSize(s) =>
+                encode_option(buf, DHCP_OPTION_MAX_MSG_SIZE, |b| b.extend_from_slice(&(*s as u16).to_be_bytes())),
+            Self::RenewalPeriod(d) =>
+                encode_option(buf, DHCP_OPTION_RENEWAL_INTERVAL, |b| d.encode(b)),
+            Self::RebindingPeriod(d) =>
+                encode_option(buf, DHCP_OPTION_REBINDING_INTERVAL, |b| d.encode(b)),
+            Self::VendorClassId(s) =>
+                encode_option(buf, DHCP_OPTION_VENDOR_CLASS_ID, |b| b.extend_from_slice(s.as_bytes())),
+            Self::ClientIdentifier(c) =>
+                encode_option(buf, DHCP_OPTION_CLIENT_IDENTIFIER, |b| b.extend_from_slice(&c.0)),
+            Self::RapidCommit =>
+                encode_option(buf, DHCP_OPTION_RAPID_COMMIT, |_| ()),
+            Self::ForceRenewNonceCapable(n) =>
+                encode_option(buf, DHCP_OPTION_FORCE_RENEW_NONCE_CAP, |b| b.extend(n.0.iter().map(|x| x.code()))),
+            Self::DomainSearch(d) =>
+                encode_option(buf, DHCP_OPTION_DOMAIN_SEARCH, |b| b.extend_from_slice(&d.0)),
+            Self::SipServers(s) => {
+                s.check()?;
+                encode_option(buf, DHCP_OPTION_SIP_SERVERS, |b| s.encode(b))
+            }
+            Self::ClientLastTransactionTime(d) =>
+                encode_option(buf, DHCP_OPTION_CLIENT_LAST_TRANSACTION_TIME, |b| d.encode(b)),
+            Self::AssociatedIp(l) =>
+                encode_option(buf, DHCP_OPTION_ASSOCIATED_IP, |b| encode_ipv4_list(b, &l.0)),
+            Self::StatusCode(s) =>
+                encode_option(buf, DHCP_OPTION_STATUS_CODE, |b| s.encode(b)),
+            Self::BaseTime(t) =>
+                encode_option(buf, DHCP_OPTION_BASE_TIME, |b| b.extend_from_slice(&t.to_be_bytes())),
+            Self::StartTimeOfState(d) =>
+                encode_option(buf, DHCP_OPTION_START_TIME_OF_STATE, |b| d.encode(b)),
+            Self::QueryStartTime(t) =>
+                encode_option(buf, DHCP_OPTION_QUERY_START_TIME, |b| b.extend_from_slice(&t.to_be_bytes())),
+            Self::QueryEndTime(t) =>
+                encode_option(buf, DHCP_OPTION_QUERY_END_TIME, |b| b.extend_from_slice(&t.to_be_bytes())),
+            Self::DhcpState(s) =>
+                encode_option(buf, DHCP_OPTION_DHCP_STATE, |b| b.push(s.code())),
+            Self::DataSource(s) =>
+                encode_option(buf, DHCP_OPTION_DATA_SOURCE, |b| b.push(s.0)),
+            Self::Other(o) =>
+                encode_option(buf, o.option_id, |b| b.extend_from_slice(&o.option.0)),
+            Self::Malformed(m) =>
+                encode_option(buf, m.code, |b| b.extend_from_slice(&m.bytes.0)),
+            Self::Pad => {
+                buf.push(0);
+                Ok(())
+            }
+            Self::End => {
+                buf.push(DHCP_OPTION_END);
+                Ok(())
+            }
+        }
+    }
+
+    // Decodes an option from its code and the bytes following its length.
+    pub fn decode(code: u8, value: &[u8]) -> Option<Self> {
+        if value.len() > 255 {
+            return None;
+        }
+        let mut buf = Vec::with_capacity(value.len() + 2);
+        buf.push(code);
+        buf.push(value.len() as u8);
+        buf.extend_from_slice(value);
+        let option = all_consuming(|x| parse_dhcp_option(x, ParseMode::Strict))(&buf).ok();
+        option.map(|(_, (_, option))| option)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    pub fn new(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8) -> Self {
+        MacAddr([a, b, c, d, e, f])
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let m = &self.0;
+        write!(w, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+}
+
+#[cfg(feature = "pnet")]
+impl From<datalink::MacAddr> for MacAddr {
+    fn from(m: datalink::MacAddr) -> Self {
+        MacAddr::new(m.0, m.1, m.2, m.3, m.4, m.5)
+    }
+}
+
+#[cfg(feature = "pnet")]
+impl From<MacAddr> for datalink::MacAddr {
+    fn from(m: MacAddr) -> Self {
+        let m = m.0;
+        datalink::MacAddr::new(m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HardwareType(pub u8);
+
+impl HardwareType {
+    pub const ETHERNET: HardwareType = HardwareType(1);
+}
+
+impl fmt::Display for HardwareType {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::ETHERNET => write!(w, "Ethernet"),
+            HardwareType(t) => write!(w, "Unknown ({})", t),
+        }
+    }
+}
+
+#[cfg(feature = "pnet")]
+impl From<HardwareType> for arp::ArpHardwareType {
+    fn from(t: HardwareType) -> Self {
+        arp::ArpHardwareType::new(t.0.into())
+    }
+}
+
+#[cfg(feature = "std")]
+pub type DhcpOptions = HashMap<DhcpOptionID, DhcpOption>;
+#[cfg(not(feature = "std"))]
+pub type DhcpOptions = BTreeMap<DhcpOptionID, DhcpOption>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DhcpPacket {
     pub ciaddr: Option<Ipv4Addr>,
     pub yiaddr: Option<Ipv4Addr>,
@@ -439,93 +1160,149 @@ pub struct DhcpPacket {
     pub opcode: BootpOpcode,
     pub hops: usize,
     pub hlen: usize,
-    pub htype: arp::ArpHardwareType,
+    pub htype: HardwareType,
     pub xid: u32,
     pub secs: DhcpDuration,
     pub broadcast: bool,
-    pub chaddr: datalink::MacAddr,
-    pub options: HashMap<DhcpOptionID, DhcpOption>,
+    pub chaddr: MacAddr,
+    pub sname: String,
+    pub file: String,
+    pub options: DhcpOptions,
 }
 
 impl BootpOpcode {
-    fn parse(buf: Input) -> Result<Self> {
-        let opcode = be_u8(buf);
-        match opcode {
-            Err(e) => Result::Err(e),
-            Ok(number) => match number {
-                (buf, 1) => Result::Ok((buf, BootpOpcode::BootRequest)),
-                (buf, 2) => Result::Ok((buf, BootpOpcode::BootReply)),
-                (buf, o) => panic!("Unknown opcode {}", o),
-            }
+    pub(crate) fn parse(buf: Input) -> Result<Self> {
+        map_opt(be_u8, |x| match x {
+            1 => Some(BootpOpcode::BootRequest),
+            2 => Some(BootpOpcode::BootReply),
+            _ => None,
+        })(buf)
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            BootpOpcode::BootRequest => 1,
+            BootpOpcode::BootReply => 2,
         }
     }
 }
 
-fn parse_dhcp_hwarp(buf: Input) -> Result<arp::ArpHardwareType> {
-    value(arp::ArpHardwareTypes::Ethernet, tag([1]))(buf)
+pub(crate) fn parse_dhcp_hwarp(buf: Input) -> Result<HardwareType> {
+    value(HardwareType::ETHERNET, tag([1]))(buf)
 }
 
-fn parse_flags(buf: Input) -> Result<bool> {
-    map(be_u16, |x| match x {
-        0x8000 => true,
-        0x0000 => false,
-        f => panic!("unknown flags {:x?}", f)
+pub(crate) fn parse_flags(buf: Input) -> Result<bool> {
+    map_opt(be_u16, |x| match x {
+        0x8000 => Some(true),
+        0x0000 => Some(false),
+        _ => None,
     })(buf)
 }
 
-fn take4(buf: Input) -> Result<&ByteStr>
-{
-    map(take(4_usize), |x| ByteStr::new(x))(buf)
-}
 
-fn parse_ipv4(buf: Input) -> Result<Option<Ipv4Addr>>
+pub(crate) fn parse_ipv4(buf: Input) -> Result<Option<Ipv4Addr>>
 {
-    map(take4, |x| if x == ByteStr::new(&[0_u8, 0_u8, 0_u8, 0_u8]) {
+    map(take(4_usize), |x: &[u8]| if x == [0_u8, 0_u8, 0_u8, 0_u8] {
         None
     } else {
         Some(Ipv4Addr::new(x[0], x[1], x[2], x[3]))
     })(buf)
 }
 
-fn new_macaddr(buf: &[u8]) -> datalink::MacAddr
+// sname and file are NUL terminated when shorter than their field.
+pub(crate) fn parse_cstring(buf: &[u8]) -> String
 {
-    datalink::MacAddr::new(buf[0], buf[1], buf[2], buf[3], buf[4], buf[5])
+    let end = buf.iter().position(|x| *x == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
 }
 
-fn parse_chaddr(buf: Input) -> Result<datalink::MacAddr>
+pub(crate) fn new_macaddr(buf: &[u8]) -> MacAddr
 {
-    terminated(map(take(6_usize), |x| new_macaddr(x)), take(10_usize))(buf)
+    MacAddr::new(buf[0], buf[1], buf[2], buf[3], buf[4], buf[5])
 }
 
-fn parse_dhcp_option(buf: Input) -> Result<(DhcpOptionID, DhcpOption)>
+fn parse_chaddr(buf: Input) -> Result<MacAddr>
 {
-    let (buf2, option_id) = be_u8(buf)?;
-    let option_id = DhcpOptionID::from(option_id);
-    let (buf2, option) = DhcpOption::parse(&option_id, buf2)?;
-    Ok((buf2, (option_id, option)))
+    terminated(map(take(6_usize), new_macaddr), take(10_usize))(buf)
+}
+
+pub(crate) fn parse_dhcp_option(buf: Input, mode: ParseMode) -> Result<(DhcpOptionID, DhcpOption)>
+{
+    let (buf2, code) = be_u8(buf)?;
+    let option_id = DhcpOptionID::from(code);
+    match (DhcpOption::parse(&option_id, buf2), mode) {
+        (Ok((buf3, option)), _) => Ok((buf3, (option_id, option))),
+        (Err(e), ParseMode::Strict) => Err(match e {
+            nom::Err::Error(e) => nom::Err::Error(DhcpParseError::add_option(buf, code, e)),
+            nom::Err::Failure(e) => nom::Err::Failure(DhcpParseError::add_option(buf, code, e)),
+            nom::Err::Incomplete(_) => nom::Err::Error(DhcpParseError::add_option(buf, code,
+                DhcpParseError::from_error_kind(buf2, ErrorKind::Eof))),
+        }),
+        (Err(_), ParseMode::Lenient) => {
+            let (buf3, option) = DhcpOptionMalformed::parse(code, buf2)?;
+            Ok((buf3, (option_id, DhcpOption::Malformed(option))))
+        }
+    }
 }
 
-fn parse_dhcp_options(buf: Input) -> Result<HashMap<DhcpOptionID, DhcpOption>>
+// Options up to the End option, anything after it is left as remaining input.
+// The options are split like DhcpPacketView does and each one is decoded from
+// the bytes its length covers.
+fn parse_dhcp_options(buf: Input, mode: ParseMode) -> Result<DhcpOptions>
 {
-    fold_many0(parse_dhcp_option, HashMap::new(), |mut options: HashMap<_, _>, option| {
-        match option.0 {
-            DhcpOptionID::OptionEnd => None,
-            DhcpOptionID::Pad => None,
-            option_id => options.insert(option_id, option.1),
+    let mut options = DhcpOptions::new();
+    let mut buf = buf;
+    loop {
+        buf = &buf[buf.iter().take_while(|x| **x == 0).count()..];
+        let (rest, (option_id, option)) = match split_option(buf) {
+            Ok((rest, Some(_))) => (rest, parse_dhcp_option(&buf[..buf.len() - rest.len()], mode)?.1),
+            Ok((rest, None)) => return Ok((rest, options)),
+            // Runs past the end, reported or kept as malformed depending on the mode.
+            Err(_) => parse_dhcp_option(buf, mode)?,
         };
-        options
-    })(buf)
+        options.insert(option_id, option);
+        buf = rest;
+    }
 }
 
 impl DhcpPacket {
     pub fn parse(buf: Input) -> Result<Self> {
-        let dhcp_packet = map(tuple((BootpOpcode::parse, parse_dhcp_hwarp, be_u8, be_u8, be_u32, be_u16,
-                                     parse_flags, parse_ipv4, parse_ipv4, parse_ipv4, parse_ipv4,
-                                     terminated(parse_chaddr, take(192_usize)),
-                                     verify(take(4_usize), |x: &[u8]| x.len() == 4
-                                         && x == [0x63, 0x82, 0x53, 0x63]),
-                                     parse_dhcp_options)),
-                              |(opcode, htype, hlen, hops, xid, sec, broadcast, ciaddr, yiaddr, siaddr, giaddr, chaddr, _, options)|
+        Self::parse_with_mode(buf, ParseMode::Strict)
+    }
+
+    pub fn new(opcode: BootpOpcode, xid: u32, chaddr: MacAddr) -> Self {
+        Self {
+            ciaddr: None,
+            yiaddr: None,
+            siaddr: None,
+            giaddr: None,
+            opcode,
+            hops: 0,
+            hlen: 6,
+            htype: HardwareType::ETHERNET,
+            xid,
+            secs: DhcpDuration::new(0, 0),
+            broadcast: false,
+            chaddr,
+            sname: String::new(),
+            file: String::new(),
+            options: DhcpOptions::new(),
+        }
+    }
+
+    pub fn parse_with_mode(buf: Input, mode: ParseMode) -> Result<Self> {
+        let dhcp_packet = map(tuple((context("op", BootpOpcode::parse), context("htype", parse_dhcp_hwarp),
+                                     context("hlen", be_u8), context("hops", be_u8),
+                                     context("xid", be_u32), context("secs", be_u16),
+                                     context("flags", parse_flags),
+                                     context("ciaddr", parse_ipv4), context("yiaddr", parse_ipv4),
+                                     context("siaddr", parse_ipv4), context("giaddr", parse_ipv4),
+                                     context("chaddr", parse_chaddr),
+                                     context("sname", map(take(64_usize), parse_cstring)),
+                                     context("file", map(take(128_usize), parse_cstring)),
+                                     context("magic cookie", tag(DHCP_MAGIC_COOKIE)),
+                                     context("options", move |x| parse_dhcp_options(x, mode)))),
+                              |(opcode, htype, hlen, hops, xid, sec, broadcast, ciaddr, yiaddr, siaddr, giaddr, chaddr, sname, file, _, options)|
                                   {
                                       Self {
                                           ciaddr,
@@ -540,21 +1317,390 @@ impl DhcpPacket {
                                           secs: DhcpDuration::new(sec.into(), 0),
                                           broadcast,
                                           chaddr,
+                                          sname,
+                                          file,
                                           options,
                                       }
                                   })(buf);
-        dhcp_packet
+        dhcp_packet.map_err(|e| match e {
+            nom::Err::Error(e) => nom::Err::Error(e.locate(buf)),
+            nom::Err::Failure(e) => nom::Err::Failure(e.locate(buf)),
+            e => e,
+        })
+    }
+
+    pub fn message_type(&self) -> Option<&DhcpMessageType> {
+        match self.options.get(&DhcpOptionID::MsgType) {
+            Some(DhcpOption::MessageType(t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    // The Message Type option goes first and the rest follows in code order,
+    // so the same packet always encodes to the same bytes. Short packets are
+    // padded to the 300 bytes BOOTP relays expect (RFC 1542 section 2.1).
+    // Nothing is left in `buf` when a field or option doesn't fit.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> core::result::Result<(), DhcpEncodeError> {
+        let secs = self.secs.as_secs();
+        let secs = u16::try_from(secs).map_err(|_| DhcpEncodeError::SecsTooLarge(secs))?;
+        check_field("sname", self.sname.as_bytes(), 64)?;
+        check_field("file", self.file.as_bytes(), 128)?;
+        let start = buf.len();
+        buf.push(self.opcode.code());
+        buf.push(self.htype.0);
+        buf.push(self.hlen as u8);
+        buf.push(self.hops as u8);
+        buf.extend_from_slice(&self.xid.to_be_bytes());
+        buf.extend_from_slice(&secs.to_be_bytes());
+        buf.extend_from_slice(&(if self.broadcast { 0x8000_u16 } else { 0 }).to_be_bytes());
+        for addr in &[self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            encode_ipv4(buf, &addr.unwrap_or(Ipv4Addr::UNSPECIFIED));
+        }
+        encode_padded(buf, &self.chaddr.0, 16);
+        encode_padded(buf, self.sname.as_bytes(), 64);
+        encode_padded(buf, self.file.as_bytes(), 128);
+        buf.extend_from_slice(&DHCP_MAGIC_COOKIE);
+
+        let mut options: Vec<_> = self.options.iter().collect();
+        options.sort_by_key(|(option_id, _)| (**option_id != DhcpOptionID::MsgType, option_id.code()));
+        for (_, option) in options {
+            if let Err(e) = option.encode(buf) {
+                buf.truncate(start);
+                return Err(e);
+            }
+        }
+        buf.push(DHCP_OPTION_END);
+        while buf.len() - start < BOOTP_MIN_LEN {
+            buf.push(0);
+        }
+        Ok(())
     }
 }
 
+// Fixed size fields can't hold more, encode_padded would cut the value short.
+fn check_field(field: &'static str, value: &[u8], max: usize) -> core::result::Result<(), DhcpEncodeError> {
+    match value.len() {
+        length if length > max => Err(DhcpEncodeError::FieldTooLong { field, length, max }),
+        _ => Ok(()),
+    }
+}
+
+// Copies at most `length` bytes and fills the rest of the field with zeroes.
+fn encode_padded(buf: &mut Vec<u8>, value: &[u8], length: usize) {
+    let value = &value[..value.len().min(length)];
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + length - value.len(), 0);
+}
+
+impl DhcpPacket {
+    fn fmt_summary(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self.message_type() {
+            Some(t) => write!(w, "{}", t.to_string().to_uppercase())?,
+            None => write!(w, "{}", self.opcode)?,
+        }
+        write!(w, " xid={:#010x} chaddr={}", self.xid, self.chaddr)?;
+        if let Some(a) = self.ciaddr {
+            write!(w, " ciaddr={}", a)?;
+        }
+        if let Some(a) = self.yiaddr {
+            write!(w, " yiaddr={}", a)?;
+        }
+        if let Some(a) = self.giaddr {
+            write!(w, " giaddr={}", a)?;
+        }
+        if let Some(o) = self.options.get(&DhcpOptionID::RequestedIpAddr) {
+            write!(w, " requested={}", o)?;
+        }
+        if let Some(o) = self.options.get(&DhcpOptionID::ServerID) {
+            write!(w, " server={}", o)?;
+        }
+        if let Some(o) = self.options.get(&DhcpOptionID::HostName) {
+            write!(w, " host={}", o)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_dissection(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let flags = if self.broadcast { "0x8000 (Broadcast)" } else { "0x0000 (Unicast)" };
+        let unset = Ipv4Addr::UNSPECIFIED;
+        match self.message_type() {
+            Some(t) => writeln!(w, "Dynamic Host Configuration Protocol ({})", t)?,
+            None => writeln!(w, "Bootstrap Protocol")?,
+        }
+        writeln!(w, "    Message type: {}", self.opcode)?;
+        writeln!(w, "    Hardware type: {}", self.htype)?;
+        writeln!(w, "    Hardware address length: {}", self.hlen)?;
+        writeln!(w, "    Hops: {}", self.hops)?;
+        writeln!(w, "    Transaction ID: {:#010x}", self.xid)?;
+        writeln!(w, "    Seconds elapsed: {}", self.secs)?;
+        writeln!(w, "    Bootp flags: {}", flags)?;
+        writeln!(w, "    Client IP address: {}", self.ciaddr.unwrap_or(unset))?;
+        writeln!(w, "    Your (client) IP address: {}", self.yiaddr.unwrap_or(unset))?;
+        writeln!(w, "    Next server IP address: {}", self.siaddr.unwrap_or(unset))?;
+        writeln!(w, "    Relay agent IP address: {}", self.giaddr.unwrap_or(unset))?;
+        writeln!(w, "    Client MAC address: {}", self.chaddr)?;
+        writeln!(w, "    Server host name: {}", if self.sname.is_empty() { "not given" } else { &self.sname })?;
+        writeln!(w, "    Boot file name: {}", if self.file.is_empty() { "not given" } else { &self.file })?;
+
+        let mut options: Vec<_> = self.options.iter().collect();
+        options.sort_by_key(|(option_id, _)| option_id.code());
+        for (option_id, option) in options {
+            write!(w, "    Option: ({}) {}", option_id.code(), option_id)?;
+            match option {
+                DhcpOption::Other(o) => writeln!(w, ": {}", o.option)?,
+                DhcpOption::RapidCommit => writeln!(w)?,
+                o => writeln!(w, ": {}", o)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// The alternate flag ({:#}) selects a one line summary.
 impl fmt::Display for DhcpPacket {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
-        let msg_type = self.options.get(&DhcpOptionID::MsgType).map(|x| x.to_string()).unwrap_or("Message type missing".to_string());
-        let hostname = self.options.get(&DhcpOptionID::HostName).map(|x| x.to_string()).unwrap_or("No hostname".to_string());
-        let subnetmask = self.options.get(&DhcpOptionID::SubnetMask).map(|x| x.to_string()).unwrap_or("No subnet mask".to_string());
-        writeln!(w, "Message Type: {}", msg_type)?;
-        writeln!(w, "Host name: {}", hostname)?;
-        writeln!(w, "Subnet mask: {}", subnetmask)?;
-        writeln!(w, "xid: {:x}", self.xid)
-    }
-}
\ No newline at end of file
+        if w.alternate() {
+            self.fmt_summary(w)
+        } else {
+            self.fmt_dissection(w)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_error::DhcpEncodeError;
+    use alloc::vec;
+
+    fn round_trip(option: DhcpOption) {
+        let mut buf = Vec::new();
+        option.encode(&mut buf).unwrap();
+        assert_eq!(buf[1] as usize, buf.len() - 2);
+        assert_eq!(DhcpOption::decode(buf[0], &buf[2..]), Some(option));
+    }
+
+    #[test]
+    fn message_type_names() {
+        for name in ["forcerenew", "Force Renew", "FORCE_RENEW", "force renew"] {
+            assert_eq!(DhcpMessageType::from_name(name), Some(DhcpMessageType::DhcpForceRenew));
+        }
+        assert_eq!(DhcpMessageType::from_name("DISCOVER"), Some(DhcpMessageType::DhcpDiscover));
+        assert_eq!(DhcpMessageType::from_name("nak"), Some(DhcpMessageType::DhcpNak));
+        assert_eq!(DhcpMessageType::from_name("1"), None);
+        assert_eq!(DhcpMessageType::from_name(""), None);
+    }
+
+    #[test]
+    fn sip_servers_round_trip() {
+        round_trip(DhcpOption::SipServers(DhcpSipServers::DomainNames(
+            vec!["sip.example.com".to_string(), "example.org".to_string()])));
+        round_trip(DhcpOption::SipServers(DhcpSipServers::Addresses(
+            Ipv4AddrList(vec![Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(192, 0, 2, 2)]))));
+    }
+
+    #[test]
+    fn sip_servers_encoding() {
+        let mut buf = Vec::new();
+        DhcpOption::SipServers(DhcpSipServers::DomainNames(vec!["a.bc".to_string()])).encode(&mut buf).unwrap();
+        assert_eq!(buf, [120, 7, 0, 1, b'a', 2, b'b', b'c', 0]);
+    }
+
+    #[test]
+    fn long_label_is_rejected() {
+        let label = "a".repeat(64);
+        let option = DhcpOption::SipServers(DhcpSipServers::DomainNames(vec![format!("{}.example", label)]));
+        let mut buf = vec![1];
+        assert_eq!(option.encode(&mut buf), Err(DhcpEncodeError::LabelTooLong { code: 120, length: 64 }));
+        assert_eq!(buf, [1]);
+        round_trip(DhcpOption::SipServers(DhcpSipServers::DomainNames(vec!["a".repeat(63)])));
+    }
+
+    #[test]
+    fn long_name_is_rejected() {
+        // Four 63 bytes labels encode to 4 * 64 + 1 bytes.
+        let name = vec!["a".repeat(63); 4].join(".");
+        let option = DhcpOption::SipServers(DhcpSipServers::DomainNames(vec![name]));
+        assert_eq!(option.encode(&mut Vec::new()), Err(DhcpEncodeError::NameTooLong { code: 120, length: 257 }));
+    }
+
+    #[test]
+    fn long_option_is_rejected() {
+        let option = |length| DhcpOption::Other(DhcpOptionOther { option_id: 224, option: vec![0; length].into() });
+        let mut buf = vec![1];
+        assert_eq!(option(256).encode(&mut buf), Err(DhcpEncodeError::OptionTooLong { code: 224, length: 256 }));
+        assert_eq!(buf, [1]);
+        round_trip(option(255));
+    }
+
+    #[test]
+    fn leasequery_options_round_trip() {
+        round_trip(DhcpOption::ClientLastTransactionTime(DhcpDuration::new(3600, 0)));
+        round_trip(DhcpOption::AssociatedIp(Ipv4AddrList(vec![Ipv4Addr::new(192, 0, 2, 10)])));
+        round_trip(DhcpOption::StatusCode(DhcpStatusCode { status: DhcpStatus::NotAllowed,
+                                                           message: "no".to_string() }));
+        round_trip(DhcpOption::BaseTime(1_600_000_000));
+        round_trip(DhcpOption::StartTimeOfState(DhcpDuration::new(60, 0)));
+        round_trip(DhcpOption::QueryStartTime(1));
+        round_trip(DhcpOption::QueryEndTime(2));
+        round_trip(DhcpOption::DhcpState(DhcpLeaseState::Active));
+        round_trip(DhcpOption::DataSource(DhcpDataSource(0x01)));
+        assert!(DhcpDataSource(0x01).remote());
+        assert!(!DhcpDataSource(0x00).remote());
+    }
+
+    #[test]
+    fn leasequery_packets() {
+        let mut query = DhcpPacket::new(BootpOpcode::BootRequest, 0x0102_0304, MacAddr::default());
+        query.ciaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        query.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpLeaseQuery));
+        let mut buf = Vec::new();
+        query.encode(&mut buf).unwrap();
+        // The options follow the 236 bytes header and the magic cookie.
+        assert_eq!(buf[240..243], [53, 1, 10]);
+        let (_, parsed) = DhcpPacket::parse(&buf).unwrap();
+        assert_eq!(parsed.message_type(), Some(&DhcpMessageType::DhcpLeaseQuery));
+        assert_eq!(parsed.ciaddr, Some(Ipv4Addr::new(192, 0, 2, 10)));
+
+        let mut active = DhcpPacket::new(BootpOpcode::BootReply, 0x0102_0304, MacAddr::default());
+        active.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpLeaseActive));
+        active.options.insert(DhcpOptionID::ClientLastTransactionTime,
+                              DhcpOption::ClientLastTransactionTime(DhcpDuration::new(120, 0)));
+        active.options.insert(DhcpOptionID::DhcpState, DhcpOption::DhcpState(DhcpLeaseState::Active));
+        active.options.insert(DhcpOptionID::LeaseTime, DhcpOption::LeaseTime(DhcpDuration::new(3600, 0)));
+        buf.clear();
+        active.encode(&mut buf).unwrap();
+        let (_, parsed) = DhcpPacket::parse(&buf).unwrap();
+        assert_eq!(parsed.message_type(), Some(&DhcpMessageType::DhcpLeaseActive));
+        assert_eq!(parsed.options.get(&DhcpOptionID::ClientLastTransactionTime),
+                   Some(&DhcpOption::ClientLastTransactionTime(DhcpDuration::new(120, 0))));
+        assert_eq!(parsed.options.get(&DhcpOptionID::DhcpState), Some(&DhcpOption::DhcpState(DhcpLeaseState::Active)));
+        assert_eq!(parsed, active);
+        assert!(parsed.validate().is_empty());
+    }
+
+    #[test]
+    fn leasequery_message_types() {
+        for code in 10..=15 {
+            let message_type = DhcpMessageType::from_code(code).unwrap();
+            assert_eq!(message_type.code(), code);
+            assert_eq!(DhcpMessageType::from_name(&message_type.to_string()), Some(message_type));
+        }
+        assert_eq!(DhcpMessageType::from_name("LEASEQUERYDONE"), Some(DhcpMessageType::DhcpLeaseQueryDone));
+        assert_eq!(DhcpMessageType::from_code(16), None);
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 0x1234_5678, MacAddr::default());
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("host".to_string()));
+        packet.options.insert(DhcpOptionID::SipServers,
+                              DhcpOption::SipServers(DhcpSipServers::DomainNames(vec!["sip.example".to_string()])));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(buf.len(), BOOTP_MIN_LEN);
+        assert_eq!(DhcpPacket::parse(&buf).unwrap().1, packet);
+    }
+
+    #[test]
+    fn packet_with_a_long_option_is_not_encoded() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("h".repeat(300)));
+        let mut buf = Vec::new();
+        assert_eq!(packet.encode(&mut buf), Err(DhcpEncodeError::OptionTooLong { code: 12, length: 300 }));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn fields_that_dont_fit_are_rejected() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        packet.secs = DhcpDuration::new(65_536, 0);
+        let mut buf = Vec::new();
+        assert_eq!(packet.encode(&mut buf), Err(DhcpEncodeError::SecsTooLarge(65_536)));
+        packet.secs = DhcpDuration::new(65_535, 0);
+        packet.sname = "s".repeat(65);
+        assert_eq!(packet.encode(&mut buf), Err(DhcpEncodeError::FieldTooLong { field: "sname", length: 65, max: 64 }));
+        packet.sname = "s".repeat(64);
+        packet.file = "f".repeat(129);
+        assert_eq!(packet.encode(&mut buf), Err(DhcpEncodeError::FieldTooLong { field: "file", length: 129, max: 128 }));
+        assert!(buf.is_empty());
+
+        packet.file = "f".repeat(128);
+        packet.encode(&mut buf).unwrap();
+        assert_eq!(DhcpPacket::parse(&buf).unwrap().1, packet);
+    }
+
+    fn request() -> DhcpPacket {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 0x1234_5678, MacAddr::new(2, 0, 0, 0, 0, 1));
+        packet.secs = DhcpDuration::new(3, 0);
+        packet.broadcast = true;
+        packet.hops = 1;
+        packet.giaddr = Some(Ipv4Addr::new(192, 0, 2, 254));
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpRequest));
+        packet.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("laptop".to_string()));
+        packet.options.insert(DhcpOptionID::RequestedIpAddr,
+                              DhcpOption::RequestedIpAddr(Ipv4Addr::new(192, 0, 2, 10)));
+        packet.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        packet.options.insert(DhcpOptionID::ParameterRequestList, DhcpOption::decode(55, &[1, 3, 6]).unwrap());
+        packet.options.insert(DhcpOptionID::RapidCommit, DhcpOption::RapidCommit);
+        packet.options.insert(DhcpOptionID::from(224),
+                              DhcpOption::Other(DhcpOptionOther { option_id: 224, option: vec![0xca, 0xfe].into() }));
+        packet
+    }
+
+    #[test]
+    fn dissection() {
+        assert_eq!(request().to_string(), "\
+Dynamic Host Configuration Protocol (Request)
+    Message type: BootRequest
+    Hardware type: Ethernet
+    Hardware address length: 6
+    Hops: 1
+    Transaction ID: 0x12345678
+    Seconds elapsed: 3s
+    Bootp flags: 0x8000 (Broadcast)
+    Client IP address: 0.0.0.0
+    Your (client) IP address: 0.0.0.0
+    Next server IP address: 0.0.0.0
+    Relay agent IP address: 192.0.2.254
+    Client MAC address: 02:00:00:00:00:01
+    Server host name: not given
+    Boot file name: not given
+    Option: (12) Host Name: laptop
+    Option: (50) Requested IP Address: 192.0.2.10
+    Option: (53) Message Type: Request
+    Option: (54) Server ID: 192.0.2.1
+    Option: (55) Parameter Request List: Subnet Mask (1), Router (3), DNS Server (6)
+    Option: (80) Rapid Commit
+    Option: (224) Unknown Parameter: ca fe
+");
+    }
+
+    #[test]
+    fn bootp_dissection() {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootReply, 1, MacAddr::default());
+        packet.yiaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        packet.secs = DhcpDuration::new(1, 500_000_000);
+        packet.sname = "boot.example".to_string();
+        packet.file = "pxelinux.0".to_string();
+        let dissection = packet.to_string();
+        let lines: Vec<_> = dissection.lines().collect();
+        assert_eq!(lines[0], "Bootstrap Protocol");
+        assert_eq!(lines[6], "    Seconds elapsed: 1.5s");
+        assert_eq!(lines[7], "    Bootp flags: 0x0000 (Unicast)");
+        assert_eq!(lines[9], "    Your (client) IP address: 192.0.2.10");
+        assert_eq!(&lines[13..], ["    Server host name: boot.example", "    Boot file name: pxelinux.0"]);
+    }
+
+    #[test]
+    fn summary() {
+        assert_eq!(format!("{:#}", request()), "REQUEST xid=0x12345678 chaddr=02:00:00:00:00:01 giaddr=192.0.2.254 \
+                                               requested=192.0.2.10 server=192.0.2.1 host=laptop");
+        let mut packet = DhcpPacket::new(BootpOpcode::BootReply, 1, MacAddr::default());
+        packet.ciaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        packet.yiaddr = Some(Ipv4Addr::new(192, 0, 2, 11));
+        assert_eq!(format!("{:#}", packet),
+                   "BootReply xid=0x00000001 chaddr=00:00:00:00:00:00 ciaddr=192.0.2.10 yiaddr=192.0.2.11");
+    }
+}
@@ -0,0 +1,706 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_PACKET: u32 = 2;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_OPT_END: u16 = 0;
+const PCAPNG_OPT_COMMENT: u16 = 1;
+const PCAPNG_OPT_IF_NAME: u16 = 2;
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+
+// Guards against allocating whatever a corrupt length field says.
+const MAX_BLOCK_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DhcpPcapError {
+    Io(io::Error),
+    UnknownFormat(u32),
+    Truncated,
+    Malformed(&'static str),
+    UnknownInterface(u32),
+}
+
+impl fmt::Display for DhcpPcapError {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(w, "{}", e),
+            Self::UnknownFormat(magic) => write!(w, "not a pcap or pcapng file, magic {:#010x}", magic),
+            Self::Truncated => write!(w, "file ends in the middle of a record"),
+            Self::Malformed(what) => write!(w, "malformed {}", what),
+            Self::UnknownInterface(id) => write!(w, "packet for undeclared interface {}", id),
+        }
+    }
+}
+
+impl From<io::Error> for DhcpPcapError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpCaptureRecord {
+    // Since the Unix epoch.
+    pub timestamp: Duration,
+    // A LINKTYPE_ value, see DhcpLinkType::from_linktype.
+    pub link_type: u32,
+    // The interface name pcapng files may carry.
+    pub interface: Option<String>,
+    pub comment: Option<String>,
+    // Before the capture cut it at the snapshot length.
+    pub original_len: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct DhcpPcapInterface {
+    link_type: u32,
+    name: Option<String>,
+    // Timestamp units per second as 10^n or, with the top bit set, 2^n.
+    tsresol: u8,
+}
+
+#[derive(Debug, Clone)]
+enum DhcpPcapFormat {
+    Pcap { link_type: u32, nanos: bool },
+    Pcapng { interfaces: Vec<DhcpPcapInterface> },
+}
+
+// Reads the records of a pcap or pcapng file one by one, which format it
+// is comes from the magic number at the start.
+pub struct DhcpPcapReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    format: DhcpPcapFormat,
+    done: bool,
+}
+
+fn ts_to_duration(ts: u64, tsresol: u8) -> Duration {
+    let exponent = u32::from(tsresol & 0x7f);
+    let units_per_sec: u128 = if tsresol & 0x80 == 0 { 10_u128.pow(exponent.min(38)) } else { 1 << exponent.min(127) };
+    let secs = u128::from(ts) / units_per_sec;
+    let nanos = (u128::from(ts) % units_per_sec) * 1_000_000_000 / units_per_sec;
+    Duration::new(secs as u64, nanos as u32)
+}
+
+fn options(buf: &[u8], big_endian: bool) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut buf = buf;
+    std::iter::from_fn(move || {
+        if buf.len() < 4 {
+            return None;
+        }
+        let code = read_u16(&buf[0..], big_endian);
+        let length = usize::from(read_u16(&buf[2..], big_endian));
+        let padded = (length + 3) & !3;
+        if code == PCAPNG_OPT_END || buf.len() < 4 + padded {
+            return None;
+        }
+        let value = &buf[4..4 + length];
+        buf = &buf[4 + padded..];
+        Some((code, value))
+    })
+}
+
+fn read_u16(buf: &[u8], big_endian: bool) -> u16 {
+    let bytes = [buf[0], buf[1]];
+    if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+}
+
+fn read_u32(buf: &[u8], big_endian: bool) -> u32 {
+    let bytes = [buf[0], buf[1], buf[2], buf[3]];
+    if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+}
+
+fn option_string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).trim_end_matches('\0').to_string()
+}
+
+impl<R: Read> DhcpPcapReader<R> {
+    pub fn new(reader: R) -> Result<Self, DhcpPcapError> {
+        let mut reader = reader;
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut pcap = DhcpPcapReader {
+            reader,
+            big_endian: false,
+            format: DhcpPcapFormat::Pcapng { interfaces: Vec::new() },
+            done: false,
+        };
+        if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER {
+            let mut length = [0; 4];
+            pcap.reader.read_exact(&mut length)?;
+            pcap.read_section_header(length)?;
+            return Ok(pcap);
+        }
+        let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (false, true),
+            (_, PCAP_MAGIC_MICROS) => (true, false),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            (_, magic) => return Err(DhcpPcapError::UnknownFormat(magic)),
+        };
+        // Version, time zone, sigfigs, snaplen and the link type.
+        let mut header = [0; 20];
+        pcap.reader.read_exact(&mut header)?;
+        pcap.big_endian = big_endian;
+        pcap.format = DhcpPcapFormat::Pcap { link_type: read_u32(&header[16..], big_endian) & 0x0fff_ffff, nanos };
+        Ok(pcap)
+    }
+
+    // Reads `len` bytes, None at a clean end of file.
+    fn read_or_eof(&mut self, len: usize) -> Result<Option<Vec<u8>>, DhcpPcapError> {
+        let mut buf = vec![0; len];
+        let mut filled = 0;
+        while filled < len {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(DhcpPcapError::Truncated),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Some(buf))
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, DhcpPcapError> {
+        if len > MAX_BLOCK_LEN {
+            return Err(DhcpPcapError::Malformed("length"));
+        }
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    // Called with the block type and length already read, the length is only
+    // understood once the byte order magic that follows it is. Every section
+    // can switch the byte order and starts without interfaces.
+    fn read_section_header(&mut self, length: [u8; 4]) -> Result<(), DhcpPcapError> {
+        let mut magic = [0; 4];
+        self.reader.read_exact(&mut magic)?;
+        self.big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_BYTE_ORDER_MAGIC, _) => false,
+            (_, PCAPNG_BYTE_ORDER_MAGIC) => true,
+            _ => return Err(DhcpPcapError::Malformed("section header")),
+        };
+        let length = read_u32(&length, self.big_endian) as usize;
+        if length < 28 || !length.is_multiple_of(4) {
+            return Err(DhcpPcapError::Malformed("section header"));
+        }
+        self.read_exact(length - 12)?;
+        self.format = DhcpPcapFormat::Pcapng { interfaces: Vec::new() };
+        Ok(())
+    }
+
+    fn next_pcap(&mut self, link_type: u32, nanos: bool) -> Result<Option<DhcpCaptureRecord>, DhcpPcapError> {
+        let header = match self.read_or_eof(16)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let big_endian = self.big_endian;
+        let secs = read_u32(&header[0..], big_endian);
+        let fraction = read_u32(&header[4..], big_endian);
+        let captured_len = read_u32(&header[8..], big_endian) as usize;
+        let original_len = read_u32(&header[12..], big_endian) as usize;
+        let data = self.read_exact(captured_len)?;
+        let nanos = if nanos { fraction } else { fraction.saturating_mul(1000) };
+        Ok(Some(DhcpCaptureRecord {
+            timestamp: Duration::new(secs.into(), 0) + Duration::from_nanos(nanos.into()),
+            link_type,
+            interface: None,
+            comment: None,
+            original_len,
+            data,
+        }))
+    }
+
+    // Skips the blocks that don't hold packets, statistics and name
+    // resolution among others.
+    fn next_pcapng(&mut self) -> Result<Option<DhcpCaptureRecord>, DhcpPcapError> {
+        loop {
+            let header = match self.read_or_eof(8)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            let block_type = read_u32(&header[0..], self.big_endian);
+            if block_type == PCAPNG_SECTION_HEADER {
+                self.read_section_header([header[4], header[5], header[6], header[7]])?;
+                continue;
+            }
+            let length = read_u32(&header[4..], self.big_endian) as usize;
+            if length < 12 || !length.is_multiple_of(4) {
+                return Err(DhcpPcapError::Malformed("block length"));
+            }
+            let body = self.read_exact(length - 8)?;
+            let body = &body[..body.len() - 4];
+            let record = match block_type {
+                PCAPNG_INTERFACE_DESCRIPTION => {
+                    self.add_interface(body)?;
+                    continue;
+                }
+                PCAPNG_ENHANCED_PACKET | PCAPNG_PACKET => self.packet_block(block_type, body)?,
+                PCAPNG_SIMPLE_PACKET => self.simple_packet_block(body)?,
+                _ => continue,
+            };
+            return Ok(Some(record));
+        }
+    }
+
+    fn interfaces(&self) -> &[DhcpPcapInterface] {
+        match &self.format {
+            DhcpPcapFormat::Pcapng { interfaces } => interfaces,
+            DhcpPcapFormat::Pcap { .. } => &[],
+        }
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> Result<(), DhcpPcapError> {
+        if body.len() < 8 {
+            return Err(DhcpPcapError::Malformed("interface description"));
+        }
+        let mut interface = DhcpPcapInterface {
+            link_type: read_u16(body, self.big_endian).into(),
+            name: None,
+            tsresol: 6,
+        };
+        for (code, value) in options(&body[8..], self.big_endian) {
+            match (code, value) {
+                (PCAPNG_OPT_IF_NAME, _) => interface.name = Some(option_string(value)),
+                (PCAPNG_OPT_IF_TSRESOL, [tsresol, ..]) => interface.tsresol = *tsresol,
+                _ => (),
+            }
+        }
+        if let DhcpPcapFormat::Pcapng { interfaces } = &mut self.format {
+            interfaces.push(interface);
+        }
+        Ok(())
+    }
+
+    fn interface(&self, id: u32) -> Result<&DhcpPcapInterface, DhcpPcapError> {
+        self.interfaces().get(id as usize).ok_or(DhcpPcapError::UnknownInterface(id))
+    }
+
+    // Enhanced packet blocks and the obsolete packet blocks they replaced,
+    // which have a 16 bit interface id followed by a drop count.
+    fn packet_block(&self, block_type: u32, body: &[u8]) -> Result<DhcpCaptureRecord, DhcpPcapError> {
+        if body.len() < 20 {
+            return Err(DhcpPcapError::Malformed("packet block"));
+        }
+        let big_endian = self.big_endian;
+        let id = match block_type {
+            PCAPNG_PACKET => read_u16(body, big_endian).into(),
+            _ => read_u32(body, big_endian),
+        };
+        let interface = self.interface(id)?;
+        let ts = u64::from(read_u32(&body[4..], big_endian)) << 32 | u64::from(read_u32(&body[8..], big_endian));
+        let captured_len = read_u32(&body[12..], big_endian) as usize;
+        let original_len = read_u32(&body[16..], big_endian) as usize;
+        let padded = (captured_len + 3) & !3;
+        if body.len() < 20 + padded {
+            return Err(DhcpPcapError::Malformed("packet block"));
+        }
+        let comment = options(&body[20 + padded..], big_endian)
+            .find(|(code, _)| *code == PCAPNG_OPT_COMMENT)
+            .map(|(_, value)| option_string(value));
+        Ok(DhcpCaptureRecord {
+            timestamp: ts_to_duration(ts, interface.tsresol),
+            link_type: interface.link_type,
+            interface: interface.name.clone(),
+            comment,
+            original_len,
+            data: body[20..20 + captured_len].to_vec(),
+        })
+    }
+
+    // Simple packet blocks belong to the first interface and have no timestamp.
+    fn simple_packet_block(&self, body: &[u8]) -> Result<DhcpCaptureRecord, DhcpPcapError> {
+        if body.len() < 4 {
+            return Err(DhcpPcapError::Malformed("simple packet block"));
+        }
+        let interface = self.interface(0)?;
+        let original_len = read_u32(body, self.big_endian) as usize;
+        let data = &body[4..];
+        Ok(DhcpCaptureRecord {
+            timestamp: Duration::default(),
+            link_type: interface.link_type,
+            interface: interface.name.clone(),
+            comment: None,
+            original_len,
+            data: data[..original_len.min(data.len())].to_vec(),
+        })
+    }
+
+    pub fn next_record(&mut self) -> Result<Option<DhcpCaptureRecord>, DhcpPcapError> {
+        match self.format {
+            DhcpPcapFormat::Pcap { link_type, nanos } => self.next_pcap(link_type, nanos),
+            DhcpPcapFormat::Pcapng { .. } => self.next_pcapng(),
+        }
+    }
+}
+
+// Stops after the first error, the position in the file is lost by then.
+impl<R: Read> Iterator for DhcpPcapReader<R> {
+    type Item = Result<DhcpCaptureRecord, DhcpPcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.next_record();
+        self.done = !matches!(record, Ok(Some(_)));
+        record.transpose()
+    }
+}
+
+// Writes a pcapng file in the host byte order with microsecond timestamps,
+// the pcapng defaults.
+pub struct DhcpPcapWriter<W: Write> {
+    writer: W,
+    interfaces: u32,
+    written: u64,
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    let length = value.len().min(usize::from(u16::MAX - 3));
+    body.extend_from_slice(&code.to_ne_bytes());
+    body.extend_from_slice(&(length as u16).to_ne_bytes());
+    body.extend_from_slice(&value[..length]);
+    body.resize((body.len() + 3) & !3, 0);
+}
+
+fn push_end_of_options(body: &mut Vec<u8>) {
+    body.extend_from_slice(&PCAPNG_OPT_END.to_ne_bytes());
+    body.extend_from_slice(&0_u16.to_ne_bytes());
+}
+
+impl<W: Write> DhcpPcapWriter<W> {
+    pub fn new(writer: W) -> io::Result<Self> {
+        let mut pcap = DhcpPcapWriter { writer, interfaces: 0, written: 0 };
+        let mut body = Vec::new();
+        body.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_ne_bytes());
+        body.extend_from_slice(&1_u16.to_ne_bytes());
+        body.extend_from_slice(&0_u16.to_ne_bytes());
+        // The section length isn't known up front.
+        body.extend_from_slice(&(-1_i64).to_ne_bytes());
+        pcap.write_block(PCAPNG_SECTION_HEADER, &body)?;
+        Ok(pcap)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let length = (body.len() + 12) as u32;
+        self.writer.write_all(&block_type.to_ne_bytes())?;
+        self.writer.write_all(&length.to_ne_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&length.to_ne_bytes())?;
+        self.written += u64::from(length);
+        Ok(())
+    }
+
+    // Returns the id records on this interface are written with.
+    pub fn add_interface(&mut self, link_type: u32, name: Option<&str>) -> io::Result<u32> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(link_type as u16).to_ne_bytes());
+        body.extend_from_slice(&0_u16.to_ne_bytes());
+        // No snapshot length limit.
+        body.extend_from_slice(&0_u32.to_ne_bytes());
+        if let Some(name) = name {
+            push_option(&mut body, PCAPNG_OPT_IF_NAME, name.as_bytes());
+            push_end_of_options(&mut body);
+        }
+        self.write_block(PCAPNG_INTERFACE_DESCRIPTION, &body)?;
+        self.interfaces += 1;
+        Ok(self.interfaces - 1)
+    }
+
+    pub fn write_record(&mut self, interface: u32, timestamp: Duration, data: &[u8], comment: Option<&str>)
+                        -> io::Result<()> {
+        if interface >= self.interfaces {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, DhcpPcapError::UnknownInterface(interface).to_string()));
+        }
+        let micros = timestamp.as_micros() as u64;
+        let mut body = Vec::with_capacity(data.len() + 64);
+        body.extend_from_slice(&interface.to_ne_bytes());
+        body.extend_from_slice(&((micros >> 32) as u32).to_ne_bytes());
+        body.extend_from_slice(&(micros as u32).to_ne_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_ne_bytes());
+        body.extend_from_slice(data);
+        body.resize((body.len() + 3) & !3, 0);
+        if let Some(comment) = comment {
+            push_option(&mut body, PCAPNG_OPT_COMMENT, comment.as_bytes());
+            push_end_of_options(&mut body);
+        }
+        self.write_block(PCAPNG_ENHANCED_PACKET, &body)
+    }
+
+    // Bytes written so far, for rotating files by size.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u16_bytes(value: u16, big_endian: bool) -> [u8; 2] {
+        if big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn u32_bytes(value: u32, big_endian: bool) -> [u8; 4] {
+        if big_endian { value.to_be_bytes() } else { value.to_le_bytes() }
+    }
+
+    fn pcap_header(magic: u32, link_type: u32, big_endian: bool) -> Vec<u8> {
+        let mut buf = u32_bytes(magic, big_endian).to_vec();
+        buf.extend_from_slice(&u16_bytes(2, big_endian));
+        buf.extend_from_slice(&u16_bytes(4, big_endian));
+        buf.extend_from_slice(&[0; 8]);
+        buf.extend_from_slice(&u32_bytes(65535, big_endian));
+        buf.extend_from_slice(&u32_bytes(link_type, big_endian));
+        buf
+    }
+
+    fn pcap_record(buf: &mut Vec<u8>, secs: u32, fraction: u32, data: &[u8], original_len: u32, big_endian: bool) {
+        for value in &[secs, fraction, data.len() as u32, original_len] {
+            buf.extend_from_slice(&u32_bytes(*value, big_endian));
+        }
+        buf.extend_from_slice(data);
+    }
+
+    fn block(buf: &mut Vec<u8>, block_type: u32, body: &[u8], big_endian: bool) {
+        let mut body = body.to_vec();
+        body.resize((body.len() + 3) & !3, 0);
+        let length = body.len() as u32 + 12;
+        buf.extend_from_slice(&u32_bytes(block_type, big_endian));
+        buf.extend_from_slice(&u32_bytes(length, big_endian));
+        buf.extend_from_slice(&body);
+        buf.extend_from_slice(&u32_bytes(length, big_endian));
+    }
+
+    fn option(body: &mut Vec<u8>, code: u16, value: &[u8], big_endian: bool) {
+        body.extend_from_slice(&u16_bytes(code, big_endian));
+        body.extend_from_slice(&u16_bytes(value.len() as u16, big_endian));
+        body.extend_from_slice(value);
+        body.resize((body.len() + 3) & !3, 0);
+    }
+
+    fn section(buf: &mut Vec<u8>, big_endian: bool) {
+        let mut body = u32_bytes(PCAPNG_BYTE_ORDER_MAGIC, big_endian).to_vec();
+        body.extend_from_slice(&u16_bytes(1, big_endian));
+        body.extend_from_slice(&u16_bytes(0, big_endian));
+        body.extend_from_slice(&[0xff; 8]);
+        block(buf, PCAPNG_SECTION_HEADER, &body, big_endian);
+    }
+
+    fn interface(buf: &mut Vec<u8>, link_type: u16, name: Option<&str>, tsresol: Option<u8>, big_endian: bool) {
+        let mut body = u16_bytes(link_type, big_endian).to_vec();
+        body.extend_from_slice(&[0; 6]);
+        if let Some(name) = name {
+            option(&mut body, PCAPNG_OPT_IF_NAME, name.as_bytes(), big_endian);
+        }
+        if let Some(tsresol) = tsresol {
+            option(&mut body, PCAPNG_OPT_IF_TSRESOL, &[tsresol], big_endian);
+        }
+        block(buf, PCAPNG_INTERFACE_DESCRIPTION, &body, big_endian);
+    }
+
+    fn enhanced_packet(buf: &mut Vec<u8>, id: u32, ts: u64, data: &[u8], comment: Option<&str>, big_endian: bool) {
+        let mut body = u32_bytes(id, big_endian).to_vec();
+        body.extend_from_slice(&u32_bytes((ts >> 32) as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(ts as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+        body.extend_from_slice(&u32_bytes(data.len() as u32, big_endian));
+        body.extend_from_slice(data);
+        body.resize((body.len() + 3) & !3, 0);
+        if let Some(comment) = comment {
+            option(&mut body, PCAPNG_OPT_COMMENT, comment.as_bytes(), big_endian);
+            option(&mut body, PCAPNG_OPT_END, &[], big_endian);
+        }
+        block(buf, PCAPNG_ENHANCED_PACKET, &body, big_endian);
+    }
+
+    fn read(buf: &[u8]) -> Vec<DhcpCaptureRecord> {
+        DhcpPcapReader::new(Cursor::new(buf)).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn pcap_microseconds() {
+        let mut buf = pcap_header(PCAP_MAGIC_MICROS, 1, false);
+        pcap_record(&mut buf, 10, 5, &[1, 2, 3], 3, false);
+        pcap_record(&mut buf, 11, 0, &[4], 60, false);
+        let records = read(&buf);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, Duration::new(10, 5_000));
+        assert_eq!(records[0].link_type, 1);
+        assert_eq!(records[0].data, [1, 2, 3]);
+        assert_eq!(records[1].timestamp, Duration::new(11, 0));
+        assert_eq!((records[1].data.len(), records[1].original_len), (1, 60));
+    }
+
+    #[test]
+    fn pcap_big_endian_nanoseconds() {
+        let mut buf = pcap_header(PCAP_MAGIC_NANOS, 113, true);
+        pcap_record(&mut buf, 10, 5, &[1, 2, 3], 3, true);
+        let records = read(&buf);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].timestamp, Duration::new(10, 5));
+        assert_eq!(records[0].link_type, 113);
+        assert_eq!(records[0].data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn pcap_truncated() {
+        let mut buf = pcap_header(PCAP_MAGIC_MICROS, 1, false);
+        pcap_record(&mut buf, 10, 0, &[1, 2, 3, 4], 4, false);
+        pcap_record(&mut buf, 11, 0, &[1, 2, 3, 4], 4, false);
+        buf.truncate(buf.len() - 2);
+        let mut reader = DhcpPcapReader::new(Cursor::new(&buf)).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(reader.next(), Some(Err(DhcpPcapError::Truncated))));
+        assert!(reader.next().is_none());
+        assert!(matches!(DhcpPcapReader::new(Cursor::new(&buf[..10])), Err(DhcpPcapError::Truncated)));
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(matches!(DhcpPcapReader::new(Cursor::new(&[0x01, 0x01, 0x06, 0x00, 0, 0])),
+                         Err(DhcpPcapError::UnknownFormat(0x0101_0600))));
+    }
+
+    #[test]
+    fn pcapng_sections() {
+        let mut buf = Vec::new();
+        section(&mut buf, false);
+        interface(&mut buf, 1, Some("eth0"), None, false);
+        interface(&mut buf, 113, Some("any"), Some(9), false);
+        enhanced_packet(&mut buf, 1, 10_000_000_005, &[1, 2, 3], None, false);
+        // Statistics, skipped.
+        block(&mut buf, 5, &[0; 12], false);
+        // A new section switches the byte order and forgets the interfaces.
+        section(&mut buf, true);
+        interface(&mut buf, 1, None, None, true);
+        enhanced_packet(&mut buf, 0, 10_000_005, &[4, 5], None, true);
+        let records = read(&buf);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, Duration::new(10, 5));
+        assert_eq!((records[0].link_type, records[0].interface.as_deref()), (113, Some("any")));
+        assert_eq!(records[0].data, [1, 2, 3]);
+        assert_eq!(records[1].timestamp, Duration::new(10, 5_000));
+        assert_eq!((records[1].link_type, records[1].interface.as_deref()), (1, None));
+        assert_eq!(records[1].data, [4, 5]);
+    }
+
+    #[test]
+    fn pcapng_unknown_interface() {
+        let mut buf = Vec::new();
+        section(&mut buf, false);
+        interface(&mut buf, 1, None, None, false);
+        section(&mut buf, false);
+        enhanced_packet(&mut buf, 0, 0, &[1], None, false);
+        let mut reader = DhcpPcapReader::new(Cursor::new(&buf)).unwrap();
+        assert!(matches!(reader.next(), Some(Err(DhcpPcapError::UnknownInterface(0)))));
+    }
+
+    #[test]
+    fn pcapng_simple_and_obsolete_packet_blocks() {
+        for &big_endian in &[false, true] {
+            let mut buf = Vec::new();
+            section(&mut buf, big_endian);
+            interface(&mut buf, 1, Some("eth0"), None, big_endian);
+            // The original length tells how much of the padded data is the packet.
+            let mut body = u32_bytes(3, big_endian).to_vec();
+            body.extend_from_slice(&[1, 2, 3, 0]);
+            block(&mut buf, PCAPNG_SIMPLE_PACKET, &body, big_endian);
+            let mut body = u16_bytes(0, big_endian).to_vec();
+            body.extend_from_slice(&u16_bytes(7, big_endian));
+            for value in &[0, 2_000_000, 2, 2] {
+                body.extend_from_slice(&u32_bytes(*value, big_endian));
+            }
+            body.extend_from_slice(&[4, 5]);
+            block(&mut buf, PCAPNG_PACKET, &body, big_endian);
+            let records = read(&buf);
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].data, [1, 2, 3]);
+            assert_eq!((records[0].timestamp, records[0].interface.as_deref()), (Duration::default(), Some("eth0")));
+            assert_eq!(records[1].data, [4, 5]);
+            assert_eq!(records[1].timestamp, Duration::new(2, 0));
+        }
+    }
+
+    #[test]
+    fn pcapng_comment() {
+        let mut buf = Vec::new();
+        section(&mut buf, false);
+        interface(&mut buf, 1, None, None, false);
+        enhanced_packet(&mut buf, 0, 0, &[1, 2, 3, 4, 5], Some("rogue offer"), false);
+        enhanced_packet(&mut buf, 0, 0, &[1], None, false);
+        let records = read(&buf);
+        assert_eq!(records[0].comment.as_deref(), Some("rogue offer"));
+        assert_eq!(records[0].data, [1, 2, 3, 4, 5]);
+        assert_eq!(records[1].comment, None);
+    }
+
+    #[test]
+    fn pcapng_truncated() {
+        let mut buf = Vec::new();
+        section(&mut buf, false);
+        interface(&mut buf, 1, None, None, false);
+        enhanced_packet(&mut buf, 0, 0, &[1, 2, 3], None, false);
+        enhanced_packet(&mut buf, 0, 0, &[1, 2, 3], None, false);
+        buf.truncate(buf.len() - 6);
+        let mut reader = DhcpPcapReader::new(Cursor::new(&buf)).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(reader.next(), Some(Err(DhcpPcapError::Truncated))));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn writer_round_trip() {
+        let mut writer = DhcpPcapWriter::new(Vec::new()).unwrap();
+        let eth0 = writer.add_interface(1, Some("eth0")).unwrap();
+        let any = writer.add_interface(113, None).unwrap();
+        writer.write_record(eth0, Duration::new(1_600_000_000, 123_456_000), &[1, 2, 3, 4, 5], Some("first"))
+            .unwrap();
+        writer.write_record(any, Duration::new(1_600_000_001, 0), &[6], None).unwrap();
+        assert!(writer.write_record(2, Duration::default(), &[7], None).is_err());
+        writer.flush().unwrap();
+        let written = writer.written();
+        let buf = writer.writer;
+        assert_eq!(written, buf.len() as u64);
+
+        let records = read(&buf);
+        assert_eq!(records, vec![
+            DhcpCaptureRecord {
+                timestamp: Duration::new(1_600_000_000, 123_456_000),
+                link_type: 1,
+                interface: Some("eth0".to_string()),
+                comment: Some("first".to_string()),
+                original_len: 5,
+                data: vec![1, 2, 3, 4, 5],
+            },
+            DhcpCaptureRecord {
+                timestamp: Duration::new(1_600_000_001, 0),
+                link_type: 113,
+                interface: None,
+                comment: None,
+                original_len: 1,
+                data: vec![6],
+            },
+        ]);
+    }
+}
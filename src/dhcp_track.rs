@@ -0,0 +1,557 @@
+use crate::dhcp_packet::{BootpOpcode, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpPacket, MacAddr};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+// How long a transaction waits for its next packet. RFC 2131 clients back
+// off up to 64 seconds between retransmissions.
+const DHCP_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// The time to ACK percentiles are over this many latest transactions, so a
+// long capture doesn't keep every one of them.
+const DHCP_TIMES_TO_ACK: usize = 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display)]
+pub enum DhcpTransactionKind {
+    // DISCOVER, OFFER, REQUEST and ACK.
+    #[strum(to_string = "DORA")]
+    Dora,
+    // A REQUEST without DISCOVER, when renewing, rebinding or rebooting.
+    #[strum(to_string = "REQUEST")]
+    Request,
+    #[strum(to_string = "INFORM")]
+    Inform,
+    #[strum(to_string = "RELEASE")]
+    Release,
+    #[strum(to_string = "DECLINE")]
+    Decline,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display)]
+pub enum DhcpOutcome {
+    #[strum(to_string = "acked")]
+    Acked,
+    #[strum(to_string = "naked")]
+    Naked,
+    #[strum(to_string = "no offer")]
+    NoOffer,
+    // The client never picked one of the offers.
+    #[strum(to_string = "no request")]
+    NoRequest,
+    #[strum(to_string = "no ack")]
+    NoAck,
+    // RELEASE and DECLINE don't get an answer.
+    #[strum(to_string = "sent")]
+    Sent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpTransaction {
+    pub kind: DhcpTransactionKind,
+    pub xid: u32,
+    pub chaddr: MacAddr,
+    // Timestamps of the first packet of each step, as given to the tracker.
+    pub started: Duration,
+    pub offered_at: Option<Duration>,
+    pub requested_at: Option<Duration>,
+    pub answered_at: Option<Duration>,
+    pub outcome: Option<DhcpOutcome>,
+    // The server that answered the request, or else the first one that offered.
+    pub server: Option<Ipv4Addr>,
+    pub offered: Option<Ipv4Addr>,
+    pub acked: Option<Ipv4Addr>,
+    pub offers: u32,
+    // Client messages sent again before an answer came.
+    pub retransmissions: u32,
+    discovers: u32,
+    last_seen: Duration,
+}
+
+fn server_id(packet: &DhcpPacket) -> Option<Ipv4Addr> {
+    match packet.options.get(&DhcpOptionID::ServerID) {
+        Some(DhcpOption::ServerID(addr)) => Some(*addr),
+        _ => None,
+    }
+}
+
+fn fmt_latency(w: &mut fmt::Formatter, name: &str, from: Option<Duration>, to: Option<Duration>) -> fmt::Result {
+    match (from, to) {
+        (Some(from), Some(to)) if to >= from =>
+            write!(w, " {}={:.1}ms", name, (to - from).as_secs_f64() * 1000.0),
+        _ => Ok(()),
+    }
+}
+
+impl DhcpTransaction {
+    fn new(kind: DhcpTransactionKind, timestamp: Duration, packet: &DhcpPacket) -> Self {
+        DhcpTransaction {
+            kind,
+            xid: packet.xid,
+            chaddr: packet.chaddr,
+            started: timestamp,
+            offered_at: None,
+            requested_at: None,
+            answered_at: None,
+            outcome: None,
+            server: None,
+            offered: None,
+            acked: None,
+            offers: 0,
+            retransmissions: 0,
+            discovers: 0,
+            last_seen: timestamp,
+        }
+    }
+
+    // From the first client packet to the ACK, retransmissions included.
+    pub fn time_to_ack(&self) -> Option<Duration> {
+        match (self.outcome, self.answered_at) {
+            (Some(DhcpOutcome::Acked), Some(answered_at)) => answered_at.checked_sub(self.started),
+            _ => None,
+        }
+    }
+
+    // What became of a transaction that stopped getting packets.
+    fn abandon(&mut self) {
+        if self.outcome.is_some() {
+            return;
+        }
+        self.outcome = Some(match (self.kind, self.offered_at, self.requested_at) {
+            (DhcpTransactionKind::Dora, None, None) => DhcpOutcome::NoOffer,
+            (DhcpTransactionKind::Dora, Some(_), None) => DhcpOutcome::NoRequest,
+            (DhcpTransactionKind::Release, ..) | (DhcpTransactionKind::Decline, ..) => DhcpOutcome::Sent,
+            _ => DhcpOutcome::NoAck,
+        });
+    }
+
+    fn observe(&mut self, timestamp: Duration, message_type: &DhcpMessageType, packet: &DhcpPacket) {
+        self.last_seen = timestamp;
+        match message_type {
+            // Once an offer came in a DISCOVER isn't a retransmission.
+            DhcpMessageType::DhcpDiscover | DhcpMessageType::DhcpInform => {
+                if self.discovers > 0 && self.offered_at.is_none() {
+                    self.retransmissions += 1;
+                }
+                self.discovers += 1;
+            }
+            DhcpMessageType::DhcpOffer => {
+                self.offers += 1;
+                self.offered_at.get_or_insert(timestamp);
+                if self.server.is_none() {
+                    self.server = server_id(packet);
+                    self.offered = packet.yiaddr;
+                }
+            }
+            DhcpMessageType::DhcpRequest => {
+                if self.requested_at.is_some() {
+                    self.retransmissions += 1;
+                }
+                self.requested_at.get_or_insert(timestamp);
+                // The offer the client picked.
+                if let Some(server) = server_id(packet) {
+                    self.server = Some(server);
+                }
+                if let Some(DhcpOption::RequestedIpAddr(addr)) = packet.options.get(&DhcpOptionID::RequestedIpAddr) {
+                    self.offered = self.offered.or(Some(*addr));
+                }
+            }
+            DhcpMessageType::DhcpAck | DhcpMessageType::DhcpNak => {
+                self.answered_at = Some(timestamp);
+                self.server = server_id(packet).or(self.server);
+                if *message_type == DhcpMessageType::DhcpAck {
+                    self.acked = packet.yiaddr.or(packet.ciaddr);
+                    self.outcome = Some(DhcpOutcome::Acked);
+                } else {
+                    self.outcome = Some(DhcpOutcome::Naked);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl fmt::Display for DhcpTransaction {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        write!(w, "{} xid={:#010x} chaddr={}", self.kind, self.xid, self.chaddr)?;
+        match self.outcome {
+            Some(outcome) => write!(w, " {}", outcome)?,
+            None => write!(w, " pending")?,
+        }
+        if let Some(addr) = self.acked.or(self.offered) {
+            write!(w, " {}", addr)?;
+        }
+        if let Some(server) = self.server {
+            write!(w, " by {}", server)?;
+        }
+        let requested_after = if self.kind == DhcpTransactionKind::Dora { self.offered_at } else { None };
+        fmt_latency(w, "offer", Some(self.started), self.offered_at)?;
+        fmt_latency(w, "request", requested_after, self.requested_at)?;
+        fmt_latency(w, "answer", self.requested_at.or(Some(self.started)), self.answered_at)?;
+        fmt_latency(w, "total", Some(self.started), self.answered_at)?;
+        if self.offers > 1 {
+            write!(w, " offers={}", self.offers)?;
+        }
+        if self.retransmissions > 0 {
+            write!(w, " retransmissions={}", self.retransmissions)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DhcpTrackerStats {
+    pub outcomes: HashMap<(DhcpTransactionKind, DhcpOutcome), u64>,
+    pub retransmissions: u64,
+    // Server packets that didn't belong to any client packet seen.
+    pub unmatched: u64,
+    // A ring of the latest times, `next_time_to_ack` is the oldest once full.
+    times_to_ack: Vec<Duration>,
+    next_time_to_ack: usize,
+}
+
+impl DhcpTrackerStats {
+    fn add(&mut self, transaction: &DhcpTransaction) {
+        if let Some(outcome) = transaction.outcome {
+            *self.outcomes.entry((transaction.kind, outcome)).or_insert(0) += 1;
+        }
+        self.retransmissions += u64::from(transaction.retransmissions);
+        if let Some(time_to_ack) = transaction.time_to_ack() {
+            if self.times_to_ack.len() < DHCP_TIMES_TO_ACK {
+                self.times_to_ack.push(time_to_ack);
+            } else {
+                self.times_to_ack[self.next_time_to_ack] = time_to_ack;
+            }
+            self.next_time_to_ack = (self.next_time_to_ack + 1) % DHCP_TIMES_TO_ACK;
+        }
+    }
+
+    pub fn count(&self, kind: DhcpTransactionKind, outcome: DhcpOutcome) -> u64 {
+        self.outcomes.get(&(kind, outcome)).copied().unwrap_or(0)
+    }
+
+    pub fn unanswered_discovers(&self) -> u64 {
+        self.count(DhcpTransactionKind::Dora, DhcpOutcome::NoOffer)
+    }
+
+    // Nearest rank percentile of the time to ACK of the latest transactions,
+    // `percentile` from 0 to 100.
+    pub fn time_to_ack(&self, percentile: f64) -> Option<Duration> {
+        let mut times = self.times_to_ack.clone();
+        times.sort();
+        let rank = (percentile / 100.0 * times.len() as f64).ceil() as usize;
+        times.get(rank.max(1) - 1).copied()
+    }
+}
+
+impl fmt::Display for DhcpTrackerStats {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        let mut outcomes: Vec<_> = self.outcomes.iter().collect();
+        outcomes.sort_by_key(|((kind, outcome), _)| (kind.to_string(), outcome.to_string()));
+        let total: u64 = outcomes.iter().map(|(_, count)| **count).sum();
+        writeln!(w, "transactions: {}", total)?;
+        for ((kind, outcome), count) in outcomes {
+            writeln!(w, "    {} {}: {}", kind, outcome, count)?;
+        }
+        for percentile in &[50.0, 99.0] {
+            if let Some(time) = self.time_to_ack(*percentile) {
+                writeln!(w, "time to ack p{}: {:.1}ms", percentile, time.as_secs_f64() * 1000.0)?;
+            }
+        }
+        writeln!(w, "retransmissions: {}", self.retransmissions)?;
+        writeln!(w, "unanswered discovers: {}", self.unanswered_discovers())?;
+        write!(w, "unmatched server packets: {}", self.unmatched)
+    }
+}
+
+// Groups packets into transactions by xid and chaddr. Timestamps only need
+// to increase, a capture's or the time since the tracker started.
+#[derive(Debug, Clone, Default)]
+pub struct DhcpTracker {
+    open: HashMap<(u32, MacAddr), DhcpTransaction>,
+    // Answered transactions stay here for a while, to recognize repeated
+    // answers as part of them.
+    closed: HashMap<(u32, MacAddr), Duration>,
+    stats: DhcpTrackerStats,
+}
+
+impl DhcpTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> &DhcpTrackerStats {
+        &self.stats
+    }
+
+    fn close(&mut self, key: (u32, MacAddr), timestamp: Duration) -> Option<DhcpTransaction> {
+        let mut transaction = self.open.remove(&key)?;
+        transaction.abandon();
+        self.stats.add(&transaction);
+        self.closed.insert(key, timestamp);
+        Some(transaction)
+    }
+
+    // Returns the transactions this packet completed, more than one when it
+    // starts a new one over a transaction that never got an answer.
+    pub fn observe(&mut self, timestamp: Duration, packet: &DhcpPacket) -> Vec<DhcpTransaction> {
+        let mut done = self.expire(timestamp);
+        let message_type = match packet.message_type() {
+            Some(message_type) => message_type,
+            None => return done,
+        };
+        let key = (packet.xid, packet.chaddr);
+        let kind = match (packet.opcode, message_type) {
+            (BootpOpcode::BootRequest, DhcpMessageType::DhcpDiscover) => Some(DhcpTransactionKind::Dora),
+            (BootpOpcode::BootRequest, DhcpMessageType::DhcpRequest) => Some(DhcpTransactionKind::Request),
+            (BootpOpcode::BootRequest, DhcpMessageType::DhcpInform) => Some(DhcpTransactionKind::Inform),
+            (BootpOpcode::BootRequest, DhcpMessageType::DhcpRelease) => Some(DhcpTransactionKind::Release),
+            (BootpOpcode::BootRequest, DhcpMessageType::DhcpDecline) => Some(DhcpTransactionKind::Decline),
+            _ => None,
+        };
+        // A client starting over with the same xid, a REQUEST continues a
+        // DORA but anything else only continues the same kind.
+        let restart = match (self.open.get(&key), kind) {
+            (Some(open), Some(kind)) => kind != DhcpTransactionKind::Request && kind != open.kind,
+            _ => false,
+        };
+        if restart {
+            done.extend(self.close(key, timestamp));
+        }
+        match (self.open.get_mut(&key), kind) {
+            (Some(open), _) => open.observe(timestamp, message_type, packet),
+            (None, Some(kind)) => {
+                self.closed.remove(&key);
+                let mut transaction = DhcpTransaction::new(kind, timestamp, packet);
+                transaction.observe(timestamp, message_type, packet);
+                self.open.insert(key, transaction);
+            }
+            (None, None) => {
+                if !self.closed.contains_key(&key) {
+                    self.stats.unmatched += 1;
+                }
+                return done;
+            }
+        }
+        let finished = match self.open.get(&key) {
+            Some(open) => open.outcome.is_some() ||
+                open.kind == DhcpTransactionKind::Release || open.kind == DhcpTransactionKind::Decline,
+            None => false,
+        };
+        if finished {
+            done.extend(self.close(key, timestamp));
+        }
+        done
+    }
+
+    // Closes the transactions that haven't seen a packet for a minute.
+    pub fn expire(&mut self, now: Duration) -> Vec<DhcpTransaction> {
+        let stale = |last_seen: Duration| now.checked_sub(last_seen).is_some_and(|x| x > DHCP_TRANSACTION_TIMEOUT);
+        self.closed.retain(|_, closed_at| !stale(*closed_at));
+        let keys: Vec<_> = self.open.iter().filter(|(_, x)| stale(x.last_seen)).map(|(key, _)| *key).collect();
+        let mut done: Vec<_> = keys.into_iter().filter_map(|key| self.close(key, now)).collect();
+        done.sort_by_key(|x| x.started);
+        done
+    }
+
+    // Closes everything still open, at the end of a capture.
+    pub fn finish(&mut self) -> Vec<DhcpTransaction> {
+        let keys: Vec<_> = self.open.keys().copied().collect();
+        let mut done: Vec<_> = keys.into_iter().filter_map(|key| {
+            let last_seen = self.open[&key].last_seen;
+            self.close(key, last_seen)
+        }).collect();
+        done.sort_by_key(|x| x.started);
+        done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+    const CLIENT: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 1]);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn packet(message_type: DhcpMessageType, xid: u32, chaddr: MacAddr) -> DhcpPacket {
+        let opcode = match message_type {
+            DhcpMessageType::DhcpOffer | DhcpMessageType::DhcpAck | DhcpMessageType::DhcpNak =>
+                BootpOpcode::BootReply,
+            _ => BootpOpcode::BootRequest,
+        };
+        let mut packet = DhcpPacket::new(opcode, xid, chaddr);
+        if opcode == BootpOpcode::BootReply {
+            packet.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(SERVER));
+            if message_type != DhcpMessageType::DhcpNak {
+                packet.yiaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+            }
+        }
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        packet
+    }
+
+    // Feeds `(milliseconds, message type)` of one client and returns every
+    // transaction completed on the way.
+    fn run(tracker: &mut DhcpTracker, xid: u32, packets: &[(u64, DhcpMessageType)]) -> Vec<DhcpTransaction> {
+        packets.iter().flat_map(|(at, message_type)| tracker.observe(ms(*at), &packet(message_type.clone(), xid, CLIENT)))
+            .collect()
+    }
+
+    #[test]
+    fn dora() {
+        let mut tracker = DhcpTracker::new();
+        let done = run(&mut tracker, 1, &[(0, DhcpMessageType::DhcpDiscover), (10, DhcpMessageType::DhcpOffer),
+            (30, DhcpMessageType::DhcpRequest), (35, DhcpMessageType::DhcpAck)]);
+        assert_eq!(done.len(), 1);
+        let transaction = &done[0];
+        assert_eq!((transaction.kind, transaction.outcome), (DhcpTransactionKind::Dora, Some(DhcpOutcome::Acked)));
+        assert_eq!((transaction.offered_at, transaction.requested_at), (Some(ms(10)), Some(ms(30))));
+        assert_eq!(transaction.time_to_ack(), Some(ms(35)));
+        assert_eq!(transaction.server, Some(SERVER));
+        assert_eq!(transaction.acked, Some(Ipv4Addr::new(192, 0, 2, 10)));
+        assert_eq!(transaction.to_string(), "DORA xid=0x00000001 chaddr=02:00:00:00:00:01 acked 192.0.2.10 by 192.0.2.1 \
+            offer=10.0ms request=20.0ms answer=5.0ms total=35.0ms");
+        assert_eq!(tracker.stats().count(DhcpTransactionKind::Dora, DhcpOutcome::Acked), 1);
+        assert!(tracker.finish().is_empty());
+    }
+
+    #[test]
+    fn inform() {
+        let mut tracker = DhcpTracker::new();
+        let done = run(&mut tracker, 2, &[(0, DhcpMessageType::DhcpInform), (4, DhcpMessageType::DhcpAck)]);
+        assert_eq!(done.len(), 1);
+        assert_eq!((done[0].kind, done[0].outcome), (DhcpTransactionKind::Inform, Some(DhcpOutcome::Acked)));
+        assert_eq!(done[0].time_to_ack(), Some(ms(4)));
+    }
+
+    #[test]
+    fn nak() {
+        let mut tracker = DhcpTracker::new();
+        let done = run(&mut tracker, 3, &[(0, DhcpMessageType::DhcpRequest), (2, DhcpMessageType::DhcpNak)]);
+        assert_eq!(done.len(), 1);
+        assert_eq!((done[0].kind, done[0].outcome), (DhcpTransactionKind::Request, Some(DhcpOutcome::Naked)));
+        assert_eq!(done[0].time_to_ack(), None);
+        assert_eq!(tracker.stats().time_to_ack(50.0), None);
+    }
+
+    #[test]
+    fn unanswered_discover() {
+        let mut tracker = DhcpTracker::new();
+        assert!(run(&mut tracker, 4, &[(0, DhcpMessageType::DhcpDiscover), (4_000, DhcpMessageType::DhcpDiscover)])
+            .is_empty());
+        // Only after a minute without packets.
+        assert!(tracker.expire(ms(64_000)).is_empty());
+        let done = tracker.expire(ms(64_001));
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].outcome, Some(DhcpOutcome::NoOffer));
+        assert_eq!(done[0].retransmissions, 1);
+        assert_eq!(tracker.stats().unanswered_discovers(), 1);
+        assert_eq!(tracker.stats().retransmissions, 1);
+    }
+
+    #[test]
+    fn repeated_answers_are_not_unmatched() {
+        let mut tracker = DhcpTracker::new();
+        run(&mut tracker, 5, &[(0, DhcpMessageType::DhcpInform), (1, DhcpMessageType::DhcpAck)]);
+        assert!(run(&mut tracker, 5, &[(2, DhcpMessageType::DhcpAck)]).is_empty());
+        assert_eq!(tracker.stats().unmatched, 0);
+        run(&mut tracker, 6, &[(3, DhcpMessageType::DhcpAck)]);
+        assert_eq!(tracker.stats().unmatched, 1);
+        // Closed transactions are forgotten along with the open ones.
+        run(&mut tracker, 5, &[(70_000, DhcpMessageType::DhcpAck)]);
+        assert_eq!(tracker.stats().unmatched, 2);
+    }
+
+    #[test]
+    fn transactions_are_told_apart_by_xid_and_chaddr() {
+        let mut tracker = DhcpTracker::new();
+        let other = MacAddr([0x02, 0, 0, 0, 0, 2]);
+        assert!(tracker.observe(ms(0), &packet(DhcpMessageType::DhcpInform, 7, CLIENT)).is_empty());
+        assert!(tracker.observe(ms(1), &packet(DhcpMessageType::DhcpInform, 7, other)).is_empty());
+        assert!(tracker.observe(ms(2), &packet(DhcpMessageType::DhcpInform, 8, CLIENT)).is_empty());
+        let done = tracker.observe(ms(5), &packet(DhcpMessageType::DhcpAck, 7, other));
+        assert_eq!(done.len(), 1);
+        assert_eq!((done[0].xid, done[0].chaddr, done[0].time_to_ack()), (7, other, Some(ms(4))));
+        let done = tracker.finish();
+        assert_eq!(done.iter().map(|x| (x.xid, x.chaddr)).collect::<Vec<_>>(), [(7, CLIENT), (8, CLIENT)]);
+        assert!(done.iter().all(|x| x.outcome == Some(DhcpOutcome::NoAck)));
+    }
+
+    #[test]
+    fn a_new_kind_restarts_the_transaction() {
+        let mut tracker = DhcpTracker::new();
+        // A REQUEST continues the DORA.
+        assert!(run(&mut tracker, 9, &[(0, DhcpMessageType::DhcpDiscover), (1, DhcpMessageType::DhcpOffer),
+            (2, DhcpMessageType::DhcpRequest)]).is_empty());
+        let done = run(&mut tracker, 9, &[(3, DhcpMessageType::DhcpInform)]);
+        assert_eq!(done.len(), 1);
+        assert_eq!((done[0].kind, done[0].outcome), (DhcpTransactionKind::Dora, Some(DhcpOutcome::NoAck)));
+        let done = run(&mut tracker, 9, &[(5, DhcpMessageType::DhcpAck)]);
+        assert_eq!((done[0].kind, done[0].time_to_ack()), (DhcpTransactionKind::Inform, Some(ms(2))));
+    }
+
+    #[test]
+    fn retransmissions() {
+        let mut tracker = DhcpTracker::new();
+        let done = run(&mut tracker, 10, &[(0, DhcpMessageType::DhcpDiscover), (1_000, DhcpMessageType::DhcpDiscover),
+            (1_001, DhcpMessageType::DhcpOffer), (1_002, DhcpMessageType::DhcpDiscover),
+            (1_003, DhcpMessageType::DhcpRequest), (2_003, DhcpMessageType::DhcpRequest),
+            (2_004, DhcpMessageType::DhcpAck)]);
+        assert_eq!(done[0].retransmissions, 2);
+        assert_eq!(done[0].requested_at, Some(ms(1_003)));
+        assert_eq!(done[0].time_to_ack(), Some(ms(2_004)));
+    }
+
+    #[test]
+    fn abandoned_transactions() {
+        let mut tracker = DhcpTracker::new();
+        run(&mut tracker, 11, &[(0, DhcpMessageType::DhcpDiscover), (1, DhcpMessageType::DhcpOffer),
+            (2, DhcpMessageType::DhcpOffer)]);
+        run(&mut tracker, 12, &[(10, DhcpMessageType::DhcpDiscover), (11, DhcpMessageType::DhcpOffer),
+            (12, DhcpMessageType::DhcpRequest)]);
+        let done = tracker.finish();
+        assert_eq!(done.iter().map(|x| x.outcome).collect::<Vec<_>>(),
+                   [Some(DhcpOutcome::NoRequest), Some(DhcpOutcome::NoAck)]);
+        assert_eq!(done[0].offers, 2);
+        // RELEASE and DECLINE are done as soon as they are sent.
+        let done = run(&mut tracker, 13, &[(20, DhcpMessageType::DhcpRelease)]);
+        assert_eq!((done[0].kind, done[0].outcome), (DhcpTransactionKind::Release, Some(DhcpOutcome::Sent)));
+        let done = run(&mut tracker, 14, &[(21, DhcpMessageType::DhcpDecline)]);
+        assert_eq!((done[0].kind, done[0].outcome), (DhcpTransactionKind::Decline, Some(DhcpOutcome::Sent)));
+    }
+
+    #[test]
+    fn time_to_ack_percentiles() {
+        let mut tracker = DhcpTracker::new();
+        // Ten ACKed transactions taking 1 to 10ms, in a shuffled order.
+        for (xid, time) in [7, 3, 10, 1, 5, 9, 2, 8, 4, 6].iter().enumerate() {
+            let start = xid as u64 * 100;
+            run(&mut tracker, xid as u32, &[(start, DhcpMessageType::DhcpInform), (start + time, DhcpMessageType::DhcpAck)]);
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.time_to_ack(0.0), Some(ms(1)));
+        assert_eq!(stats.time_to_ack(50.0), Some(ms(5)));
+        assert_eq!(stats.time_to_ack(51.0), Some(ms(6)));
+        assert_eq!(stats.time_to_ack(99.0), Some(ms(10)));
+        assert_eq!(stats.time_to_ack(100.0), Some(ms(10)));
+        assert!(stats.to_string().contains("time to ack p50: 5.0ms\ntime to ack p99: 10.0ms\n"));
+    }
+
+    #[test]
+    fn only_the_latest_times_to_ack_are_kept() {
+        let mut tracker = DhcpTracker::new();
+        let mut start = 0;
+        for (xid, time) in (0..DHCP_TIMES_TO_ACK as u64 + 10).map(|x| if x < 10 { 1_000 } else { 1 }).enumerate() {
+            run(&mut tracker, xid as u32, &[(start, DhcpMessageType::DhcpInform), (start + time, DhcpMessageType::DhcpAck)]);
+            start += 1_000;
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.times_to_ack.len(), DHCP_TIMES_TO_ACK);
+        assert_eq!(stats.time_to_ack(100.0), Some(ms(1)));
+        assert_eq!(stats.count(DhcpTransactionKind::Inform, DhcpOutcome::Acked), DHCP_TIMES_TO_ACK as u64 + 10);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::dhcp_packet::{BootpOpcode, DhcpMessageType, DhcpOption, DhcpOptionID, DhcpPacket};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::net::Ipv4Addr;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display)]
+pub enum DhcpField {
+    #[strum(to_string = "ciaddr")]
+    Ciaddr,
+    #[strum(to_string = "yiaddr")]
+    Yiaddr,
+    #[strum(to_string = "siaddr")]
+    Siaddr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhcpViolation {
+    MissingMessageType,
+    WrongOpcode { message_type: DhcpMessageType, opcode: BootpOpcode },
+    MissingOption { message_type: DhcpMessageType, option_id: DhcpOptionID },
+    ForbiddenOption { message_type: DhcpMessageType, option_id: DhcpOptionID },
+    FieldNotZero { message_type: DhcpMessageType, field: DhcpField },
+    FieldZero { message_type: DhcpMessageType, field: DhcpField },
+}
+
+impl fmt::Display for DhcpViolation {
+    fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingMessageType =>
+                write!(w, "Message Type option missing"),
+            Self::WrongOpcode { message_type, opcode } =>
+                write!(w, "{} sent with opcode {}", message_type, opcode),
+            Self::MissingOption { message_type, option_id } =>
+                write!(w, "{} MUST contain {}", message_type, option_id),
+            Self::ForbiddenOption { message_type, option_id } =>
+                write!(w, "{} MUST NOT contain {}", message_type, option_id),
+            Self::FieldNotZero { message_type, field } =>
+                write!(w, "{} MUST have {} set to 0", message_type, field),
+            Self::FieldZero { message_type, field } =>
+                write!(w, "{} MUST have {} set", message_type, field),
+        }
+    }
+}
+
+// Per message type requirements from RFC 2131 tables 3 (server) and 5 (client).
+// When `allowed` is set, every option not listed there is a MUST NOT. RFC 6842
+// has servers echo the Client Identifier, table 3 forbade it in OFFER and ACK.
+struct MessageRules {
+    opcode: BootpOpcode,
+    required: &'static [DhcpOptionID],
+    forbidden: &'static [DhcpOptionID],
+    allowed: Option<&'static [DhcpOptionID]>,
+    ciaddr: Option<bool>,
+    yiaddr: Option<bool>,
+    siaddr: Option<bool>,
+}
+
+const DISCOVER_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[],
+    forbidden: &[DhcpOptionID::ServerID],
+    allowed: None,
+    ciaddr: Some(false),
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+const INFORM_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[],
+    forbidden: &[DhcpOptionID::RequestedIpAddr, DhcpOptionID::LeaseTime, DhcpOptionID::ServerID],
+    allowed: None,
+    ciaddr: Some(true),
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+// ciaddr and the Server ID/Requested IP options depend on the client state,
+// see DhcpPacket::validate_request.
+const REQUEST_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[],
+    forbidden: &[],
+    allowed: None,
+    ciaddr: None,
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+const DECLINE_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[DhcpOptionID::RequestedIpAddr, DhcpOptionID::ServerID],
+    forbidden: &[],
+    allowed: Some(&[DhcpOptionID::MsgType, DhcpOptionID::ClientIdentifier, DhcpOptionID::RequestedIpAddr,
+        DhcpOptionID::ServerID, DhcpOptionID::Message]),
+    ciaddr: Some(false),
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+const RELEASE_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[DhcpOptionID::ServerID],
+    forbidden: &[],
+    allowed: Some(&[DhcpOptionID::MsgType, DhcpOptionID::ClientIdentifier, DhcpOptionID::ServerID,
+        DhcpOptionID::Message]),
+    ciaddr: Some(true),
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+const OFFER_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootReply,
+    required: &[DhcpOptionID::LeaseTime, DhcpOptionID::ServerID],
+    forbidden: &[DhcpOptionID::RequestedIpAddr, DhcpOptionID::ParameterRequestList, DhcpOptionID::MaxMsgSize],
+    allowed: None,
+    ciaddr: Some(false),
+    yiaddr: Some(true),
+    siaddr: None,
+};
+
+// Lease Time depends on whether the ACK answers a REQUEST or an INFORM,
+// see DhcpPacket::validate_ack.
+const ACK_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootReply,
+    required: &[DhcpOptionID::ServerID],
+    forbidden: &[DhcpOptionID::RequestedIpAddr, DhcpOptionID::ParameterRequestList, DhcpOptionID::MaxMsgSize],
+    allowed: None,
+    ciaddr: None,
+    yiaddr: None,
+    siaddr: None,
+};
+
+const NAK_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootReply,
+    required: &[DhcpOptionID::ServerID],
+    forbidden: &[],
+    allowed: Some(&[DhcpOptionID::MsgType, DhcpOptionID::ServerID, DhcpOptionID::ClientIdentifier,
+        DhcpOptionID::VendorClassId, DhcpOptionID::Message]),
+    ciaddr: Some(false),
+    yiaddr: Some(false),
+    siaddr: Some(false),
+};
+
+// RFC 3203 section 4.
+const FORCE_RENEW_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootReply,
+    required: &[DhcpOptionID::ServerID],
+    forbidden: &[],
+    allowed: None,
+    ciaddr: None,
+    yiaddr: None,
+    siaddr: None,
+};
+
+// RFC 4388 and RFC 6926, only the opcode is checked.
+const LEASEQUERY_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootRequest,
+    required: &[],
+    forbidden: &[],
+    allowed: None,
+    ciaddr: None,
+    yiaddr: None,
+    siaddr: None,
+};
+
+const LEASEQUERY_REPLY_RULES: MessageRules = MessageRules {
+    opcode: BootpOpcode::BootReply,
+    required: &[],
+    forbidden: &[],
+    allowed: None,
+    ciaddr: None,
+    yiaddr: None,
+    siaddr: None,
+};
+
+impl DhcpMessageType {
+    fn rules(&self) -> &'static MessageRules {
+        match self {
+            Self::DhcpDiscover => &DISCOVER_RULES,
+            Self::DhcpOffer => &OFFER_RULES,
+            Self::DhcpRequest => &REQUEST_RULES,
+            Self::DhcpDecline => &DECLINE_RULES,
+            Self::DhcpAck => &ACK_RULES,
+            Self::DhcpNak => &NAK_RULES,
+            Self::DhcpRelease => &RELEASE_RULES,
+            Self::DhcpInform => &INFORM_RULES,
+            Self::DhcpForceRenew => &FORCE_RENEW_RULES,
+            Self::DhcpLeaseQuery | Self::DhcpBulkLeaseQuery => &LEASEQUERY_RULES,
+            Self::DhcpLeaseUnassigned | Self::DhcpLeaseUnknown | Self::DhcpLeaseActive |
+            Self::DhcpLeaseQueryDone => &LEASEQUERY_REPLY_RULES,
+        }
+    }
+}
+
+impl DhcpPacket {
+    /// Check the packet against the MUST and MUST NOT requirements of RFC 2131
+    /// for its message type. An empty list means the packet is conformant.
+    pub fn validate(&self) -> Vec<DhcpViolation> {
+        let message_type = match self.message_type() {
+            Some(t) => t.clone(),
+            None => return vec![DhcpViolation::MissingMessageType],
+        };
+        let rules = message_type.rules();
+        let mut violations = Vec::new();
+
+        if self.opcode != rules.opcode {
+            violations.push(DhcpViolation::WrongOpcode { message_type: message_type.clone(), opcode: self.opcode });
+        }
+        for option_id in rules.required {
+            if !self.has_option(option_id) {
+                violations.push(DhcpViolation::MissingOption {
+                    message_type: message_type.clone(), option_id: option_id.clone() });
+            }
+        }
+        // Options are kept in a map, sorting makes the order of the violations
+        // the same on every run.
+        let mut present: Vec<_> = self.options.keys().collect();
+        present.sort_by_key(|x| x.code());
+        for option_id in present {
+            let forbidden = rules.forbidden.contains(option_id) ||
+                rules.allowed.map(|x| !x.contains(option_id)).unwrap_or(false);
+            if forbidden {
+                violations.push(DhcpViolation::ForbiddenOption {
+                    message_type: message_type.clone(), option_id: option_id.clone() });
+            }
+        }
+        self.validate_field(&mut violations, &message_type, DhcpField::Ciaddr, self.ciaddr, rules.ciaddr);
+        self.validate_field(&mut violations, &message_type, DhcpField::Yiaddr, self.yiaddr, rules.yiaddr);
+        self.validate_field(&mut violations, &message_type, DhcpField::Siaddr, self.siaddr, rules.siaddr);
+
+        match message_type {
+            DhcpMessageType::DhcpRequest => self.validate_request(&mut violations),
+            DhcpMessageType::DhcpAck => self.validate_ack(&mut violations),
+            _ => (),
+        }
+        violations
+    }
+
+    // Lenient parsing keeps options that failed to decode under their ID, a
+    // server can't use those so they don't satisfy a MUST.
+    fn has_option(&self, option_id: &DhcpOptionID) -> bool {
+        match self.options.get(option_id) {
+            Some(DhcpOption::Malformed(_)) | None => false,
+            Some(_) => true,
+        }
+    }
+
+    fn validate_field(&self, violations: &mut Vec<DhcpViolation>, message_type: &DhcpMessageType,
+                      field: DhcpField, value: Option<Ipv4Addr>, rule: Option<bool>) {
+        match (rule, value) {
+            (Some(false), Some(_)) =>
+                violations.push(DhcpViolation::FieldNotZero { message_type: message_type.clone(), field }),
+            (Some(true), None) =>
+                violations.push(DhcpViolation::FieldZero { message_type: message_type.clone(), field }),
+            _ => (),
+        }
+    }
+
+    // RFC 2131 section 4.3.2: a REQUEST carrying a Server ID is sent from
+    // SELECTING, one with only a Requested IP from INIT-REBOOT and one with
+    // neither from RENEWING or REBINDING.
+    fn validate_request(&self, violations: &mut Vec<DhcpViolation>) {
+        let message_type = DhcpMessageType::DhcpRequest;
+        let server_id = self.has_option(&DhcpOptionID::ServerID);
+        let requested_ip = self.has_option(&DhcpOptionID::RequestedIpAddr);
+
+        if server_id && !requested_ip {
+            violations.push(DhcpViolation::MissingOption {
+                message_type: message_type.clone(), option_id: DhcpOptionID::RequestedIpAddr });
+        }
+        if server_id || requested_ip {
+            self.validate_field(violations, &message_type, DhcpField::Ciaddr, self.ciaddr, Some(false));
+        } else if self.ciaddr.is_none() {
+            violations.push(DhcpViolation::MissingOption {
+                message_type, option_id: DhcpOptionID::RequestedIpAddr });
+        }
+    }
+
+    // RFC 2131 section 4.3.5: an ACK to an INFORM carries no address and
+    // MUST NOT carry a lease time, an ACK to a REQUEST MUST carry both.
+    fn validate_ack(&self, violations: &mut Vec<DhcpViolation>) {
+        let message_type = DhcpMessageType::DhcpAck;
+        let lease_time = self.has_option(&DhcpOptionID::LeaseTime);
+
+        match (self.yiaddr, lease_time) {
+            (Some(_), false) => violations.push(DhcpViolation::MissingOption {
+                message_type, option_id: DhcpOptionID::LeaseTime }),
+            (None, true) => violations.push(DhcpViolation::ForbiddenOption {
+                message_type, option_id: DhcpOptionID::LeaseTime }),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhcp_packet::{DhcpDuration, MacAddr, ParseMode};
+    use alloc::string::ToString;
+
+    fn packet(message_type: DhcpMessageType) -> DhcpPacket {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        packet.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(message_type));
+        packet
+    }
+
+    #[test]
+    fn forbidden_options_in_code_order() {
+        let mut release = packet(DhcpMessageType::DhcpRelease);
+        release.ciaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        release.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        release.options.insert(DhcpOptionID::VendorClassId, DhcpOption::VendorClassId("x".to_string()));
+        release.options.insert(DhcpOptionID::HostName, DhcpOption::HostName("x".to_string()));
+        release.options.insert(DhcpOptionID::RequestedIpAddr,
+                               DhcpOption::RequestedIpAddr(Ipv4Addr::new(192, 0, 2, 10)));
+        let message_type = DhcpMessageType::DhcpRelease;
+        assert_eq!(release.validate(), vec![
+            DhcpViolation::ForbiddenOption { message_type: message_type.clone(), option_id: DhcpOptionID::HostName },
+            DhcpViolation::ForbiddenOption {
+                message_type: message_type.clone(), option_id: DhcpOptionID::RequestedIpAddr },
+            DhcpViolation::ForbiddenOption { message_type, option_id: DhcpOptionID::VendorClassId },
+        ]);
+    }
+
+    #[test]
+    fn malformed_option_counts_as_missing() {
+        let mut decline = packet(DhcpMessageType::DhcpDecline);
+        decline.options.insert(DhcpOptionID::RequestedIpAddr,
+                               DhcpOption::RequestedIpAddr(Ipv4Addr::new(192, 0, 2, 10)));
+        decline.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        let mut buf = Vec::new();
+        decline.encode(&mut buf).unwrap();
+        assert_eq!(decline.validate(), vec![]);
+
+        // Server ID, after the Message Type and Requested IP, cut to 3 bytes.
+        assert_eq!(buf[249..252], [54, 4, 192]);
+        buf[250] = 3;
+        buf.remove(254);
+        let (_, decline) = DhcpPacket::parse_with_mode(&buf, ParseMode::Lenient).unwrap();
+        assert!(matches!(decline.options.get(&DhcpOptionID::ServerID), Some(DhcpOption::Malformed(_))));
+        assert_eq!(decline.validate(), vec![DhcpViolation::MissingOption {
+            message_type: DhcpMessageType::DhcpDecline, option_id: DhcpOptionID::ServerID }]);
+    }
+
+    #[test]
+    fn replies_echo_client_identifier() {
+        let client_id = DhcpOption::ClientIdentifier(vec![1, 2, 0, 0, 0, 0, 1].into());
+        let mut offer = packet(DhcpMessageType::DhcpOffer);
+        offer.opcode = BootpOpcode::BootReply;
+        offer.yiaddr = Some(Ipv4Addr::new(192, 0, 2, 10));
+        offer.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        offer.options.insert(DhcpOptionID::LeaseTime, DhcpOption::LeaseTime(DhcpDuration::new(3600, 0)));
+        offer.options.insert(DhcpOptionID::ClientIdentifier, client_id.clone());
+        assert_eq!(offer.validate(), vec![]);
+
+        let mut ack = offer.clone();
+        ack.options.insert(DhcpOptionID::MsgType, DhcpOption::MessageType(DhcpMessageType::DhcpAck));
+        assert_eq!(ack.validate(), vec![]);
+
+        let mut nak = packet(DhcpMessageType::DhcpNak);
+        nak.opcode = BootpOpcode::BootReply;
+        nak.options.insert(DhcpOptionID::ServerID, DhcpOption::ServerID(Ipv4Addr::new(192, 0, 2, 1)));
+        nak.options.insert(DhcpOptionID::ClientIdentifier, client_id);
+        assert_eq!(nak.validate(), vec![]);
+
+        // The other options a client sends stay forbidden in replies.
+        offer.options.insert(DhcpOptionID::MaxMsgSize, DhcpOption::MaxMsgSize(1500));
+        assert_eq!(offer.validate(), vec![DhcpViolation::ForbiddenOption {
+            message_type: DhcpMessageType::DhcpOffer, option_id: DhcpOptionID::MaxMsgSize }]);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::dhcp_error::DhcpParseError;
+use crate::dhcp_packet::{
+    parse_dhcp_hwarp, parse_flags, BootpOpcode, DhcpMessageType, DhcpOptionID, DhcpPacket, HardwareType, Input,
+    MacAddr, Result, DHCP_MAGIC_COOKIE, DHCP_OPTION_END,
+};
+use core::convert::TryInto;
+use nom::bytes::complete::{tag, take};
+use nom::error::{context, ErrorKind, ParseError};
+use nom::number::complete::{be_u16, be_u32, be_u8};
+use nom::sequence::tuple;
+#[cfg(feature = "std")]
+use std::net::Ipv4Addr;
+#[cfg(not(feature = "std"))]
+use core::net::Ipv4Addr;
+
+// Covers the option sets clients and servers commonly send. Options past it
+// are still validated, lookups for them rescan the buffer.
+const DHCP_VIEW_OPTIONS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhcpRawOption<'a> {
+    pub code: u8,
+    pub value: &'a [u8],
+}
+
+impl<'a> DhcpRawOption<'a> {
+    const EMPTY: DhcpRawOption<'static> = DhcpRawOption { code: 0, value: &[] };
+}
+
+// A packet decoded in place, the options are kept as slices into the buffer
+// so parsing doesn't allocate. The checks are the same as DhcpPacket::parse
+// except for option values, which are only checked when decoded.
+#[derive(Debug, Clone)]
+pub struct DhcpPacketView<'a> {
+    buf: Input<'a>,
+    opcode: BootpOpcode,
+    broadcast: bool,
+    options: [DhcpRawOption<'a>; DHCP_VIEW_OPTIONS],
+    indexed: usize,
+    overflow: Option<Input<'a>>,
+}
+
+// Splits off the next option, skipping Pad. None at End or the end of the buffer.
+pub(crate) fn split_option(buf: Input) -> Result<Option<DhcpRawOption>> {
+    let mut buf = buf;
+    while let Some((&code, rest)) = buf.split_first() {
+        match code {
+            0 => buf = rest,
+            DHCP_OPTION_END => return Ok((rest, None)),
+            _ => {
+                return match rest.split_first() {
+                    Some((&length, value)) if value.len() >= length.into() => {
+                        let (value, rest) = value.split_at(length.into());
+                        Ok((rest, Some(DhcpRawOption { code, value })))
+                    }
+                    _ => Err(nom::Err::Error(DhcpParseError::add_option(buf, code,
+                        DhcpParseError::from_error_kind(rest, ErrorKind::Eof)))),
+                }
+            }
+        }
+    }
+    Ok((buf, None))
+}
+
+// Walks options that were already validated by DhcpPacketView::parse.
+fn raw_options(buf: Input) -> impl Iterator<Item = DhcpRawOption> {
+    let mut buf = buf;
+    core::iter::from_fn(move || {
+        let (rest, option) = split_option(buf).ok()?;
+        buf = rest;
+        option
+    })
+}
+
+fn ipv4(buf: &[u8]) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = buf.try_into().ok()?;
+    Some(Ipv4Addr::from(octets)).filter(|x| !x.is_unspecified())
+}
+
+fn cstring(buf: &[u8]) -> &[u8] {
+    let end = buf.iter().position(|x| *x == 0).unwrap_or(buf.len());
+    &buf[..end]
+}
+
+impl<'a> DhcpPacketView<'a> {
+    pub fn parse(buf: Input<'a>) -> Result<'a, Self> {
+        let header = tuple((context("op", BootpOpcode::parse), context("htype", parse_dhcp_hwarp),
+                            context("hlen", be_u8), context("hops", be_u8),
+                            context("xid", be_u32), context("secs", be_u16),
+                            context("flags", parse_flags),
+                            context("ciaddr", take(4_usize)), context("yiaddr", take(4_usize)),
+                            context("siaddr", take(4_usize)), context("giaddr", take(4_usize)),
+                            context("chaddr", take(16_usize)),
+                            context("sname", take(64_usize)),
+                            context("file", take(128_usize)),
+                            context("magic cookie", tag(DHCP_MAGIC_COOKIE))));
+        let (rest, (opcode, _, _, _, _, _, broadcast, ..)) = header(buf).map_err(|e| Self::locate(e, buf))?;
+        let mut view = DhcpPacketView {
+            buf,
+            opcode,
+            broadcast,
+            options: [DhcpRawOption::EMPTY; DHCP_VIEW_OPTIONS],
+            indexed: 0,
+            overflow: None,
+        };
+        match view.index_options(rest) {
+            Ok((rest, ())) => Ok((rest, view)),
+            Err(e) => Err(Self::locate(e.map(|e| DhcpParseError::add_context(rest, "options", e)), buf)),
+        }
+    }
+
+    fn locate(e: nom::Err<DhcpParseError<'a>>, buf: Input<'a>) -> nom::Err<DhcpParseError<'a>> {
+        match e {
+            nom::Err::Error(e) => nom::Err::Error(e.locate(buf)),
+            nom::Err::Failure(e) => nom::Err::Failure(e.locate(buf)),
+            e => e,
+        }
+    }
+
+    fn index_options(&mut self, buf: Input<'a>) -> Result<'a, ()> {
+        let mut buf = buf;
+        loop {
+            let (rest, option) = split_option(buf)?;
+            let option = match option {
+                Some(option) => option,
+                None => return Ok((rest, ())),
+            };
+            if self.indexed < DHCP_VIEW_OPTIONS {
+                self.options[self.indexed] = option;
+                self.indexed += 1;
+            } else if self.overflow.is_none() {
+                self.overflow = Some(buf);
+            }
+            buf = rest;
+        }
+    }
+
+    pub fn opcode(&self) -> BootpOpcode {
+        self.opcode
+    }
+
+    pub fn htype(&self) -> HardwareType {
+        HardwareType(self.buf[1])
+    }
+
+    pub fn hlen(&self) -> u8 {
+        self.buf[2]
+    }
+
+    pub fn hops(&self) -> u8 {
+        self.buf[3]
+    }
+
+    pub fn xid(&self) -> u32 {
+        u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+    }
+
+    pub fn secs(&self) -> u16 {
+        u16::from_be_bytes([self.buf[8], self.buf[9]])
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    pub fn ciaddr(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.buf[12..16])
+    }
+
+    pub fn yiaddr(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.buf[16..20])
+    }
+
+    pub fn siaddr(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.buf[20..24])
+    }
+
+    pub fn giaddr(&self) -> Option<Ipv4Addr> {
+        ipv4(&self.buf[24..28])
+    }
+
+    pub fn chaddr(&self) -> MacAddr {
+        let mut mac = MacAddr::default();
+        mac.0.copy_from_slice(&self.buf[28..34]);
+        mac
+    }
+
+    pub fn sname(&self) -> &'a [u8] {
+        cstring(&self.buf[44..108])
+    }
+
+    pub fn file(&self) -> &'a [u8] {
+        cstring(&self.buf[108..236])
+    }
+
+    // In packet order, Pad and End are left out.
+    pub fn options(&self) -> impl Iterator<Item = DhcpRawOption<'a>> + '_ {
+        self.options[..self.indexed].iter().copied().chain(self.overflow.into_iter().flat_map(raw_options))
+    }
+
+    // When an option appears more than once the last one wins, like in DhcpPacket.
+    pub fn option(&self, code: u8) -> Option<&'a [u8]> {
+        let overflow = self.overflow.into_iter().flat_map(raw_options).filter(|x| x.code == code).last();
+        overflow.or_else(|| self.options[..self.indexed].iter().rev().find(|x| x.code == code).copied())
+            .map(|x| x.value)
+    }
+
+    pub fn message_type(&self) -> Option<DhcpMessageType> {
+        match self.option(DhcpOptionID::MsgType.code())? {
+            [code] => DhcpMessageType::from_code(*code),
+            _ => None,
+        }
+    }
+
+    // Fully decodes the packet, this allocates.
+    pub fn to_packet(&self) -> Result<'a, DhcpPacket> {
+        DhcpPacket::parse(self.buf)
+    }
+}
+
+// Needs std for the allocator the allocations are counted with.
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    // Counts per thread, the other tests allocate while running in parallel.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|x| x.set(x.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations<F: FnOnce()>(f: F) -> usize {
+        let before = ALLOCATIONS.with(|x| x.get());
+        f();
+        ALLOCATIONS.with(|x| x.get()) - before
+    }
+
+    fn corpus() -> Vec<Vec<u8>> {
+        include_str!("../benches/corpus.hex").lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(|x| (0..x.len()).step_by(2).map(|i| u8::from_str_radix(&x[i..i + 2], 16).unwrap()).collect())
+            .collect()
+    }
+
+    // A DISCOVER with `count` Other options of 2 bytes each.
+    fn packet_with_options(count: u8) -> Vec<u8> {
+        let mut packet = DhcpPacket::new(BootpOpcode::BootRequest, 1, MacAddr::default());
+        packet.options.insert(DhcpOptionID::MsgType,
+                              crate::dhcp_packet::DhcpOption::MessageType(DhcpMessageType::DhcpDiscover));
+        let mut buf = Vec::new();
+        packet.encode(&mut buf).unwrap();
+        let end = buf.iter().rposition(|x| *x == DHCP_OPTION_END).unwrap();
+        buf.truncate(end);
+        for code in 0..count - 1 {
+            buf.extend_from_slice(&[224 + code % 30, 2, code, code]);
+        }
+        buf.push(DHCP_OPTION_END);
+        buf
+    }
+
+    fn inspect(buf: &[u8]) {
+        let (_, view) = DhcpPacketView::parse(buf).unwrap();
+        let found = view.options().count() + view.option(50).map_or(0, |x| x.len());
+        core::hint::black_box((found, view.option(254), view.message_type(), view.chaddr(), view.sname(), view.file()));
+    }
+
+    #[test]
+    fn parse_does_not_allocate() {
+        assert_eq!(allocations(|| drop(core::hint::black_box(Vec::<u8>::with_capacity(8)))), 1);
+        let mut packets = corpus();
+        packets.push(packet_with_options(32));
+        packets.push(packet_with_options(40));
+        for buf in &packets {
+            assert_eq!(allocations(|| inspect(buf)), 0);
+        }
+    }
+
+    #[test]
+    fn options_match_packet() {
+        for buf in corpus().iter().chain(Some(&packet_with_options(40))) {
+            let (_, view) = DhcpPacketView::parse(buf).unwrap();
+            let (_, packet) = DhcpPacket::parse(buf).unwrap();
+            let mut codes: Vec<_> = view.options().map(|x| x.code).collect();
+            codes.sort_unstable();
+            codes.dedup();
+            let mut expected: Vec<_> = packet.options.keys().map(|x| x.code()).collect();
+            expected.sort_unstable();
+            assert_eq!(codes, expected);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// strum_macros emits ::std::fmt paths, point them at core when std is off.
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+extern crate alloc;
+#[macro_use]
+extern crate strum_macros;
+
+pub mod dhcp_diff;
+pub mod dhcp_dissect;
+pub mod dhcp_error;
+pub mod dhcp_filter;
+pub mod dhcp_fingerprint;
+#[cfg(any(feature = "server", feature = "client", feature = "relay", feature = "monitor"))]
+pub mod dhcp_frame;
+pub mod dhcp_packet;
+#[cfg(feature = "monitor")]
+pub mod dhcp_pcap;
+#[cfg(feature = "monitor")]
+pub mod dhcp_track;
+pub mod dhcp_validate;
+pub mod dhcp_view;
@@ -1,19 +1,202 @@
-use std::net::{UdpSocket};
-use dhcp_packet::DhcpPacket;
+mod cli;
 
-extern crate strum;
-#[macro_use]
-extern crate strum_macros;
+use clap::{Parser, Subcommand};
+use cli::client::ClientArgs;
+use cli::craft::CraftArgs;
+use cli::export::{CsvColumn, CSV_DEFAULT_COLUMNS};
+use cli::input::InputFormat;
+use cli::probe::ProbeArgs;
+use cli::record::Recorder;
+use cli::relay::RelayArgs;
+use cli::rogue::RogueArgs;
+use cli::send::SendArgs;
+use cli::serve::ServeArgs;
+use cli::{default_interface, find_interface, CliError, CliResult, Format, Output, Tracking};
+use dhcp_rs::dhcp_frame::{DHCP_CLIENT_PORT, DHCP_SERVER_PORT};
+use dhcp_rs::dhcp_filter::DhcpFilter;
+use dhcp_rs::dhcp_fingerprint::DhcpFingerprintDb;
+use dhcp_rs::dhcp_track::DhcpTracker;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::net::Ipv4Addr;
+use std::process;
+use std::time::Duration;
 
-mod dhcp_packet;
+/// Decode, watch and take part in DHCP exchanges
+#[derive(Debug, Parser)]
+#[command(name = "dhcp-rs", version)]
+struct Cli {
+    /// Network interface to sniff on or take addresses from
+    #[arg(short, long, global = true)]
+    interface: Option<String>,
+    /// UDP port to bind or to send raw frames from, 67 by default or 68 for client, probe and send
+    #[arg(short, long, global = true)]
+    port: Option<u16>,
+    /// How to print packets
+    #[arg(short, long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Columns of the csv format, option<code> for any option such as option82
+    #[arg(long, global = true, value_delimiter = ',', default_value = CSV_DEFAULT_COLUMNS)]
+    columns: Vec<CsvColumn>,
+    /// Also print validation problems, twice for hexdumps
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Only print packets and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Record received and sent packets to a pcapng file
+    #[arg(long, global = true)]
+    record: Option<String>,
+    /// Start a new recording file after this many megabytes
+    #[arg(long, global = true, default_value_t = 100)]
+    record_size: u64,
+    /// How many recording files to keep, the oldest are removed
+    #[arg(long, global = true, default_value_t = 10)]
+    record_files: usize,
+    /// Only handle packets matching an expression such as
+    /// 'msgtype == REQUEST && vendor_class ~ "MSFT" && has(option 82)'
+    #[arg(long, global = true)]
+    filter: Option<String>,
+    /// Group packets into transactions and print their outcome and latency
+    #[arg(long, global = true)]
+    transactions: bool,
+    /// Seconds between transaction statistics, 0 to only print them at the end
+    #[arg(long, global = true, default_value_t = 60)]
+    stats_interval: u64,
+    /// Device signatures to use besides the built-in ones, preferred when both match
+    #[arg(long, global = true, value_name = "FILE")]
+    fingerprints: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the packets sent to a UDP port on this host
+    Listen {
+        /// Local address to bind
+        #[arg(long, default_value = "0.0.0.0")]
+        address: Ipv4Addr,
+    },
+    /// Decode packets given as hex, hex dumps, base64 or raw bytes
+    Decode {
+        /// File to read, - for stdin
+        #[arg(default_value = "-")]
+        file: String,
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+    },
+    /// Compare the packets of two captures or files decode reads
+    Diff {
+        old: String,
+        new: String,
+        /// Format of files that aren't pcap or pcapng captures
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input: InputFormat,
+    },
+    /// Print every DHCP packet seen on an interface without binding a port
+    #[command(alias = "capture")]
+    Sniff,
+    /// Alert on DHCP servers missing from an allow-list
+    Rogue(RogueArgs),
+    /// Print the DHCP packets in a pcap or pcapng file
+    Pcap {
+        file: String,
+    },
+    /// Send packets from a file and print the replies
+    Send(SendArgs),
+    /// Build packets, malformed ones too, from JSON, YAML or flags and send them
+    Craft(CraftArgs),
+    /// Hand out addresses from a range
+    Serve(ServeArgs),
+    /// Forward requests to servers and their replies back to the clients
+    Relay(RelayArgs),
+    /// Ask for a lease and print it
+    Client(ClientArgs),
+    /// List every server answering a DISCOVER or INFORM on the link
+    Probe(ProbeArgs),
+}
+
+fn run(cli: &Cli) -> CliResult {
+    let live = !matches!(cli.command, Command::Decode { .. } | Command::Diff { .. } | Command::Pcap { .. });
+    let recorder = match &cli.record {
+        Some(_) if !live => return Err(CliError::usage("--record only applies to commands that capture or send")),
+        Some(path) => Some(RefCell::new(Recorder::new(path, cli.record_size.saturating_mul(1_000_000), cli.record_files)?)),
+        None => None,
+    };
+    let filter = match &cli.filter {
+        Some(_) if !matches!(cli.command, Command::Listen { .. } | Command::Sniff | Command::Pcap { .. }) =>
+            return Err(CliError::usage("--filter only applies to listen, sniff and pcap")),
+        Some(filter) => Some(DhcpFilter::parse(filter).map_err(|e| CliError::usage(format!("--filter {}", e)))?),
+        None => None,
+    };
+    // Captures are read far faster than the time in them passes, they only
+    // get statistics at the end.
+    let interval = match cli.command {
+        Command::Pcap { .. } => None,
+        _ => Some(Duration::from_secs(cli.stats_interval)).filter(|x| !x.is_zero()),
+    };
+    let tracking = match cli.command {
+        Command::Decode { .. } | Command::Diff { .. } if cli.transactions =>
+            return Err(CliError::usage("--transactions needs packets with timestamps")),
+        _ if cli.transactions => Some(Tracking { tracker: DhcpTracker::new(), interval, last_report: cli::now() }),
+        _ => None,
+    };
+    let mut fingerprints = DhcpFingerprintDb::builtin();
+    if let Some(path) = &cli.fingerprints {
+        let text = fs::read_to_string(path).map_err(|e| CliError::new(format!("{}: {}", path, e)))?;
+        fingerprints.extend(DhcpFingerprintDb::parse(&text).map_err(|e| CliError::new(format!("{}: {}", path, e)))?);
+    }
+    let output = Output {
+        format: cli.format,
+        verbosity: if cli.quiet { -1 } else { cli.verbose.min(i8::MAX as u8) as i8 },
+        recorder,
+        interface: cli.interface.clone().unwrap_or_else(|| "any".to_string()),
+        tracking: tracking.map(RefCell::new),
+        columns: cli.columns.clone(),
+        filter,
+        fingerprints,
+        header_written: Cell::new(false),
+    };
+    let interface = cli.interface.as_deref();
+    let server_port = cli.port.unwrap_or(DHCP_SERVER_PORT);
+    let client_port = cli.port.unwrap_or(DHCP_CLIENT_PORT);
+    let result = match &cli.command {
+        Command::Listen { .. } if interface.is_some() =>
+            Err(CliError::usage("listen binds a UDP port, use `sniff --interface` to watch an interface")),
+        Command::Listen { address } => cli::listen::listen(&output, *address, server_port),
+        Command::Decode { file, input } => cli::decode::decode(&output, file, *input),
+        Command::Diff { old, new, input } => cli::diff::diff(old, new, *input),
+        Command::Sniff => match interface {
+            Some(name) => cli::sniff::sniff(&output, &find_interface(name)?),
+            None => cli::sniff::sniff(&output, &default_interface()?),
+        },
+        Command::Rogue(args) => match interface {
+            Some(name) => cli::rogue::rogue(&output, args, &find_interface(name)?),
+            None => cli::rogue::rogue(&output, args, &default_interface()?),
+        },
+        Command::Pcap { file } => cli::pcap::pcap(&output, file),
+        Command::Send(args) => cli::send::send(&output, args, client_port),
+        Command::Craft(args) => cli::craft::craft(&output, args, interface, client_port),
+        Command::Serve(args) => cli::serve::serve(&output, args, interface, server_port),
+        Command::Relay(args) => cli::relay::relay(&output, args, interface, server_port),
+        Command::Client(args) => cli::client::client(&output, args, interface, client_port),
+        Command::Probe(args) => cli::probe::probe(&output, args, interface, client_port),
+    };
+    // diff writes its own report rather than packets.
+    if !matches!(cli.command, Command::Diff { .. }) {
+        output.finish();
+    }
+    result
+}
 
 fn main() {
-    let socket = UdpSocket::bind("0.0.0.0:67").unwrap();
-    loop {
-        let mut buf = [0; 2048];
-        let (number_of_bytes, src_addr) = socket.recv_from(&mut buf).unwrap();
-        let packet = DhcpPacket::parse(&buf[0..number_of_bytes]);
-        //println!("{:x?} {:?} {:x?}", number_of_bytes, src_addr, &packet);
-        println!("{}\n", packet.unwrap().1);
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("dhcp-rs: {}", e);
+            process::exit(e.code);
+        }
     }
 }